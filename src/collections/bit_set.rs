@@ -0,0 +1,434 @@
+use std::iter::FromIterator;
+use std::marker::PhantomData;
+
+use super::sparse_index::SparseIndex;
+
+const BITS: usize = 64;
+
+/// Largest element index a [BitSet] can hold, which bounds its storage to 32 MiB.
+pub const MAX_INDEX: usize = (1 << 28) - 1;
+
+/// A set type `BitSet<T>` backed by a bit vector, for array index-like element types.
+///
+/// Complements [`SparseSet`](super::SparseSet) for dense universes: memory usage is one bit per
+/// possible element index (up to the largest inserted index), and elements are always iterated
+/// in ascending order. Storage grows automatically when an element beyond current capacity
+/// is inserted, up to [MAX_INDEX].
+///
+/// Set operations work a word (64 elements) at a time. [rank()][Self::rank()],
+/// [select()][Self::select()] and [count_ones()][Self::count_ones()] count bits word by word,
+/// so they are O(capacity / 64).
+pub struct BitSet<T: SparseIndex> {
+    words: Vec<u64>,
+    marker: PhantomData<T>,
+}
+
+impl<T: SparseIndex> BitSet<T> {
+    pub fn new() -> BitSet<T> {
+        BitSet {
+            words: Vec::new(),
+            marker: PhantomData,
+        }
+    }
+
+    /// Creates an empty set able to hold elements with indices below `capacity` without
+    /// reallocating.
+    pub fn with_capacity(capacity: usize) -> BitSet<T> {
+        BitSet {
+            words: vec![0; capacity.div_ceil(BITS)],
+            marker: PhantomData,
+        }
+    }
+
+    /// Returns number of element indices the set can hold without reallocating.
+    pub fn capacity(&self) -> usize {
+        self.words.len() * BITS
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|&w| w == 0)
+    }
+
+    /// Returns number of elements in the set.
+    pub fn count_ones(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    pub fn clear(&mut self) {
+        for w in self.words.iter_mut() {
+            *w = 0;
+        }
+    }
+
+    /// Inserts `value` into the set, returning whether it was newly inserted.
+    ///
+    /// # Panics
+    ///
+    /// Panics if index of `value` is greater than [MAX_INDEX].
+    pub fn insert(&mut self, value: T) -> bool {
+        let index = value.to_index();
+        if index > MAX_INDEX {
+            panic!("index {} out of range of bit set storage", index);
+        }
+        let (w, mask) = (index / BITS, 1 << (index % BITS));
+        if w >= self.words.len() {
+            self.words.resize(w + 1, 0);
+        }
+        let inserted = self.words[w] & mask == 0;
+        self.words[w] |= mask;
+        inserted
+    }
+
+    /// Removes `value` from the set, returning whether it was present.
+    pub fn remove(&mut self, value: &T) -> bool {
+        let index = value.to_index();
+        let (w, mask) = (index / BITS, 1 << (index % BITS));
+        match self.words.get_mut(w) {
+            Some(word) if *word & mask != 0 => {
+                *word &= !mask;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn contains(&self, value: &T) -> bool {
+        let index = value.to_index();
+        match self.words.get(index / BITS) {
+            Some(word) => word & (1 << (index % BITS)) != 0,
+            None => false,
+        }
+    }
+
+    /// Returns number of elements in the set lower than `value`.
+    pub fn rank(&self, value: &T) -> usize {
+        let index = value.to_index();
+        let w = index / BITS;
+        if w >= self.words.len() {
+            self.count_ones()
+        } else {
+            let mask = (1u64 << (index % BITS)) - 1;
+            self.words[..w].iter().map(|w| w.count_ones() as usize).sum::<usize>()
+                + (self.words[w] & mask).count_ones() as usize
+        }
+    }
+
+    /// Returns the `n`-th lowest element of the set (counting from 0), or `None` if the set has
+    /// fewer than `n + 1` elements.
+    pub fn select(&self, n: usize) -> Option<T> {
+        let mut n = n;
+        for (i, &word) in self.words.iter().enumerate() {
+            let ones = word.count_ones() as usize;
+            if n < ones {
+                let mut word = word;
+                for _ in 0..n {
+                    word &= word - 1;
+                }
+                return Some(T::from_index(i * BITS + word.trailing_zeros() as usize));
+            }
+            n -= ones;
+        }
+        None
+    }
+
+    /// Adds all elements of `other` to this set.
+    pub fn union_with(&mut self, other: &BitSet<T>) {
+        if other.words.len() > self.words.len() {
+            self.words.resize(other.words.len(), 0);
+        }
+        for (a, b) in self.words.iter_mut().zip(other.words.iter()) {
+            *a |= *b;
+        }
+    }
+
+    /// Retains only elements also present in `other`.
+    pub fn intersect_with(&mut self, other: &BitSet<T>) {
+        let len = other.words.len();
+        for (i, a) in self.words.iter_mut().enumerate() {
+            *a &= if i < len { other.words[i] } else { 0 };
+        }
+    }
+
+    /// Removes all elements present in `other`.
+    pub fn difference_with(&mut self, other: &BitSet<T>) {
+        for (a, b) in self.words.iter_mut().zip(other.words.iter()) {
+            *a &= !*b;
+        }
+    }
+
+    /// Retains elements present in exactly one of the sets.
+    pub fn symmetric_difference_with(&mut self, other: &BitSet<T>) {
+        if other.words.len() > self.words.len() {
+            self.words.resize(other.words.len(), 0);
+        }
+        for (a, b) in self.words.iter_mut().zip(other.words.iter()) {
+            *a ^= *b;
+        }
+    }
+
+    /// Returns an iterator visiting all elements in ascending order.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            words: &self.words,
+            index: 0,
+            word: self.words.first().cloned().unwrap_or(0),
+            marker: PhantomData,
+        }
+    }
+
+    fn used_words(&self) -> &[u64] {
+        let len = self.words.iter().rposition(|&w| w != 0).map_or(0, |i| i + 1);
+        &self.words[..len]
+    }
+}
+
+impl<T: SparseIndex> Clone for BitSet<T> {
+    fn clone(&self) -> Self {
+        BitSet {
+            words: self.words.clone(),
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T: SparseIndex> Default for BitSet<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: SparseIndex> PartialEq for BitSet<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.used_words() == other.used_words()
+    }
+}
+
+impl<T: SparseIndex> Eq for BitSet<T> {}
+
+impl<T: SparseIndex + std::fmt::Debug> std::fmt::Debug for BitSet<T> {
+    #[inline]
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl<T: SparseIndex> Extend<T> for BitSet<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for e in iter {
+            self.insert(e);
+        }
+    }
+}
+
+impl<T: SparseIndex> FromIterator<T> for BitSet<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut set = BitSet::new();
+        set.extend(iter);
+        set
+    }
+}
+
+impl<'a, T: SparseIndex> IntoIterator for &'a BitSet<T> {
+    type Item = T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An ascending-order iterator over elements of a `BitSet`.
+pub struct Iter<'a, T: 'a> {
+    words: &'a [u64],
+    index: usize,
+    word: u64,
+    marker: PhantomData<T>,
+}
+
+impl<'a, T: SparseIndex> Iterator for Iter<'a, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        while self.word == 0 {
+            self.index += 1;
+            if self.index >= self.words.len() {
+                return None;
+            }
+            self.word = self.words[self.index];
+        }
+        let bit = self.word.trailing_zeros() as usize;
+        self.word &= self.word - 1;
+        Some(T::from_index(self.index * BITS + bit))
+    }
+}
+
+#[cfg(feature = "serde_impl")]
+mod serde {
+    extern crate serde;
+
+    use super::*;
+
+    use self::serde::{de, ser};
+
+    impl<T> ser::Serialize for BitSet<T>
+        where T: SparseIndex + ser::Serialize
+    {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+            where S: ser::Serializer
+        {
+            serializer.collect_seq(self.iter())
+        }
+    }
+
+    impl<'de, T> de::Deserialize<'de> for BitSet<T>
+        where T: SparseIndex + de::Deserialize<'de>
+    {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+            where D: de::Deserializer<'de>
+        {
+            let elems: Vec<T> = Vec::deserialize(deserializer)?;
+            let mut set = BitSet::new();
+            for e in elems {
+                let index = e.to_index();
+                if index > MAX_INDEX {
+                    return Err(de::Error::invalid_value(de::Unexpected::Unsigned(index as u64),
+                        &"an element with index not greater than MAX_INDEX"));
+                }
+                set.insert(e);
+            }
+            Ok(set)
+        }
+    }
+}
+
+#[cfg(feature = "heapsize_impl")]
+mod heapsize {
+    extern crate heapsize;
+
+    use super::*;
+
+    use self::heapsize::HeapSizeOf;
+
+    impl<T: SparseIndex> HeapSizeOf for BitSet<T> {
+        fn heap_size_of_children(&self) -> usize {
+            self.words.heap_size_of_children()
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+
+    fn random_set(seed: &mut u32, count: usize, max: u32) -> (BitSet<u32>, BTreeSet<u32>) {
+        let mut set = BitSet::new();
+        let mut bset = BTreeSet::new();
+        for _ in 0..count {
+            *seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            let v = (*seed >> 8) % max;
+            assert_eq!(set.insert(v), bset.insert(v));
+        }
+        (set, bset)
+    }
+
+    #[test]
+    fn operations_match_btree_set() {
+        let mut seed = 1;
+        let (mut set, mut bset) = random_set(&mut seed, 1000, 2000);
+        for v in (0..2000).step_by(7) {
+            assert_eq!(set.remove(&v), bset.remove(&v));
+        }
+        assert_eq!(set.count_ones(), bset.len());
+        assert_eq!(set.iter().collect::<Vec<_>>(), bset.iter().cloned().collect::<Vec<_>>());
+        for v in 0..2100 {
+            assert_eq!(set.contains(&v), bset.contains(&v));
+        }
+    }
+
+    #[test]
+    fn set_algebra_matches_btree_set() {
+        let mut seed = 2;
+        for _ in 0..10 {
+            let (a, ba) = random_set(&mut seed, 300, 1000);
+            let (b, bb) = random_set(&mut seed, 300, 700);
+
+            let mut s = a.clone();
+            s.union_with(&b);
+            assert!(s.iter().eq(ba.union(&bb).cloned()));
+
+            let mut s = a.clone();
+            s.intersect_with(&b);
+            assert!(s.iter().eq(ba.intersection(&bb).cloned()));
+
+            let mut s = a.clone();
+            s.difference_with(&b);
+            assert!(s.iter().eq(ba.difference(&bb).cloned()));
+
+            let mut s = a.clone();
+            s.symmetric_difference_with(&b);
+            assert!(s.iter().eq(ba.symmetric_difference(&bb).cloned()));
+        }
+    }
+
+    #[test]
+    fn rank_and_select() {
+        let mut seed = 3;
+        let (set, bset) = random_set(&mut seed, 500, 3000);
+        let elems: Vec<u32> = bset.iter().cloned().collect();
+        for (n, e) in elems.iter().enumerate() {
+            assert_eq!(set.select(n), Some(*e));
+            assert_eq!(set.rank(e), n);
+        }
+        assert_eq!(set.select(elems.len()), None);
+        assert_eq!(set.rank(&10000), elems.len());
+    }
+
+    #[test]
+    fn equality_ignores_capacity() {
+        let mut a: BitSet<usize> = BitSet::with_capacity(1000);
+        let b: BitSet<usize> = [1, 64, 65].iter().cloned().collect();
+        a.extend(vec![65, 1, 64]);
+        assert_eq!(a, b);
+        a.remove(&64);
+        assert_ne!(a, b);
+        assert!(BitSet::<usize>::with_capacity(100) == BitSet::new());
+    }
+
+    #[cfg(feature = "serde_impl")]
+    #[test]
+    fn serde_round_trip() {
+        let set: BitSet<u16> = [3, 200, 64].iter().cloned().collect();
+        let json = serde_json::to_string(&set).unwrap();
+        assert_eq!(json, "[3,64,200]");
+        let copy: BitSet<u16> = serde_json::from_str(&json).unwrap();
+        assert_eq!(copy, set);
+    }
+
+    #[test]
+    fn insert_max_index() {
+        let mut set = BitSet::new();
+        assert!(set.insert(MAX_INDEX as u64));
+        assert!(set.contains(&(MAX_INDEX as u64)));
+        assert_eq!(set.capacity(), MAX_INDEX + 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "out of range of bit set storage")]
+    fn insert_beyond_max_index_panics() {
+        let mut set = BitSet::new();
+        set.insert(u64::MAX);
+    }
+
+    #[cfg(feature = "serde_impl")]
+    #[test]
+    fn deserialize_rejects_index_beyond_max_index() {
+        let json = format!("[1,{}]", MAX_INDEX + 1);
+        assert!(serde_json::from_str::<BitSet<usize>>(&json).is_err());
+        let bytes = bincode::serialize(&vec![u32::MAX]).unwrap();
+        assert_eq!(bytes.len(), 12);
+        assert!(bincode::deserialize::<BitSet<u32>>(&bytes).is_err());
+    }
+}
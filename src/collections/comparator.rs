@@ -0,0 +1,92 @@
+use std::cmp::Ordering;
+use std::marker::PhantomData;
+
+/// Total order over values of type `T`, used by ordered collections like
+/// [`OrdSetBy`](super::OrdSetBy) in place of `T`'s natural order.
+///
+/// Implemented for closures `Fn(&T, &T) -> Ordering`. A comparator can implement this trait for
+/// several types, which allows lookups by borrowed forms of the element type, as long as
+/// the order is consistent between them.
+pub trait Comparator<T: ?Sized> {
+    fn compare(&self, a: &T, b: &T) -> Ordering;
+}
+
+impl<T: ?Sized, F> Comparator<T> for F
+    where F: Fn(&T, &T) -> Ordering
+{
+    #[inline]
+    fn compare(&self, a: &T, b: &T) -> Ordering {
+        self(a, b)
+    }
+}
+
+/// Comparator using the natural order of `T`, as defined by its `Ord` implementation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Natural;
+
+impl<T: Ord + ?Sized> Comparator<T> for Natural {
+    #[inline]
+    fn compare(&self, a: &T, b: &T) -> Ordering {
+        a.cmp(b)
+    }
+}
+
+/// Comparator ordering values by a key extracted with function `F`.
+///
+/// ```
+/// use kg_utils::collections::{ByKey, OrdSetBy};
+///
+/// let mut set = OrdSetBy::with_comparator(ByKey::new(|s: &String| s.to_lowercase()));
+/// set.insert("b".to_string());
+/// set.insert("A".to_string());
+/// assert!(set.contains_by_key(&"a".to_string()));
+/// assert_eq!(set.as_slice(), &["A", "b"]);
+/// ```
+pub struct ByKey<F, K> {
+    f: F,
+    marker: PhantomData<fn() -> K>,
+}
+
+impl<F, K> ByKey<F, K> {
+    pub fn new(f: F) -> ByKey<F, K> {
+        ByKey {
+            f,
+            marker: PhantomData,
+        }
+    }
+
+    /// Extracts the key of `value`.
+    #[inline]
+    pub fn key<T: ?Sized>(&self, value: &T) -> K
+        where F: Fn(&T) -> K
+    {
+        (self.f)(value)
+    }
+}
+
+impl<T: ?Sized, F, K> Comparator<T> for ByKey<F, K>
+    where F: Fn(&T) -> K, K: Ord
+{
+    #[inline]
+    fn compare(&self, a: &T, b: &T) -> Ordering {
+        (self.f)(a).cmp(&(self.f)(b))
+    }
+}
+
+impl<F: Clone, K> Clone for ByKey<F, K> {
+    fn clone(&self) -> Self {
+        ByKey::new(self.f.clone())
+    }
+}
+
+impl<F: Default, K> Default for ByKey<F, K> {
+    fn default() -> Self {
+        ByKey::new(F::default())
+    }
+}
+
+impl<F, K> std::fmt::Debug for ByKey<F, K> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("ByKey")
+    }
+}
@@ -1,48 +1,39 @@
 extern crate heapsize;
 
 use std::hash::{BuildHasher, Hash};
+use std::mem::size_of;
 
-use super::{KeyRef, LinkedHashMap, Node};
+use super::{LinkedHashMap, NodeStorage};
 
 use self::heapsize::{heap_size_of, HeapSizeOf};
 
-impl<K> HeapSizeOf for KeyRef<K> {
-    fn heap_size_of_children(&self) -> usize {
-        0
-    }
-}
-
-impl<K, V> HeapSizeOf for Node<K, V>
-    where K: HeapSizeOf,
-          V: HeapSizeOf
-{
-    fn heap_size_of_children(&self) -> usize {
-        self.key.heap_size_of_children() + self.value.heap_size_of_children()
-    }
+/// Returns an approximate size of the allocation of a hashbrown table of `T` with the given
+/// capacity, which hashbrown doesn't expose: a slot and a control byte for each element the table
+/// can hold. The actual allocation is somewhat larger, as the table keeps some of its buckets
+/// empty and pads control bytes to its group width.
+fn table_size_of<T>(capacity: usize) -> usize {
+    capacity * (size_of::<T>() + 1)
 }
 
-impl<K, V, S> HeapSizeOf for LinkedHashMap<K, V, S>
+impl<K, V, S, A> HeapSizeOf for LinkedHashMap<K, V, S, A>
     where K: HeapSizeOf + Hash + Eq,
           V: HeapSizeOf,
-          S: BuildHasher
+          S: BuildHasher,
+          A: NodeStorage<K, V>
 {
     fn heap_size_of_children(&self) -> usize {
         unsafe {
-            let mut size = self.map.heap_size_of_children();
-            for &value in self.map.values() {
-                size += (*value).heap_size_of_children();
-                size += heap_size_of(value as *const _ as *const _);
+            let mut size = table_size_of::<A::Handle>(self.table.capacity());
+            for (k, v) in self.iter() {
+                size += k.heap_size_of_children() + v.heap_size_of_children();
             }
 
-            if !self.head.is_null() {
-                size += heap_size_of(self.head as *const _ as *const _);
-            }
+            size += self.nodes.heap_size_of_nodes(self.head);
 
-            let mut free = self.free;
-            while !free.is_null() {
-                size += heap_size_of(free as *const _ as *const _);
-                free = (*free).next
+            if self.order.nodes.capacity() > 0 {
+                size += heap_size_of(self.order.nodes.as_ptr() as *const _);
             }
+            size += table_size_of::<(A::Handle, usize)>(self.order.slots.capacity());
 
             size
         }
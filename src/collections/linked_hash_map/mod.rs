@@ -33,9 +33,11 @@
 //! let items: Vec<(i32, i32)> = map.iter().map(|t| (*t.0, *t.1)).collect();
 //! assert_eq!(items, [(2, 20), (1, 10), (3, 30)]);
 //! ```
+//!
+//! Nodes of the list are allocated separately by default. [`SlabLinkedHashMap`] has the same API,
+//! but keeps all nodes in a single slab, which improves cache locality of iteration.
 
 #![forbid(missing_docs)]
-#![cfg_attr(all(feature = "nightly", test), feature(test))]
 
 #![cfg_attr(feature = "clippy", feature(plugin))]
 #![cfg_attr(feature = "clippy", plugin(clippy))]
@@ -43,14 +45,16 @@
 
 use std::borrow::Borrow;
 use std::cmp::Ordering;
-use std::collections::hash_map::{self, HashMap};
+use std::collections::hash_map;
 use std::fmt;
 use std::hash::{BuildHasher, Hash, Hasher};
 use std::iter;
 use std::marker;
-use std::mem;
-use std::ops::{Index, IndexMut};
-use std::ptr;
+use std::mem::{self, MaybeUninit};
+use std::ops::{Bound, Index, IndexMut, RangeBounds};
+use std::ptr::{self, NonNull};
+
+use hashbrown::HashTable;
 
 // Optional Serde support
 #[cfg(feature = "serde_impl")]
@@ -59,6 +63,13 @@ pub mod serde;
 #[cfg(feature = "heapsize_impl")]
 mod heapsize;
 
+mod order;
+pub mod slab;
+
+use self::order::OrderTree;
+
+pub use self::slab::{SlabLinkedHashMap, SlabNodes};
+
 /// Creates an `LinkedHashMap` containing the provided key => value pairs.
 ///
 /// # Examples
@@ -85,184 +96,460 @@ macro_rules! linkedhashmap {
     }}
 }
 
-struct KeyRef<K>(*const K);
-
-struct Node<K, V> {
-    next: *mut Node<K, V>,
-    prev: *mut Node<K, V>,
+#[doc(hidden)]
+pub struct Node<K, V, H> {
+    next: H,
+    prev: H,
+    // key and value are uninitialized in the guard node and in free nodes
     key: K,
     value: V,
 }
 
-/// A linked hash map.
-pub struct LinkedHashMap<K, V, S = hash_map::RandomState> {
-    map: HashMap<KeyRef<K>, *mut Node<K, V>, S>,
-    head: *mut Node<K, V>,
-    free: *mut Node<K, V>,
+mod sealed {
+    pub trait Sealed {}
 }
 
-impl<K: Hash> Hash for KeyRef<K> {
-    fn hash<H: Hasher>(&self, state: &mut H) {
-        unsafe { (*self.0).hash(state) }
-    }
+/// Storage of nodes of a [`LinkedHashMap`], selected by its last type parameter.
+///
+/// Nodes are either allocated separately on the heap by [`BoxedNodes`], the default, or kept in
+/// a single `Vec` by [`SlabNodes`]. This trait is sealed, and its items are implementation
+/// details of the map.
+pub trait NodeStorage<K, V>: sealed::Sealed {
+    /// Handle of a node, valid until the node is released.
+    #[doc(hidden)]
+    type Handle: Copy + Eq + Hash;
+
+    #[doc(hidden)]
+    fn with_capacity(capacity: usize) -> Self;
+
+    #[doc(hidden)]
+    fn reserve(&mut self, additional: usize);
+
+    /// Returns a handle of no node, which is never dereferenced.
+    #[doc(hidden)]
+    fn dangling() -> Self::Handle;
+
+    /// Stores a node with the given key and value, reusing a free node if possible. Links of
+    /// the node are left uninitialized.
+    #[doc(hidden)]
+    fn alloc(&mut self, k: K, v: V) -> Self::Handle;
+
+    /// Stores a node with uninitialized contents, to be used as the guard node of the list.
+    #[doc(hidden)]
+    fn alloc_guard(&mut self) -> Self::Handle;
+
+    /// Returns a pointer to the node, valid until the next call of `alloc`, `alloc_guard`,
+    /// `reserve` or `shrink_to_fit`.
+    #[doc(hidden)]
+    fn ptr(&self, node: Self::Handle) -> *mut Node<K, V, Self::Handle>;
+
+    /// Adds the node, whose key and value were moved out or dropped, to the free list.
+    #[doc(hidden)]
+    unsafe fn release(&mut self, node: Self::Handle);
+
+    /// Frees memory of the nodes in the free list. Nodes of the list with the given guard node
+    /// can be moved, in which case the guard handle is updated and `true` is returned.
+    #[doc(hidden)]
+    unsafe fn shrink_to_fit(&mut self, head: Option<&mut Self::Handle>) -> bool;
+
+    /// Returns the size of heap memory used by the nodes, excluding heap memory owned by keys
+    /// and values.
+    #[cfg(feature = "heapsize_impl")]
+    #[doc(hidden)]
+    unsafe fn heap_size_of_nodes(&self, head: Option<Self::Handle>) -> usize;
 }
 
-impl<K: PartialEq> PartialEq for KeyRef<K> {
-    fn eq(&self, other: &Self) -> bool {
-        unsafe{ (*self.0).eq(&*other.0) }
-    }
+/// Pointer to a node allocated by [`BoxedNodes`].
+#[doc(hidden)]
+pub struct NodePtr<K, V>(NonNull<Node<K, V, NodePtr<K, V>>>);
+
+impl<K, V> Clone for NodePtr<K, V> {
+    fn clone(&self) -> Self { *self }
 }
 
-impl<K: Eq> Eq for KeyRef<K> {}
+impl<K, V> Copy for NodePtr<K, V> {}
 
-// This type exists only to support borrowing `KeyRef`s, which cannot be borrowed to `Q` directly
-// due to conflicting implementations of `Borrow`. The layout of `&Qey<Q>` must be identical to
-// `&Q` in order to support transmuting in the `Qey::from_ref` method.
-#[derive(Hash, PartialEq, Eq)]
-struct Qey<Q: ?Sized>(Q);
+impl<K, V> PartialEq for NodePtr<K, V> {
+    fn eq(&self, other: &Self) -> bool { self.0 == other.0 }
+}
+
+impl<K, V> Eq for NodePtr<K, V> {}
+
+impl<K, V> Hash for NodePtr<K, V> {
+    fn hash<H: Hasher>(&self, state: &mut H) { self.0.hash(state) }
+}
 
-impl<Q: ?Sized> Qey<Q> {
-    fn from_ref(q: &Q) -> &Self { unsafe { mem::transmute(q) } }
+/// Node storage allocating every node of a [`LinkedHashMap`] in a separate `Box`. Nodes of
+/// removed entries are kept in a free list and reused by subsequent inserts.
+pub struct BoxedNodes<K, V> {
+    // free nodes linked by `next`, the last one pointing to itself
+    free: Option<NodePtr<K, V>>,
 }
 
-impl<K, Q: ?Sized> Borrow<Qey<Q>> for KeyRef<K> where K: Borrow<Q> {
-    fn borrow(&self) -> &Qey<Q> {
-        Qey::from_ref(unsafe { (*self.0).borrow() })
+impl<K, V> BoxedNodes<K, V> {
+    fn pop_free(&mut self) -> Option<NodePtr<K, V>> {
+        let node = self.free?;
+        let next = unsafe { (*node.0.as_ptr()).next };
+        self.free = if next == node { None } else { Some(next) };
+        Some(node)
+    }
+
+    fn alloc_node(&mut self) -> NodePtr<K, V> {
+        match self.pop_free() {
+            Some(node) => node,
+            None => {
+                let node = Box::into_raw(Box::new(MaybeUninit::<Node<K, V, NodePtr<K, V>>>::uninit()));
+                NodePtr(unsafe { NonNull::new_unchecked(node as *mut _) })
+            }
+        }
     }
 }
 
-impl<K, V> Node<K, V> {
-    fn new(k: K, v: V) -> Self {
-        Node {
-            key: k,
-            value: v,
-            next: ptr::null_mut(),
-            prev: ptr::null_mut(),
+impl<K, V> sealed::Sealed for BoxedNodes<K, V> {}
+
+impl<K, V> NodeStorage<K, V> for BoxedNodes<K, V> {
+    type Handle = NodePtr<K, V>;
+
+    #[inline]
+    fn with_capacity(_capacity: usize) -> Self {
+        BoxedNodes { free: None }
+    }
+
+    #[inline]
+    fn reserve(&mut self, _additional: usize) {}
+
+    #[inline]
+    fn dangling() -> NodePtr<K, V> {
+        NodePtr(NonNull::dangling())
+    }
+
+    #[inline]
+    fn alloc(&mut self, k: K, v: V) -> NodePtr<K, V> {
+        let node = self.alloc_node();
+        unsafe {
+            let p = node.0.as_ptr();
+            ptr::addr_of_mut!((*p).key).write(k);
+            ptr::addr_of_mut!((*p).value).write(v);
+        }
+        node
+    }
+
+    #[inline]
+    fn alloc_guard(&mut self) -> NodePtr<K, V> {
+        self.alloc_node()
+    }
+
+    #[inline]
+    fn ptr(&self, node: NodePtr<K, V>) -> *mut Node<K, V, NodePtr<K, V>> {
+        node.0.as_ptr()
+    }
+
+    #[inline]
+    unsafe fn release(&mut self, node: NodePtr<K, V>) {
+        (*node.0.as_ptr()).next = self.free.unwrap_or(node);
+        self.free = Some(node);
+    }
+
+    unsafe fn shrink_to_fit(&mut self, _head: Option<&mut NodePtr<K, V>>) -> bool {
+        while let Some(node) = self.pop_free() {
+            drop(Box::from_raw(node.0.as_ptr() as *mut MaybeUninit<Node<K, V, NodePtr<K, V>>>));
+        }
+        false
+    }
+
+    #[cfg(feature = "heapsize_impl")]
+    unsafe fn heap_size_of_nodes(&self, head: Option<NodePtr<K, V>>) -> usize {
+        let mut size = 0;
+        if let Some(head) = head {
+            let mut cur = head;
+            loop {
+                size += ::heapsize::heap_size_of(cur.0.as_ptr() as *const _);
+                cur = (*cur.0.as_ptr()).next;
+                if cur == head {
+                    break;
+                }
+            }
         }
+        if let Some(free) = self.free {
+            let mut cur = free;
+            loop {
+                size += ::heapsize::heap_size_of(cur.0.as_ptr() as *const _);
+                let next = (*cur.0.as_ptr()).next;
+                if next == cur {
+                    break;
+                }
+                cur = next;
+            }
+        }
+        size
+    }
+}
+
+impl<K, V> Drop for BoxedNodes<K, V> {
+    fn drop(&mut self) {
+        unsafe { self.shrink_to_fit(None); }
     }
 }
 
-unsafe fn drop_empty_node<K, V>(the_box: *mut Node<K, V>) {
-    // Prevent compiler from trying to drop the un-initialized key and values in the node.
-    let Node { key, value, .. } = *Box::from_raw(the_box);
-    mem::forget(key);
-    mem::forget(value);
+unsafe impl<K: Send, V: Send> Send for BoxedNodes<K, V> {}
+
+unsafe impl<K: Sync, V: Sync> Sync for BoxedNodes<K, V> {}
+
+/// A linked hash map.
+///
+/// Node storage is selected by the `A` type parameter, see [`NodeStorage`].
+///
+/// # Positional operations
+///
+/// Positions of entries are kept in an order-statistic tree outside of the nodes. Maps not using
+/// positional operations don't pay for it: the tree is only built, in O(n log n), once the map
+/// opts in, by the first positional operation taking `&mut self` or by
+/// [reindex()][Self::reindex()].
+///
+/// From then on every change of order keeps the tree up to date, at an expected cost of
+/// O(log n) for each inserted, removed or moved entry, and positional operations are expected
+/// O(log n). Reversing or sorting the map rebuilds the tree. Positional operations taking
+/// `&self` on a map which has not opted in walk the list in O(n).
+pub struct LinkedHashMap<K, V, S = hash_map::RandomState, A: NodeStorage<K, V> = BoxedNodes<K, V>> {
+    // handles of all nodes, except the guard node
+    table: HashTable<A::Handle>,
+    hash_builder: S,
+    nodes: A,
+    // guard node of the circular list, allocated on first insertion
+    head: Option<A::Handle>,
+    // positions of nodes in iteration order, maintained only if `indexed`
+    order: OrderTree<A::Handle>,
+    indexed: bool,
 }
 
 impl<K: Hash + Eq, V> LinkedHashMap<K, V> {
     /// Creates a linked hash map.
-    pub fn new() -> Self { Self::with_map(HashMap::new()) }
+    pub fn new() -> Self { Self::with_parts(0, hash_map::RandomState::new()) }
 
     /// Creates an empty linked hash map with the given initial capacity.
     pub fn with_capacity(capacity: usize) -> Self {
-        Self::with_map(HashMap::with_capacity(capacity))
+        Self::with_parts(capacity, hash_map::RandomState::new())
     }
 }
 
-impl<K, V, S> LinkedHashMap<K, V, S> {
-    #[inline]
-    fn detach(&mut self, node: *mut Node<K, V>) {
-        unsafe {
-            (*(*node).prev).next = (*node).next;
-            (*(*node).next).prev = (*node).prev;
+impl<K: Hash + Eq, V, S: BuildHasher> LinkedHashMap<K, V, S> {
+    /// Creates an empty linked hash map with the given initial hash builder.
+    pub fn with_hasher(hash_builder: S) -> Self {
+        Self::with_parts(0, hash_builder)
+    }
+
+    /// Creates an empty linked hash map with the given initial capacity and hash builder.
+    pub fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> Self {
+        Self::with_parts(capacity, hash_builder)
+    }
+}
+
+impl<K: Hash + Eq, V, A: NodeStorage<K, V>> LinkedHashMap<K, V, hash_map::RandomState, A> {
+    /// Creates a linked hash map with node storage `A`.
+    ///
+    /// Unlike [new()][LinkedHashMap::new()], this is available for every node storage, e.g. for
+    /// [`SlabLinkedHashMap`].
+    pub fn new_in() -> Self { Self::with_parts(0, hash_map::RandomState::new()) }
+
+    /// Creates an empty linked hash map with node storage `A` and the given initial capacity.
+    pub fn with_capacity_in(capacity: usize) -> Self {
+        Self::with_parts(capacity, hash_map::RandomState::new())
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher, A: NodeStorage<K, V>> LinkedHashMap<K, V, S, A> {
+    /// Creates an empty linked hash map with node storage `A` and the given initial hash builder.
+    pub fn with_hasher_in(hash_builder: S) -> Self {
+        Self::with_parts(0, hash_builder)
+    }
+
+    /// Creates an empty linked hash map with node storage `A`, and the given initial capacity and
+    /// hash builder.
+    pub fn with_capacity_and_hasher_in(capacity: usize, hash_builder: S) -> Self {
+        Self::with_parts(capacity, hash_builder)
+    }
+}
+
+impl<K, V, S, A: NodeStorage<K, V>> LinkedHashMap<K, V, S, A> {
+    fn with_parts(capacity: usize, hash_builder: S) -> Self {
+        LinkedHashMap {
+            table: HashTable::with_capacity(capacity),
+            hash_builder,
+            nodes: A::with_capacity(capacity),
+            head: None,
+            order: OrderTree::new(),
+            indexed: false,
         }
     }
 
     #[inline]
-    fn attach(&mut self, node: *mut Node<K, V>) {
-        unsafe {
-            (*node).next = (*self.head).next;
-            (*node).prev = self.head;
-            (*self.head).next = node;
-            (*(*node).next).prev = node;
-        }
+    fn ptr(&self, node: A::Handle) -> *mut Node<K, V, A::Handle> {
+        self.nodes.ptr(node)
     }
 
-    // Caller must check `!self.head.is_null()`
-    unsafe fn drop_entries(&mut self) {
-        let mut cur = (*self.head).next;
-        while cur != self.head {
-            let next = (*cur).next;
-            Box::from_raw(cur);
-            cur = next;
+    #[inline]
+    fn next(&self, node: A::Handle) -> A::Handle {
+        unsafe { (*self.ptr(node)).next }
+    }
+
+    #[inline]
+    fn prev(&self, node: A::Handle) -> A::Handle {
+        unsafe { (*self.ptr(node)).prev }
+    }
+
+    /// Returns the first and the last node, or dangling handles if the guard node is not
+    /// allocated.
+    #[inline]
+    fn ends(&self) -> (A::Handle, A::Handle) {
+        match self.head {
+            Some(head) => (self.next(head), self.prev(head)),
+            None => (A::dangling(), A::dangling()),
         }
     }
 
-    fn clear_free_list(&mut self) {
+    #[inline]
+    fn detach(&mut self, node: A::Handle) {
+        if self.indexed {
+            self.order.remove(&node);
+        }
         unsafe {
-            let mut free = self.free;
-            while ! free.is_null() {
-                let next_free = (*free).next;
-                drop_empty_node(free);
-                free = next_free;
-            }
-            self.free = ptr::null_mut();
+            let n = self.ptr(node);
+            (*self.ptr((*n).prev)).next = (*n).next;
+            (*self.ptr((*n).next)).prev = (*n).prev;
         }
     }
 
-    fn ensure_guard_node(&mut self) {
-        if self.head.is_null() {
-            // allocate the guard node if not present
-            unsafe {
-                self.head = Box::into_raw(Box::new(mem::MaybeUninit::uninit().assume_init()));
-                (*self.head).next = self.head;
-                (*self.head).prev = self.head;
-            }
+    /// Links `node` at the back of the list.
+    #[inline]
+    fn attach(&mut self, node: A::Handle) {
+        let head = self.ensure_guard_node();
+        if self.indexed {
+            self.order.push(node);
+        }
+        unsafe {
+            let n = self.ptr(node);
+            let back = (*self.ptr(head)).prev;
+            (*n).prev = back;
+            (*n).next = head;
+            (*self.ptr(back)).next = node;
+            (*self.ptr(head)).prev = node;
         }
     }
-}
 
-impl<K: Hash + Eq, V, S: BuildHasher> LinkedHashMap<K, V, S> {
-    fn with_map(map: HashMap<KeyRef<K>, *mut Node<K, V>, S>) -> Self {
-        LinkedHashMap {
-            map: map,
-            head: ptr::null_mut(),
-            free: ptr::null_mut(),
+    /// Links `node` immediately before `at` in iteration order.
+    #[inline]
+    fn attach_before(&mut self, node: A::Handle, at: A::Handle) {
+        if self.indexed {
+            let index = if Some(at) == self.head { self.order.len() } else { self.order.rank(&at) };
+            self.order.insert(index, node);
+        }
+        unsafe {
+            let n = self.ptr(node);
+            let prev = (*self.ptr(at)).prev;
+            (*n).prev = prev;
+            (*n).next = at;
+            (*self.ptr(prev)).next = node;
+            (*self.ptr(at)).prev = node;
         }
     }
 
-    /// Creates an empty linked hash map with the given initial hash builder.
-    pub fn with_hasher(hash_builder: S) -> Self {
-        Self::with_map(HashMap::with_hasher(hash_builder))
+    /// Links `node` immediately after `at` in iteration order.
+    #[inline]
+    fn attach_after(&mut self, node: A::Handle, at: A::Handle) {
+        if self.indexed {
+            let index = if Some(at) == self.head { 0 } else { self.order.rank(&at) + 1 };
+            self.order.insert(index, node);
+        }
+        unsafe {
+            let n = self.ptr(node);
+            let next = (*self.ptr(at)).next;
+            (*n).prev = at;
+            (*n).next = next;
+            (*self.ptr(next)).prev = node;
+            (*self.ptr(at)).next = node;
+        }
     }
 
-    /// Creates an empty linked hash map with the given initial capacity and hash builder.
-    pub fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> Self {
-        Self::with_map(HashMap::with_capacity_and_hasher(capacity, hash_builder))
+    /// Moves key and value out of a detached node, and adds the node to the free list.
+    /// Node must already be removed from the hash table.
+    #[inline]
+    unsafe fn recycle_node(&mut self, node: A::Handle) -> (K, V) {
+        let n = self.ptr(node);
+        let entry = (ptr::read(&(*n).key), ptr::read(&(*n).value));
+        self.nodes.release(node);
+        entry
+    }
+
+    /// Drops all entries of the list and releases their nodes, leaving the guard node unlinked.
+    unsafe fn drop_entries(&mut self, head: A::Handle) {
+        let mut cur = self.next(head);
+        while cur != head {
+            let next = self.next(cur);
+            drop(self.recycle_node(cur));
+            cur = next;
+        }
     }
 
-    #[inline]
-    fn attach_at(&mut self, index: usize, node: *mut Node<K, V>) {
-        if index >= self.map.len() {
-            self.attach(node);
-        } else {
-            unsafe {
-                let mut h = (*self.head).prev;
-                for _ in 0..index {
-                    h = (*h).prev;
+    fn ensure_guard_node(&mut self) -> A::Handle {
+        match self.head {
+            Some(head) => head,
+            None => {
+                // allocate the guard node if not present
+                let head = self.nodes.alloc_guard();
+                unsafe {
+                    let h = self.ptr(head);
+                    ptr::addr_of_mut!((*h).next).write(head);
+                    ptr::addr_of_mut!((*h).prev).write(head);
                 }
-                (*node).next = (*h).next;
-                (*node).prev = h;
-                (*h).next = node;
-                (*(*node).next).prev = node;
+                self.head = Some(head);
+                head
             }
         }
     }
+}
 
+impl<K: Hash + Eq, V, S: BuildHasher, A: NodeStorage<K, V>> LinkedHashMap<K, V, S, A> {
     /// Reserves capacity for at least `additional` more elements to be inserted into the map. The
     /// map may reserve more space to avoid frequent allocations.
     ///
     /// # Panics
     ///
     /// Panics if the new allocation size overflows `usize.`
-    pub fn reserve(&mut self, additional: usize) { self.map.reserve(additional); }
+    pub fn reserve(&mut self, additional: usize) {
+        let (nodes, hash_builder) = (&self.nodes, &self.hash_builder);
+        self.table.reserve(additional, |&h| hash_builder.hash_one(unsafe { &(*nodes.ptr(h)).key }));
+        self.nodes.reserve(additional);
+    }
 
     /// Shrinks the capacity of the map as much as possible. It will drop down as much as possible
     /// while maintaining the internal rules and possibly leaving some space in accordance with the
     /// resize policy.
     pub fn shrink_to_fit(&mut self) {
-        self.map.shrink_to_fit();
+        let (nodes, hash_builder) = (&self.nodes, &self.hash_builder);
+        self.table.shrink_to_fit(|&h| hash_builder.hash_one(unsafe { &(*nodes.ptr(h)).key }));
         self.clear_free_list();
+        if !self.indexed {
+            self.order = OrderTree::new();
+        }
+        self.order.shrink_to_fit();
+    }
+
+    /// Releases nodes in the free list. If the storage moves nodes in the process, the hash table
+    /// is rebuilt.
+    fn clear_free_list(&mut self) {
+        if unsafe { self.nodes.shrink_to_fit(self.head.as_mut()) } {
+            let len = self.len();
+            self.table.clear();
+            let mut cur = self.ends().0;
+            for _ in 0..len {
+                let hash = self.hash_key(unsafe { &(*self.ptr(cur)).key });
+                self.insert_handle(hash, cur);
+                cur = self.next(cur);
+            }
+            if self.indexed {
+                self.build_order();
+            }
+        }
     }
 
     /// Gets the given key's corresponding entry in the map for in-place manipulation.
@@ -284,21 +571,126 @@ impl<K: Hash + Eq, V, S: BuildHasher> LinkedHashMap<K, V, S> {
     /// assert_eq!(letters[&'u'], 1);
     /// assert_eq!(letters.get(&'y'), None);
     /// ```
-    pub fn entry(&mut self, k: K) -> Entry<K, V, S> {
-        let self_ptr: *mut Self = self;
-
-        if let Some(entry) = self.map.get_mut(&KeyRef(&k)) {
-            return Entry::Occupied(OccupiedEntry {
-                entry: *entry,
-                map: self_ptr,
+    pub fn entry(&mut self, k: K) -> Entry<'_, K, V, S, A> {
+        let hash = self.hash_key(&k);
+        match self.find_hashed(hash, |q| *q == k) {
+            Some(node) => Entry::Occupied(OccupiedEntry {
+                entry: node,
+                map: self,
+                key: Some(k),
                 marker: marker::PhantomData,
-            });
+            }),
+            None => Entry::Vacant(VacantEntry {
+                key: k,
+                hash,
+                map: self,
+            }),
         }
+    }
 
-        Entry::Vacant(VacantEntry {
-            key: k,
-            map: self,
-        })
+    /// Creates a raw immutable entry builder, for looking up entries by a precomputed hash and
+    /// custom equality.
+    ///
+    /// Hashes must be computed with the map's [hasher()][Self::hasher()], consistently with
+    /// the `Hash` implementation of `K`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::hash::{BuildHasher, Hash, Hasher};
+    /// use kg_utils::collections::LinkedHashMap;
+    ///
+    /// let mut map = LinkedHashMap::new();
+    /// map.insert("a".to_string(), 1);
+    ///
+    /// let mut hasher = map.hasher().build_hasher();
+    /// "a".hash(&mut hasher);
+    /// let hash = hasher.finish();
+    ///
+    /// assert_eq!(map.raw_entry().from_hash(hash, |k| k == "a"), Some((&"a".to_string(), &1)));
+    /// assert_eq!(map.raw_entry().from_key("b"), None);
+    /// ```
+    pub fn raw_entry(&self) -> RawEntryBuilder<'_, K, V, S, A> {
+        RawEntryBuilder { map: self }
+    }
+
+    /// Creates a raw mutable entry builder, for looking up, inserting and removing entries by
+    /// a precomputed hash and custom equality.
+    ///
+    /// Hashes must be computed with the map's [hasher()][Self::hasher()], consistently with
+    /// the `Hash` implementation of `K`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::hash::{BuildHasher, Hash, Hasher};
+    /// use kg_utils::collections::LinkedHashMap;
+    /// use kg_utils::collections::linked_hash_map::RawEntryMut;
+    ///
+    /// let mut map: LinkedHashMap<String, u32> = LinkedHashMap::new();
+    ///
+    /// let mut hasher = map.hasher().build_hasher();
+    /// "a".hash(&mut hasher);
+    /// let hash = hasher.finish();
+    ///
+    /// match map.raw_entry_mut().from_key_hashed_nocheck(hash, "a") {
+    ///     RawEntryMut::Occupied(_) => unreachable!(),
+    ///     RawEntryMut::Vacant(e) => { e.insert_hashed_nocheck(hash, "a".to_string(), 1); }
+    /// }
+    /// match map.raw_entry_mut().from_hash(hash, |k| k == "a") {
+    ///     RawEntryMut::Occupied(mut e) => { *e.get_mut() += 1; }
+    ///     RawEntryMut::Vacant(_) => unreachable!(),
+    /// }
+    /// assert_eq!(map["a"], 2);
+    ///
+    /// if let RawEntryMut::Occupied(e) = map.raw_entry_mut().from_key("a") {
+    ///     assert_eq!(e.remove_entry(), ("a".to_string(), 2));
+    /// }
+    /// assert!(map.is_empty());
+    /// ```
+    pub fn raw_entry_mut(&mut self) -> RawEntryBuilderMut<'_, K, V, S, A> {
+        RawEntryBuilderMut { map: self }
+    }
+
+    #[inline]
+    fn hash_key<Q: ?Sized + Hash>(&self, k: &Q) -> u64 {
+        self.hash_builder.hash_one(k)
+    }
+
+    fn find_hashed<F>(&self, hash: u64, mut is_match: F) -> Option<A::Handle>
+        where F: FnMut(&K) -> bool
+    {
+        let nodes = &self.nodes;
+        self.table
+            .find(hash, |&h| is_match(unsafe { &(*nodes.ptr(h)).key }))
+            .cloned()
+    }
+
+    #[inline]
+    fn find<Q>(&self, k: &Q) -> Option<A::Handle> where K: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        self.find_hashed(self.hash_key(k), |q| q.borrow() == k)
+    }
+
+    /// Adds the handle of a new node to the hash table.
+    fn insert_handle(&mut self, hash: u64, node: A::Handle) {
+        let (nodes, hash_builder) = (&self.nodes, &self.hash_builder);
+        self.table.insert_unique(hash, node, |&h| hash_builder.hash_one(unsafe { &(*nodes.ptr(h)).key }));
+    }
+
+    /// Removes the handle of a node from the hash table.
+    fn remove_handle(&mut self, hash: u64, node: A::Handle) {
+        if let Ok(e) = self.table.find_entry(hash, |&h| h == node) {
+            e.remove();
+        }
+    }
+
+    /// Allocates a node for a key not present in the map, and adds it to the hash table. The node
+    /// is not linked.
+    fn new_node(&mut self, hash: u64, k: K, v: V) -> A::Handle {
+        self.ensure_guard_node();
+        let node = self.nodes.alloc(k, v);
+        self.insert_handle(hash, node);
+        node
     }
 
     /// Returns an iterator visiting all entries in insertion order.
@@ -323,20 +715,109 @@ impl<K: Hash + Eq, V, S: BuildHasher> LinkedHashMap<K, V, S> {
     ///
     /// assert_eq!(&17, map.get(&"a").unwrap());
     /// ```
-    pub fn entries(&mut self) -> Entries<K, V, S> {
-        let head = if ! self.head.is_null() {
-            unsafe { (*self.head).prev }
-        } else {
-            ptr::null_mut()
-        };
+    pub fn entries(&mut self) -> Entries<'_, K, V, S, A> {
+        let (front, _) = self.ends();
         Entries {
-            map: self,
-            head: head,
             remaining: self.len(),
+            map: self,
+            cur: front,
             marker: marker::PhantomData,
         }
     }
 
+    /// Returns a cursor pointing at the first entry of the map, or at the "ghost" position if
+    /// the map is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use kg_utils::collections::LinkedHashMap;
+    ///
+    /// let mut map = LinkedHashMap::new();
+    /// map.insert("a", 1);
+    /// map.insert("c", 3);
+    ///
+    /// let mut cursor = map.cursor_front_mut();
+    /// assert_eq!(cursor.key(), Some(&"a"));
+    /// cursor.insert_after("b", 2);
+    /// cursor.move_next();
+    /// *cursor.value_mut().unwrap() *= 10;
+    ///
+    /// let items: Vec<_> = map.iter().map(|(k, v)| (*k, *v)).collect();
+    /// assert_eq!(items, [("a", 1), ("b", 20), ("c", 3)]);
+    /// ```
+    pub fn cursor_front_mut(&mut self) -> CursorMut<'_, K, V, S, A> {
+        let head = self.ensure_guard_node();
+        CursorMut {
+            cur: self.next(head),
+            head,
+            map: self,
+        }
+    }
+
+    /// Returns a cursor pointing at the last entry of the map, or at the "ghost" position if
+    /// the map is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use kg_utils::collections::LinkedHashMap;
+    /// let mut map = LinkedHashMap::new();
+    /// map.insert("a", 1);
+    /// map.insert("b", 2);
+    ///
+    /// let mut cursor = map.cursor_back_mut();
+    /// assert_eq!(cursor.key(), Some(&"b"));
+    /// cursor.insert_after("c", 3);
+    /// cursor.move_next();
+    /// cursor.move_next();
+    /// assert_eq!(cursor.key(), None);
+    /// cursor.move_next();
+    /// assert_eq!(cursor.remove_current(), Some(("a", 1)));
+    ///
+    /// assert_eq!(map.keys().cloned().collect::<Vec<_>>(), ["b", "c"]);
+    /// ```
+    pub fn cursor_back_mut(&mut self) -> CursorMut<'_, K, V, S, A> {
+        let head = self.ensure_guard_node();
+        CursorMut {
+            cur: self.prev(head),
+            head,
+            map: self,
+        }
+    }
+
+    /// Returns a cursor pointing at the entry with the given key, or `None` if the key is not
+    /// present in the map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use kg_utils::collections::LinkedHashMap;
+    ///
+    /// let mut map = LinkedHashMap::new();
+    /// map.insert(1, "a");
+    /// map.insert(2, "b");
+    /// map.insert(3, "c");
+    ///
+    /// let mut cursor = map.cursor_at_key_mut(&2).unwrap();
+    /// assert_eq!(cursor.remove_current(), Some((2, "b")));
+    /// assert_eq!(cursor.key(), Some(&3));
+    /// cursor.insert_before(4, "d");
+    ///
+    /// assert_eq!(map.keys().cloned().collect::<Vec<_>>(), [1, 4, 3]);
+    /// ```
+    pub fn cursor_at_key_mut<Q>(&mut self, k: &Q) -> Option<CursorMut<'_, K, V, S, A>>
+        where K: Borrow<Q>, Q: ?Sized + Eq + Hash
+    {
+        let node = self.find(k)?;
+        let head = self.ensure_guard_node();
+        Some(CursorMut {
+            cur: node,
+            head,
+            map: self,
+        })
+    }
+
     /// Inserts a key-value pair into the map. If the key already existed, the old value is
     /// returned.
     ///
@@ -352,45 +833,31 @@ impl<K: Hash + Eq, V, S: BuildHasher> LinkedHashMap<K, V, S> {
     /// assert_eq!(map[&2], "b");
     /// ```
     pub fn insert(&mut self, k: K, v: V) -> Option<V> {
-        self.ensure_guard_node();
-
-        let (node, old_val) = match self.map.get(&KeyRef(&k)) {
+        let hash = self.hash_key(&k);
+        match self.find_hashed(hash, |q| *q == k) {
             Some(node) => {
-                let old_val = unsafe { ptr::replace(&mut (**node).value, v) };
-                (*node, Some(old_val))
-            }
-            None => {
-                let node = if self.free.is_null() {
-                    Box::into_raw(Box::new(Node::new(k, v)))
-                } else {
-                    // use a recycled box
-                    unsafe {
-                        let free = self.free;
-                        self.free = (*free).next;
-                        ptr::write(free, Node::new(k, v));
-                        free
-                    }
-                };
-                (node, None)
-            }
-        };
-        match old_val {
-            Some(_) => {
+                let old_val = unsafe { mem::replace(&mut (*self.ptr(node)).value, v) };
                 // Existing node, just update LRU position
                 self.detach(node);
                 self.attach(node);
+                Some(old_val)
             }
             None => {
-                let keyref = unsafe { &(*node).key };
-                self.map.insert(KeyRef(keyref), node);
+                let node = self.new_node(hash, k, v);
                 self.attach(node);
+                None
             }
         }
-        old_val
     }
 
-    /// Inserts a key-value pair into the map. If the key already existed, the old value is
-    /// returned.
+    /// Inserts a key-value pair into the map at the given position. If the key already existed,
+    /// its entry is moved to that position and the old value is returned.
+    ///
+    /// Valid positions for a new key are `0..=len()`, and for an existing key `0..len()`.
+    /// If `index` is out of range, the key-value pair is given back as an error and the map is
+    /// left unchanged.
+    ///
+    /// This operation is expected O(log n), see [positional operations](#positional-operations).
     ///
     /// # Examples
     ///
@@ -400,50 +867,58 @@ impl<K: Hash + Eq, V, S: BuildHasher> LinkedHashMap<K, V, S> {
     ///
     /// map.insert(1, "a");
     /// map.insert(2, "b");
-    /// assert_eq!(map[&1], "a");
-    /// assert_eq!(map[&2], "b");
+    /// assert_eq!(map.insert_at(1, 3, "c"), Ok(None));
+    /// assert_eq!(map.insert_at(0, 2, "d"), Ok(Some("b")));
+    /// assert_eq!(map.insert_at(4, 4, "e"), Err((4, "e")));
+    /// assert_eq!(map.keys().cloned().collect::<Vec<_>>(), [2, 1, 3]);
     /// ```
-    pub fn insert_at(&mut self, index: usize, k: K, v: V) -> Option<V> {
-        self.ensure_guard_node();
-
-        let (node, old_val) = match self.map.get(&KeyRef(&k)) {
+    pub fn insert_at(&mut self, index: usize, k: K, v: V) -> Result<Option<V>, (K, V)> {
+        let len = self.len();
+        let hash = self.hash_key(&k);
+        match self.find_hashed(hash, |q| *q == k) {
             Some(node) => {
-                let old_val = unsafe { ptr::replace(&mut (**node).value, v) };
-                (*node, Some(old_val))
-            }
-            None => {
-                let node = if self.free.is_null() {
-                    Box::into_raw(Box::new(Node::new(k, v)))
-                } else {
-                    // use a recycled box
-                    unsafe {
-                        let free = self.free;
-                        self.free = (*free).next;
-                        ptr::write(free, Node::new(k, v));
-                        free
+                if index >= len {
+                    return Err((k, v));
+                }
+                let old_val = unsafe { mem::replace(&mut (*self.ptr(node)).value, v) };
+                self.reindex();
+                let pos = self.order.rank(&node);
+                if pos != index {
+                    let at = self.order.select(index).unwrap();
+                    self.detach(node);
+                    if pos < index {
+                        self.attach_after(node, at);
+                    } else {
+                        self.attach_before(node, at);
                     }
-                };
-                (node, None)
-            }
-        };
-        match old_val {
-            Some(_) => {
-                // Existing node, just update position
-                self.detach(node);
-                self.attach_at(index, node);
+                }
+                Ok(Some(old_val))
             }
             None => {
-                let keyref = unsafe { &(*node).key };
-                self.map.insert(KeyRef(keyref), node);
-                self.attach_at(index, node);
+                if index > len {
+                    return Err((k, v));
+                }
+                self.insert_new_at(index, hash, k, v);
+                Ok(None)
             }
         }
-        old_val
     }
 
-    /// Checks if the map contains the given key.
+    /// Inserts an entry with a key not present in the map at position `index <= len`.
+    fn insert_new_at(&mut self, index: usize, hash: u64, k: K, v: V) -> A::Handle {
+        self.reindex();
+        let node = self.new_node(hash, k, v);
+        let at = match self.order.select(index) {
+            Some(at) => at,
+            None => self.ensure_guard_node(),
+        };
+        self.attach_before(node, at);
+        node
+    }
+
+    /// Checks if the map contains the given key.
     pub fn contains_key<Q: ?Sized>(&self, k: &Q) -> bool where K: Borrow<Q>, Q: Eq + Hash {
-        self.map.contains_key(Qey::from_ref(k))
+        self.find(k).is_some()
     }
 
     /// Returns the value corresponding to the key in the map.
@@ -463,7 +938,7 @@ impl<K: Hash + Eq, V, S: BuildHasher> LinkedHashMap<K, V, S> {
     /// assert_eq!(map.get(&2), Some(&"c"));
     /// ```
     pub fn get<Q: ?Sized>(&self, k: &Q) -> Option<&V> where K: Borrow<Q>, Q: Eq + Hash {
-        self.map.get(Qey::from_ref(k)).map(|e| unsafe { &(**e).value })
+        self.find(k).map(|node| unsafe { &(*self.ptr(node)).value })
     }
 
     /// Returns the mutable reference corresponding to the key in the map.
@@ -481,7 +956,7 @@ impl<K: Hash + Eq, V, S: BuildHasher> LinkedHashMap<K, V, S> {
     /// assert_eq!(map.get(&1), Some(&"c"));
     /// ```
     pub fn get_mut<Q: ?Sized>(&mut self, k: &Q) -> Option<&mut V> where K: Borrow<Q>, Q: Eq + Hash {
-        self.map.get(Qey::from_ref(k)).map(|e| unsafe { &mut (**e).value })
+        self.find(k).map(|node| unsafe { &mut (*self.ptr(node)).value })
     }
 
     /// Returns the value corresponding to the key in the map.
@@ -504,17 +979,10 @@ impl<K: Hash + Eq, V, S: BuildHasher> LinkedHashMap<K, V, S> {
     /// assert_eq!((&2, &"b"), map.iter().rev().next().unwrap());
     /// ```
     pub fn get_refresh<Q: ?Sized>(&mut self, k: &Q) -> Option<&mut V> where K: Borrow<Q>, Q: Eq + Hash {
-        let (value, node_ptr_opt) = match self.map.get(Qey::from_ref(k)) {
-            None => (None, None),
-            Some(node) => {
-                (Some(unsafe { &mut (**node).value }), Some(*node))
-            }
-        };
-        if let Some(node_ptr) = node_ptr_opt {
-            self.detach(node_ptr);
-            self.attach(node_ptr);
-        }
-        value
+        let node = self.find(k)?;
+        self.detach(node);
+        self.attach(node);
+        Some(unsafe { &mut (*self.ptr(node)).value })
     }
 
     /// Removes and returns the value corresponding to the key from the map.
@@ -533,22 +1001,18 @@ impl<K: Hash + Eq, V, S: BuildHasher> LinkedHashMap<K, V, S> {
     /// assert_eq!(map.len(), 0);
     /// ```
     pub fn remove<Q: ?Sized>(&mut self, k: &Q) -> Option<V> where K: Borrow<Q>, Q: Eq + Hash {
-        let removed = self.map.remove(Qey::from_ref(k));
-        removed.map(|node| {
-            self.detach(node);
-            unsafe {
-                // add to free list
-                (*node).next = self.free;
-                self.free = node;
-                // drop the key and return the value
-                drop(ptr::read(&(*node).key));
-                ptr::read(&(*node).value)
-            }
-        })
+        let hash = self.hash_key(k);
+        let node = self.find_hashed(hash, |q| q.borrow() == k)?;
+        self.remove_handle(hash, node);
+        self.detach(node);
+        // drop the key and return the value
+        Some(unsafe { self.recycle_node(node).1 })
     }
 
     /// Removes and returns the value corresponding to the position in the list.
     ///
+    /// This operation is expected O(log n), see [positional operations](#positional-operations).
+    ///
     /// # Examples
     ///
     /// ```
@@ -561,11 +1025,511 @@ impl<K: Hash + Eq, V, S: BuildHasher> LinkedHashMap<K, V, S> {
     /// assert_eq!(map.contains_key(&2), true);
     /// assert_eq!(map.remove_at(0), Some("a"));
     /// assert_eq!(map.contains_key(&2), false);
+    /// assert_eq!(map.remove_at(1), None);
     /// assert_eq!(map.len(), 1);
     /// ```
     pub fn remove_at(&mut self, index: usize) -> Option<V> {
-        let entry = self.entries().nth(index).expect("index out of bounds");
-        Some(entry.remove())
+        if index >= self.len() {
+            return None;
+        }
+        self.reindex();
+        let node = self.order.select(index)?;
+        unsafe { Some(self.take_node(node).1) }
+    }
+
+    /// Removes the node from the hash table and the list, and adds it to the free list.
+    #[inline]
+    unsafe fn take_node(&mut self, node: A::Handle) -> (K, V) {
+        let hash = self.hash_key(&(*self.ptr(node)).key);
+        self.remove_handle(hash, node);
+        self.detach(node);
+        self.recycle_node(node)
+    }
+
+    /// Retains only the entries for which `f` returns `true`, visiting entries in order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use kg_utils::collections::LinkedHashMap;
+    /// let mut map: LinkedHashMap<i32, i32> = (0..8).map(|i| (i, i * 10)).collect();
+    ///
+    /// map.retain(|&k, v| {
+    ///     *v += 1;
+    ///     k % 3 != 0
+    /// });
+    /// assert_eq!(map.values().cloned().collect::<Vec<_>>(), [11, 21, 41, 51, 71]);
+    /// ```
+    pub fn retain<F>(&mut self, mut f: F) where F: FnMut(&K, &mut V) -> bool {
+        let head = match self.head {
+            Some(head) => head,
+            None => return,
+        };
+        let mut cur = self.next(head);
+        while cur != head {
+            let next = self.next(cur);
+            unsafe {
+                let n = self.ptr(cur);
+                if !f(&(*n).key, &mut (*n).value) {
+                    self.take_node(cur);
+                }
+            }
+            cur = next;
+        }
+    }
+
+    /// Removes all entries from the map, returning them in order as an iterator. Entries not
+    /// consumed by the iterator are removed when it is dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use kg_utils::collections::LinkedHashMap;
+    /// let mut map = LinkedHashMap::new();
+    /// map.insert("a", 1);
+    /// map.insert("b", 2);
+    ///
+    /// assert_eq!(map.drain().collect::<Vec<_>>(), [("a", 1), ("b", 2)]);
+    /// assert!(map.is_empty());
+    /// ```
+    pub fn drain(&mut self) -> Drain<'_, K, V, S, A> {
+        self.drain_range(..)
+    }
+
+    /// Removes entries in the given range of positions from the map, returning them in order
+    /// as an iterator. Entries not consumed by the iterator are removed when it is dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the starting point is greater than the end point, or if the end point is greater
+    /// than the length of the map, like `Vec::drain`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use kg_utils::collections::LinkedHashMap;
+    /// let mut map: LinkedHashMap<i32, i32> = (0..6).map(|i| (i, i)).collect();
+    ///
+    /// assert_eq!(map.drain_range(1..3).map(|e| e.0).collect::<Vec<_>>(), [1, 2]);
+    /// map.drain_range(2..4);
+    /// assert_eq!(map.keys().cloned().collect::<Vec<_>>(), [0, 3]);
+    /// map.drain_range(..=0);
+    /// assert_eq!(map.keys().cloned().collect::<Vec<_>>(), [3]);
+    /// ```
+    pub fn drain_range<R: RangeBounds<usize>>(&mut self, range: R) -> Drain<'_, K, V, S, A> {
+        let len = self.len();
+        let start = match range.start_bound() {
+            Bound::Included(&start) => start,
+            Bound::Excluded(&start) => start.checked_add(1).expect("range start overflows usize"),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&end) => end.checked_add(1).expect("range end overflows usize"),
+            Bound::Excluded(&end) => end,
+            Bound::Unbounded => len,
+        };
+        assert!(start <= end, "range start {} is greater than range end {}", start, end);
+        assert!(end <= len, "range end {} is out of range for map of length {}", end, len);
+        let cur = match self.node_at(start) {
+            Some(node) => node,
+            None => self.ends().0,
+        };
+        Drain {
+            map: self,
+            cur,
+            remaining: end - start,
+        }
+    }
+
+    /// Returns an iterator removing and yielding entries for which `pred` returns `true`,
+    /// visiting entries in order. Entries not visited by the iterator are retained.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use kg_utils::collections::LinkedHashMap;
+    /// let mut map: LinkedHashMap<i32, i32> = (0..6).map(|i| (i, i)).collect();
+    ///
+    /// let odd: Vec<_> = map.extract_if(|k, _| k % 2 == 1).map(|e| e.0).collect();
+    /// assert_eq!(odd, [1, 3, 5]);
+    /// assert_eq!(map.keys().cloned().collect::<Vec<_>>(), [0, 2, 4]);
+    /// ```
+    pub fn extract_if<F>(&mut self, pred: F) -> ExtractIf<'_, K, V, S, A, F>
+        where F: FnMut(&K, &mut V) -> bool
+    {
+        let (front, _) = self.ends();
+        ExtractIf {
+            map: self,
+            cur: front,
+            pred,
+        }
+    }
+
+    /// Moves the entry with the given key to the front of the map. Returns `false` if the key
+    /// is not present.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use kg_utils::collections::LinkedHashMap;
+    /// let mut map = LinkedHashMap::new();
+    /// map.insert(1, "a");
+    /// map.insert(2, "b");
+    /// map.insert(3, "c");
+    ///
+    /// assert!(map.move_to_front(&3));
+    /// assert!(map.move_to_back(&1));
+    /// assert_eq!(map.keys().cloned().collect::<Vec<_>>(), [3, 2, 1]);
+    /// ```
+    pub fn move_to_front<Q>(&mut self, k: &Q) -> bool where K: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        match self.find(k) {
+            Some(node) => {
+                self.detach(node);
+                let head = self.ensure_guard_node();
+                self.attach_after(node, head);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Moves the entry with the given key to the back of the map. Returns `false` if the key
+    /// is not present.
+    pub fn move_to_back<Q>(&mut self, k: &Q) -> bool where K: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        match self.find(k) {
+            Some(node) => {
+                self.detach(node);
+                self.attach(node);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Moves the entry with key `k` immediately before the entry with key `target`. Returns
+    /// `false` if either key is not present.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use kg_utils::collections::LinkedHashMap;
+    /// let mut map = LinkedHashMap::new();
+    /// map.insert(1, "a");
+    /// map.insert(2, "b");
+    /// map.insert(3, "c");
+    ///
+    /// assert!(map.move_before(&3, &1));
+    /// assert_eq!(map.keys().cloned().collect::<Vec<_>>(), [3, 1, 2]);
+    /// assert!(map.move_after(&3, &2));
+    /// assert_eq!(map.keys().cloned().collect::<Vec<_>>(), [1, 2, 3]);
+    /// assert!(!map.move_after(&3, &4));
+    /// ```
+    pub fn move_before<Q>(&mut self, k: &Q, target: &Q) -> bool where K: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        match (self.find(k), self.find(target)) {
+            (Some(node), Some(at)) => {
+                if node != at {
+                    self.detach(node);
+                    self.attach_before(node, at);
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Moves the entry with key `k` immediately after the entry with key `target`. Returns
+    /// `false` if either key is not present.
+    pub fn move_after<Q>(&mut self, k: &Q, target: &Q) -> bool where K: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        match (self.find(k), self.find(target)) {
+            (Some(node), Some(at)) => {
+                if node != at {
+                    self.detach(node);
+                    self.attach_after(node, at);
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Swaps positions of entries with the given keys. Returns `false` if either key is not
+    /// present.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use kg_utils::collections::LinkedHashMap;
+    /// let mut map = LinkedHashMap::new();
+    /// map.insert(1, "a");
+    /// map.insert(2, "b");
+    /// map.insert(3, "c");
+    ///
+    /// assert!(map.swap(&1, &3));
+    /// assert_eq!(map.keys().cloned().collect::<Vec<_>>(), [3, 2, 1]);
+    /// assert!(map.swap(&2, &1));
+    /// assert_eq!(map.keys().cloned().collect::<Vec<_>>(), [3, 1, 2]);
+    /// ```
+    pub fn swap<Q>(&mut self, a: &Q, b: &Q) -> bool where K: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        match (self.find(a), self.find(b)) {
+            (Some(a), Some(b)) => {
+                self.swap_nodes(a, b);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn swap_nodes(&mut self, a: A::Handle, b: A::Handle) {
+        if a != b {
+            let a_next = self.next(a);
+            self.detach(a);
+            self.attach_after(a, b);
+            if a_next != b {
+                self.detach(b);
+                self.attach_before(b, a_next);
+            }
+        }
+    }
+
+    /// Reverses the order of entries in the map.
+    pub fn reverse(&mut self) {
+        let head = match self.head {
+            Some(head) => head,
+            None => return,
+        };
+        let mut cur = head;
+        loop {
+            unsafe {
+                let n = self.ptr(cur);
+                mem::swap(&mut (*n).prev, &mut (*n).next);
+                cur = (*n).next;
+            }
+            if cur == head {
+                break;
+            }
+        }
+        if self.indexed {
+            self.build_order();
+        }
+    }
+
+    /// Sorts entries of the map with the given comparison function. The sort is stable.
+    ///
+    /// Entries are relinked in place, nodes are not reallocated.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use kg_utils::collections::LinkedHashMap;
+    /// let mut map = LinkedHashMap::new();
+    /// map.insert("b", 2);
+    /// map.insert("c", 1);
+    /// map.insert("a", 2);
+    ///
+    /// map.sort_by(|_, v1, _, v2| v1.cmp(v2));
+    /// assert_eq!(map.keys().cloned().collect::<Vec<_>>(), ["c", "b", "a"]);
+    /// map.sort_keys();
+    /// assert_eq!(map.keys().cloned().collect::<Vec<_>>(), ["a", "b", "c"]);
+    /// ```
+    pub fn sort_by<F>(&mut self, mut compare: F)
+        where F: FnMut(&K, &V, &K, &V) -> Ordering
+    {
+        let mut nodes = self.nodes();
+        nodes.sort_by(|&a, &b| unsafe {
+            let (a, b) = (self.ptr(a), self.ptr(b));
+            compare(&(*a).key, &(*a).value, &(*b).key, &(*b).value)
+        });
+        self.relink(&nodes);
+    }
+
+    /// Sorts entries of the map by the key extracted with the given function. The sort is stable.
+    ///
+    /// Entries are relinked in place, nodes are not reallocated.
+    pub fn sort_by_key<T, F>(&mut self, mut f: F)
+        where T: Ord, F: FnMut(&K, &V) -> T
+    {
+        let mut nodes = self.nodes();
+        nodes.sort_by_key(|&n| unsafe {
+            let n = self.ptr(n);
+            f(&(*n).key, &(*n).value)
+        });
+        self.relink(&nodes);
+    }
+
+    /// Sorts entries of the map by their keys.
+    ///
+    /// Entries are relinked in place, nodes are not reallocated.
+    pub fn sort_keys(&mut self) where K: Ord {
+        self.sort_by(|k1, _, k2, _| k1.cmp(k2));
+    }
+
+    /// Returns handles of all nodes in iteration order.
+    fn nodes(&self) -> Vec<A::Handle> {
+        let mut nodes = Vec::with_capacity(self.len());
+        let (mut cur, _) = self.ends();
+        for _ in 0..self.len() {
+            nodes.push(cur);
+            cur = self.next(cur);
+        }
+        nodes
+    }
+
+    /// Links all nodes of the map in the given iteration order.
+    fn relink(&mut self, nodes: &[A::Handle]) {
+        let head = match self.head {
+            Some(head) => head,
+            None => return,
+        };
+        let mut prev = head;
+        for &node in nodes {
+            unsafe {
+                (*self.ptr(prev)).next = node;
+                (*self.ptr(node)).prev = prev;
+            }
+            prev = node;
+        }
+        unsafe {
+            (*self.ptr(prev)).next = head;
+            (*self.ptr(head)).prev = prev;
+        }
+        if self.indexed {
+            self.build_order();
+        }
+    }
+
+    /// Returns the key-value pair at the given position.
+    ///
+    /// This operation is expected O(log n) if the map is indexed, otherwise it walks the list from
+    /// the nearer end.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use kg_utils::collections::LinkedHashMap;
+    /// let mut map = LinkedHashMap::new();
+    ///
+    /// map.insert("a", 1);
+    /// map.insert("b", 2);
+    /// map.insert("c", 3);
+    /// map.reindex();
+    ///
+    /// assert_eq!(map.get_index(1), Some((&"b", &2)));
+    /// assert_eq!(map.get_index(3), None);
+    /// assert_eq!(map.get_index_of("c"), Some(2));
+    /// ```
+    pub fn get_index(&self, index: usize) -> Option<(&K, &V)> {
+        self.node_at(index).map(|node| unsafe {
+            let n = self.ptr(node);
+            (&(*n).key, &(*n).value)
+        })
+    }
+
+    /// Returns the key and a mutable reference to the value at the given position.
+    ///
+    /// Indexes the map if it is not indexed yet, see
+    /// [positional operations](#positional-operations).
+    pub fn get_index_mut(&mut self, index: usize) -> Option<(&K, &mut V)> {
+        self.reindex();
+        self.node_at(index).map(|node| unsafe {
+            let n = self.ptr(node);
+            (&(*n).key, &mut (*n).value)
+        })
+    }
+
+    /// Returns position of the entry with the given key.
+    ///
+    /// This operation is expected O(log n) if the map is indexed, otherwise it walks the list from
+    /// the front.
+    pub fn get_index_of<Q>(&self, k: &Q) -> Option<usize> where K: Borrow<Q>, Q: ?Sized + Eq + Hash {
+        let node = self.find(k)?;
+        if self.indexed {
+            Some(self.order.rank(&node))
+        } else {
+            let (mut cur, _) = self.ends();
+            for pos in 0..self.len() {
+                if cur == node {
+                    return Some(pos);
+                }
+                cur = self.next(cur);
+            }
+            None
+        }
+    }
+
+    /// Swaps positions of entries at the given positions. Returns `false` if either position is
+    /// out of range.
+    ///
+    /// Indexes the map if it is not indexed yet, see
+    /// [positional operations](#positional-operations).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use kg_utils::collections::LinkedHashMap;
+    /// let mut map = LinkedHashMap::new();
+    ///
+    /// map.insert("a", 1);
+    /// map.insert("b", 2);
+    /// map.insert("c", 3);
+    ///
+    /// assert!(map.swap_indices(0, 2));
+    /// assert!(!map.swap_indices(0, 3));
+    /// assert_eq!(map.keys().cloned().collect::<Vec<_>>(), ["c", "b", "a"]);
+    /// ```
+    pub fn swap_indices(&mut self, a: usize, b: usize) -> bool {
+        let len = self.len();
+        if a >= len || b >= len {
+            return false;
+        }
+        self.reindex();
+        let (node_a, node_b) = (self.order.select(a).unwrap(), self.order.select(b).unwrap());
+        self.swap_nodes(node_a, node_b);
+        true
+    }
+
+    /// Indexes the map in O(n log n), if it is not indexed yet. From then on positions of entries
+    /// are kept up to date by all operations, and positional operations taking `&self` are
+    /// expected O(log n) instead of walking the list.
+    ///
+    /// The index is kept outside of the nodes, and is released by
+    /// [shrink_to_fit()][Self::shrink_to_fit()] while the map is not indexed.
+    pub fn reindex(&mut self) {
+        if !self.indexed {
+            self.build_order();
+            self.indexed = true;
+        }
+    }
+
+    /// Rebuilds the order tree from the list.
+    fn build_order(&mut self) {
+        self.order.clear();
+        self.order.reserve(self.len());
+        let (mut cur, _) = self.ends();
+        for _ in 0..self.len() {
+            self.order.push(cur);
+            cur = self.next(cur);
+        }
+    }
+
+    fn node_at(&self, index: usize) -> Option<A::Handle> {
+        let len = self.len();
+        if index >= len {
+            None
+        } else if self.indexed {
+            self.order.select(index)
+        } else if index < len / 2 {
+            let (mut cur, _) = self.ends();
+            for _ in 0..index {
+                cur = self.next(cur);
+            }
+            Some(cur)
+        } else {
+            let (_, mut cur) = self.ends();
+            for _ in index + 1..len {
+                cur = self.prev(cur);
+            }
+            Some(cur)
+        }
     }
 
     /// Returns the maximum number of key-value pairs the map can hold without reallocating.
@@ -579,7 +1543,7 @@ impl<K: Hash + Eq, V, S: BuildHasher> LinkedHashMap<K, V, S> {
     /// assert!(capacity >= 10);
     /// ```
     pub fn capacity(&self) -> usize {
-        self.map.capacity()
+        self.table.capacity()
     }
 
     /// Removes the first entry.
@@ -602,14 +1566,8 @@ impl<K: Hash + Eq, V, S: BuildHasher> LinkedHashMap<K, V, S> {
         if self.is_empty() {
             return None
         }
-        let lru = unsafe { (*self.head).prev };
-        self.detach(lru);
-        self.map
-            .remove(&KeyRef(unsafe { &(*lru).key }))
-            .map(|e| {
-                let e = *unsafe { Box::from_raw(e) };
-                (e.key, e.value)
-            })
+        let (lru, _) = self.ends();
+        Some(unsafe { self.take_node(lru) })
     }
 
     /// Gets the first entry.
@@ -628,10 +1586,11 @@ impl<K: Hash + Eq, V, S: BuildHasher> LinkedHashMap<K, V, S> {
         if self.is_empty() {
             return None
         }
-        let lru = unsafe { (*self.head).prev };
-        self.map
-            .get(&KeyRef(unsafe { &(*lru).key }))
-            .map(|e| unsafe { (&(**e).key, &(**e).value) })
+        let (lru, _) = self.ends();
+        unsafe {
+            let n = self.ptr(lru);
+            Some((&(*n).key, &(*n).value))
+        }
     }
 
     /// Removes the last entry.
@@ -652,14 +1611,8 @@ impl<K: Hash + Eq, V, S: BuildHasher> LinkedHashMap<K, V, S> {
         if self.is_empty() {
             return None
         }
-        let mru = unsafe { (*self.head).next };
-        self.detach(mru);
-        self.map
-            .remove(&KeyRef(unsafe { &(*mru).key }))
-            .map(|e| {
-                let e = *unsafe { Box::from_raw(e) };
-                (e.key, e.value)
-            })
+        let (_, mru) = self.ends();
+        Some(unsafe { self.take_node(mru) })
     }
 
     /// Gets the last entry.
@@ -674,36 +1627,38 @@ impl<K: Hash + Eq, V, S: BuildHasher> LinkedHashMap<K, V, S> {
     /// assert_eq!(map.back(), Some((&2, &20)));
     /// ```
     #[inline]
-    pub fn back(&mut self) -> Option<(&K, &V)> {
+    pub fn back(&self) -> Option<(&K, &V)> {
         if self.is_empty() {
             return None
         }
-        let mru = unsafe { (*self.head).next };
-        self.map
-            .get(&KeyRef(unsafe { &(*mru).key }))
-            .map(|e| unsafe { (&(**e).key, &(**e).value) })
+        let (_, mru) = self.ends();
+        unsafe {
+            let n = self.ptr(mru);
+            Some((&(*n).key, &(*n).value))
+        }
     }
 
     /// Returns the number of key-value pairs in the map.
-    pub fn len(&self) -> usize { self.map.len() }
+    pub fn len(&self) -> usize { self.table.len() }
 
     /// Returns whether the map is currently empty.
     pub fn is_empty(&self) -> bool { self.len() == 0 }
 
     /// Returns a reference to the map's hasher.
     pub fn hasher(&self) -> &S {
-        self.map.hasher()
+        &self.hash_builder
     }
 
     /// Clears the map of all key-value pairs.
     pub fn clear(&mut self) {
-        self.map.clear();
+        self.table.clear();
+        self.order.clear();
         // update the guard node if present
-        if ! self.head.is_null() {
+        if let Some(head) = self.head {
             unsafe {
-                self.drop_entries();
-                (*self.head).prev = self.head;
-                (*self.head).next = self.head;
+                self.drop_entries(head);
+                (*self.ptr(head)).prev = head;
+                (*self.ptr(head)).next = head;
             }
         }
     }
@@ -726,15 +1681,12 @@ impl<K: Hash + Eq, V, S: BuildHasher> LinkedHashMap<K, V, S> {
     /// assert_eq!((&"b", &20), iter.next().unwrap());
     /// assert_eq!(None, iter.next());
     /// ```
-    pub fn iter(&self) -> Iter<K, V> {
-        let head = if self.head.is_null() {
-            ptr::null_mut()
-        } else {
-            unsafe { (*self.head).prev }
-        };
+    pub fn iter(&self) -> Iter<'_, K, V, A> {
+        let (head, tail) = self.ends();
         Iter {
-            head: head,
-            tail: self.head,
+            nodes: &self.nodes,
+            head,
+            tail,
             remaining: self.len(),
             marker: marker::PhantomData,
         }
@@ -760,20 +1712,18 @@ impl<K: Hash + Eq, V, S: BuildHasher> LinkedHashMap<K, V, S> {
     ///
     /// assert_eq!(&17, map.get(&"a").unwrap());
     /// ```
-    pub fn iter_mut(&mut self) -> IterMut<K, V> {
-        let head = if self.head.is_null() {
-            ptr::null_mut()
-        } else {
-            unsafe { (*self.head).prev }
-        };
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V, A> {
+        let (head, tail) = self.ends();
         IterMut {
-            head: head,
-            tail: self.head,
+            nodes: &self.nodes,
+            head,
+            tail,
             remaining: self.len(),
             marker: marker::PhantomData,
         }
     }
 
+
     /// Returns a double-ended iterator visiting all key in order of insertion.
     ///
     /// # Examples
@@ -791,7 +1741,7 @@ impl<K: Hash + Eq, V, S: BuildHasher> LinkedHashMap<K, V, S> {
     /// assert_eq!(&'b', keys.next().unwrap());
     /// assert_eq!(None, keys.next());
     /// ```
-    pub fn keys(&self) -> Keys<K, V> {
+    pub fn keys(&self) -> Keys<'_, K, V, A> {
         Keys { inner: self.iter() }
     }
 
@@ -812,13 +1762,14 @@ impl<K: Hash + Eq, V, S: BuildHasher> LinkedHashMap<K, V, S> {
     /// assert_eq!(&20, values.next().unwrap());
     /// assert_eq!(None, values.next());
     /// ```
-    pub fn values(&self) -> Values<K, V> {
+    pub fn values(&self) -> Values<'_, K, V, A> {
         Values { inner: self.iter() }
     }
 }
 
-impl<'a, K, V, S, Q: ?Sized> Index<&'a Q> for LinkedHashMap<K, V, S>
-    where K: Hash + Eq + Borrow<Q>, S: BuildHasher, Q: Eq + Hash
+
+impl<'a, K, V, S, A, Q: ?Sized> Index<&'a Q> for LinkedHashMap<K, V, S, A>
+    where K: Hash + Eq + Borrow<Q>, S: BuildHasher, Q: Eq + Hash, A: NodeStorage<K, V>
 {
     type Output = V;
 
@@ -827,27 +1778,29 @@ impl<'a, K, V, S, Q: ?Sized> Index<&'a Q> for LinkedHashMap<K, V, S>
     }
 }
 
-impl<'a, K, V, S, Q: ?Sized> IndexMut<&'a Q> for LinkedHashMap<K, V, S>
-    where K: Hash + Eq + Borrow<Q>, S: BuildHasher, Q: Eq + Hash
+impl<'a, K, V, S, A, Q: ?Sized> IndexMut<&'a Q> for LinkedHashMap<K, V, S, A>
+    where K: Hash + Eq + Borrow<Q>, S: BuildHasher, Q: Eq + Hash, A: NodeStorage<K, V>
 {
     fn index_mut(&mut self, index: &'a Q) -> &mut V {
         self.get_mut(index).expect("no entry found for key")
     }
 }
 
-impl<K: Hash + Eq + Clone, V: Clone, S: BuildHasher + Clone> Clone for LinkedHashMap<K, V, S> {
+impl<K, V, S, A> Clone for LinkedHashMap<K, V, S, A>
+    where K: Hash + Eq + Clone, V: Clone, S: BuildHasher + Clone, A: NodeStorage<K, V>
+{
     fn clone(&self) -> Self {
-        let mut map = Self::with_hasher(self.map.hasher().clone());
+        let mut map = Self::with_parts(self.len(), self.hash_builder.clone());
         map.extend(self.iter().map(|(k, v)| (k.clone(), v.clone())));
         map
     }
 }
 
-impl<K: Hash + Eq, V, S: BuildHasher + Default> Default for LinkedHashMap<K, V, S> {
-    fn default() -> Self { Self::with_hasher(S::default()) }
+impl<K: Hash + Eq, V, S: BuildHasher + Default, A: NodeStorage<K, V>> Default for LinkedHashMap<K, V, S, A> {
+    fn default() -> Self { Self::with_parts(0, S::default()) }
 }
 
-impl<K: Hash + Eq, V, S: BuildHasher> Extend<(K, V)> for LinkedHashMap<K, V, S> {
+impl<K: Hash + Eq, V, S: BuildHasher, A: NodeStorage<K, V>> Extend<(K, V)> for LinkedHashMap<K, V, S, A> {
     fn extend<I: IntoIterator<Item=(K, V)>>(&mut self, iter: I) {
         for (k, v) in iter {
             self.insert(k, v);
@@ -855,8 +1808,8 @@ impl<K: Hash + Eq, V, S: BuildHasher> Extend<(K, V)> for LinkedHashMap<K, V, S>
     }
 }
 
-impl<'a, K, V, S> Extend<(&'a K, &'a V)> for LinkedHashMap<K, V, S>
-    where K: 'a + Hash + Eq + Copy, V: 'a + Copy, S: BuildHasher,
+impl<'a, K, V, S, A> Extend<(&'a K, &'a V)> for LinkedHashMap<K, V, S, A>
+    where K: 'a + Hash + Eq + Copy, V: 'a + Copy, S: BuildHasher, A: NodeStorage<K, V>
 {
     fn extend<I: IntoIterator<Item = (&'a K, &'a V)>>(&mut self, iter: I) {
         for (&k, &v) in iter {
@@ -865,31 +1818,37 @@ impl<'a, K, V, S> Extend<(&'a K, &'a V)> for LinkedHashMap<K, V, S>
     }
 }
 
-impl<K: Hash + Eq, V, S: BuildHasher + Default> iter::FromIterator<(K, V)> for LinkedHashMap<K, V, S> {
+impl<K, V, S, A> iter::FromIterator<(K, V)> for LinkedHashMap<K, V, S, A>
+    where K: Hash + Eq, S: BuildHasher + Default, A: NodeStorage<K, V>
+{
     fn from_iter<I: IntoIterator<Item=(K, V)>>(iter: I) -> Self {
         let iter = iter.into_iter();
-        let mut map = Self::with_capacity_and_hasher(iter.size_hint().0, S::default());
+        let mut map = Self::with_parts(iter.size_hint().0, S::default());
         map.extend(iter);
         map
     }
 }
 
-impl<A: fmt::Debug + Hash + Eq, B: fmt::Debug, S: BuildHasher> fmt::Debug for LinkedHashMap<A, B, S> {
+impl<K, V, S, A> fmt::Debug for LinkedHashMap<K, V, S, A>
+    where K: fmt::Debug + Hash + Eq, V: fmt::Debug, S: BuildHasher, A: NodeStorage<K, V>
+{
     /// Returns a string that lists the key-value pairs in insertion order.
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_map().entries(self).finish()
     }
 }
 
-impl<K: Hash + Eq, V: PartialEq, S: BuildHasher> PartialEq for LinkedHashMap<K, V, S> {
+impl<K: Hash + Eq, V: PartialEq, S: BuildHasher, A: NodeStorage<K, V>> PartialEq for LinkedHashMap<K, V, S, A> {
     fn eq(&self, other: &Self) -> bool {
         self.len() == other.len() && self.iter().eq(other)
     }
 }
 
-impl<K: Hash + Eq, V: Eq, S: BuildHasher> Eq for LinkedHashMap<K, V, S> {}
+impl<K: Hash + Eq, V: Eq, S: BuildHasher, A: NodeStorage<K, V>> Eq for LinkedHashMap<K, V, S, A> {}
 
-impl<K: Hash + Eq + PartialOrd, V: PartialOrd, S: BuildHasher> PartialOrd for LinkedHashMap<K, V, S> {
+impl<K, V, S, A> PartialOrd for LinkedHashMap<K, V, S, A>
+    where K: Hash + Eq + PartialOrd, V: PartialOrd, S: BuildHasher, A: NodeStorage<K, V>
+{
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         self.iter().partial_cmp(other)
     }
@@ -911,134 +1870,157 @@ impl<K: Hash + Eq + PartialOrd, V: PartialOrd, S: BuildHasher> PartialOrd for Li
     }
 }
 
-impl<K: Hash + Eq + Ord, V: Ord, S: BuildHasher> Ord for LinkedHashMap<K, V, S> {
+impl<K: Hash + Eq + Ord, V: Ord, S: BuildHasher, A: NodeStorage<K, V>> Ord for LinkedHashMap<K, V, S, A> {
     fn cmp(&self, other: &Self) -> Ordering {
         self.iter().cmp(other)
     }
 }
 
-impl<K: Hash + Eq, V: Hash, S: BuildHasher> Hash for LinkedHashMap<K, V, S> {
+impl<K: Hash + Eq, V: Hash, S: BuildHasher, A: NodeStorage<K, V>> Hash for LinkedHashMap<K, V, S, A> {
     fn hash<H: Hasher>(&self, h: &mut H) { for e in self.iter() { e.hash(h); } }
 }
 
-unsafe impl<K: Send, V: Send, S: Send> Send for LinkedHashMap<K, V, S> {}
+unsafe impl<K: Send, V: Send, S: Send, A: NodeStorage<K, V> + Send> Send for LinkedHashMap<K, V, S, A> {}
 
-unsafe impl<K: Sync, V: Sync, S: Sync> Sync for LinkedHashMap<K, V, S> {}
+unsafe impl<K: Sync, V: Sync, S: Sync, A: NodeStorage<K, V> + Sync> Sync for LinkedHashMap<K, V, S, A> {}
 
-impl<K, V, S> Drop for LinkedHashMap<K, V, S> {
+impl<K, V, S, A: NodeStorage<K, V>> Drop for LinkedHashMap<K, V, S, A> {
     fn drop(&mut self) {
-        if !self.head.is_null() {
+        if let Some(head) = self.head {
             unsafe {
-                self.drop_entries();
-                drop_empty_node(self.head);
+                self.drop_entries(head);
+                self.nodes.release(head);
             }
         }
-        self.clear_free_list();
     }
 }
 
 /// An insertion-order iterator over a `LinkedHashMap`'s entries, with immutable references to the
 /// values.
-pub struct Iter<'a, K: 'a, V: 'a> {
-    head: *const Node<K, V>,
-    tail: *const Node<K, V>,
+pub struct Iter<'a, K: 'a, V: 'a, A: 'a + NodeStorage<K, V> = BoxedNodes<K, V>> {
+    nodes: &'a A,
+    head: A::Handle,
+    tail: A::Handle,
     remaining: usize,
     marker: marker::PhantomData<(&'a K, &'a V)>,
 }
 
 /// An insertion-order iterator over a `LinkedHashMap`'s entries, with mutable references to the
 /// values.
-pub struct IterMut<'a, K: 'a, V: 'a> {
-    head: *mut Node<K, V>,
-    tail: *mut Node<K, V>,
+pub struct IterMut<'a, K: 'a, V: 'a, A: 'a + NodeStorage<K, V> = BoxedNodes<K, V>> {
+    nodes: &'a A,
+    head: A::Handle,
+    tail: A::Handle,
     remaining: usize,
     marker: marker::PhantomData<(&'a K, &'a mut V)>,
 }
 
 /// A consuming insertion-order iterator over a `LinkedHashMap`'s entries.
-pub struct IntoIter<K, V> {
-    head: *mut Node<K, V>,
-    tail: *mut Node<K, V>,
+pub struct IntoIter<K, V, A: NodeStorage<K, V> = BoxedNodes<K, V>> {
+    nodes: A,
+    head: A::Handle,
+    tail: A::Handle,
     remaining: usize,
     marker: marker::PhantomData<(K, V)>,
 }
 
 /// An insertion-order iterator over a `LinkedHashMap`'s entries represented as
 /// an `OccupiedEntry`.
-pub struct Entries<'a, K: 'a, V: 'a, S: 'a = hash_map::RandomState> {
-    map: *mut LinkedHashMap<K, V, S>,
-    head: *mut Node<K, V>,
+pub struct Entries<'a, K: 'a, V: 'a, S: 'a = hash_map::RandomState, A: 'a + NodeStorage<K, V> = BoxedNodes<K, V>> {
+    map: *mut LinkedHashMap<K, V, S, A>,
+    cur: A::Handle,
     remaining: usize,
     marker: marker::PhantomData<(&'a K, &'a mut V, &'a S)>,
 }
 
-unsafe impl<'a, K, V> Send for Iter<'a, K, V> where K: Send, V: Send {}
+unsafe impl<'a, K, V, A> Send for Iter<'a, K, V, A> where K: Send, V: Send, A: NodeStorage<K, V> + Sync {}
+
+unsafe impl<'a, K, V, A> Send for IterMut<'a, K, V, A> where K: Send, V: Send, A: NodeStorage<K, V> + Sync {}
+
+unsafe impl<K, V, A> Send for IntoIter<K, V, A> where K: Send, V: Send, A: NodeStorage<K, V> + Send {}
+
+unsafe impl<'a, K, V, S, A> Send for Entries<'a, K, V, S, A>
+    where K: Send, V: Send, S: Send, A: NodeStorage<K, V> + Send {}
+
+unsafe impl<'a, K, V, A> Sync for Iter<'a, K, V, A> where K: Sync, V: Sync, A: NodeStorage<K, V> + Sync {}
+
+unsafe impl<'a, K, V, A> Sync for IterMut<'a, K, V, A> where K: Sync, V: Sync, A: NodeStorage<K, V> + Sync {}
+
+unsafe impl<K, V, A> Sync for IntoIter<K, V, A> where K: Sync, V: Sync, A: NodeStorage<K, V> + Sync {}
+
+unsafe impl<'a, K, V, S, A> Sync for Entries<'a, K, V, S, A>
+    where K: Sync, V: Sync, S: Sync, A: NodeStorage<K, V> + Sync {}
 
-unsafe impl<'a, K, V> Send for IterMut<'a, K, V> where K: Send, V: Send {}
+unsafe impl<'a, K, V, S, A> Send for CursorMut<'a, K, V, S, A>
+    where K: Send, V: Send, S: Send, A: NodeStorage<K, V> + Send {}
 
-unsafe impl<K, V> Send for IntoIter<K, V> where K: Send, V: Send {}
+unsafe impl<'a, K, V, S, A> Send for RawOccupiedEntryMut<'a, K, V, S, A>
+    where K: Send, V: Send, S: Send, A: NodeStorage<K, V> + Send {}
 
-unsafe impl<'a, K, V, S> Send for Entries<'a, K, V, S> where K: Send, V: Send, S: Send {}
+unsafe impl<'a, K, V, S, A> Send for Drain<'a, K, V, S, A>
+    where K: Hash + Eq + Send, V: Send, S: BuildHasher + Send, A: NodeStorage<K, V> + Send {}
 
-unsafe impl<'a, K, V> Sync for Iter<'a, K, V> where K: Sync, V: Sync {}
+unsafe impl<'a, K, V, S, A, F> Send for ExtractIf<'a, K, V, S, A, F>
+    where K: Send, V: Send, S: Send, A: NodeStorage<K, V> + Send, F: Send {}
 
-unsafe impl<'a, K, V> Sync for IterMut<'a, K, V> where K: Sync, V: Sync {}
+unsafe impl<'a, K, V, S, A> Sync for CursorMut<'a, K, V, S, A>
+    where K: Sync, V: Sync, S: Sync, A: NodeStorage<K, V> + Sync {}
 
-unsafe impl<K, V> Sync for IntoIter<K, V> where K: Sync, V: Sync {}
+unsafe impl<'a, K, V, S, A> Sync for RawOccupiedEntryMut<'a, K, V, S, A>
+    where K: Sync, V: Sync, S: Sync, A: NodeStorage<K, V> + Sync {}
 
-unsafe impl<'a, K, V, S> Sync for Entries<'a, K, V, S> where K: Sync, V: Sync, S: Sync {}
+unsafe impl<'a, K, V, S, A> Sync for Drain<'a, K, V, S, A>
+    where K: Hash + Eq + Sync, V: Sync, S: BuildHasher + Sync, A: NodeStorage<K, V> + Sync {}
 
-impl<'a, K, V> Clone for Iter<'a, K, V> {
+unsafe impl<'a, K, V, S, A, F> Sync for ExtractIf<'a, K, V, S, A, F>
+    where K: Sync, V: Sync, S: Sync, A: NodeStorage<K, V> + Sync, F: Sync {}
+
+impl<'a, K, V, A: NodeStorage<K, V>> Clone for Iter<'a, K, V, A> {
     fn clone(&self) -> Self { Iter { ..*self } }
 }
 
-impl<K, V> Clone for IntoIter<K, V> where K: Clone, V: Clone {
+impl<K, V, A> Clone for IntoIter<K, V, A> where K: Clone, V: Clone, A: NodeStorage<K, V> {
     fn clone(&self) -> Self {
-        if self.remaining == 0 {
-            return IntoIter { ..*self }
-        }
-
-        fn clone_node<K, V>(e: *mut Node<K, V>) -> *mut Node<K, V>
-            where K: Clone, V: Clone,
-        {
-            Box::into_raw(Box::new(Node::new(
-                unsafe { (*e).key.clone() }, unsafe { (*e).value.clone() }
-            )))
-        }
-
+        let mut nodes = A::with_capacity(self.remaining);
+        let (mut head, mut tail) = (A::dangling(), A::dangling());
         let mut cur = self.head;
-        let head = clone_node(cur);
-        let mut tail = head;
-        for _ in 1..self.remaining {
+        for i in 0..self.remaining {
             unsafe {
-                (*tail).prev = clone_node((*cur).prev);
-                (*(*tail).prev).next = tail;
-                tail = (*tail).prev;
-                cur = (*cur).prev;
+                let n = self.nodes.ptr(cur);
+                let node = nodes.alloc((*n).key.clone(), (*n).value.clone());
+                if i == 0 {
+                    head = node;
+                } else {
+                    (*nodes.ptr(tail)).next = node;
+                    (*nodes.ptr(node)).prev = tail;
+                }
+                tail = node;
+                cur = (*n).next;
             }
         }
 
         IntoIter {
-            head: head,
-            tail: tail,
+            nodes,
+            head,
+            tail,
             remaining: self.remaining,
             marker: marker::PhantomData,
         }
     }
 }
 
-impl<'a, K, V> Iterator for Iter<'a, K, V> {
+impl<'a, K, V, A: NodeStorage<K, V>> Iterator for Iter<'a, K, V, A> {
     type Item = (&'a K, &'a V);
 
     fn next(&mut self) -> Option<(&'a K, &'a V)> {
-        if self.head == self.tail {
+        if self.remaining == 0 {
             None
         } else {
             self.remaining -= 1;
             unsafe {
-                let r = Some((&(*self.head).key, &(*self.head).value));
-                self.head = (*self.head).prev;
-                r
+                let n = self.nodes.ptr(self.head);
+                self.head = (*n).next;
+                Some((&(*n).key, &(*n).value))
             }
         }
     }
@@ -1048,18 +2030,18 @@ impl<'a, K, V> Iterator for Iter<'a, K, V> {
     }
 }
 
-impl<'a, K, V> Iterator for IterMut<'a, K, V> {
+impl<'a, K, V, A: NodeStorage<K, V>> Iterator for IterMut<'a, K, V, A> {
     type Item = (&'a K, &'a mut V);
 
     fn next(&mut self) -> Option<(&'a K, &'a mut V)> {
-        if self.head == self.tail {
+        if self.remaining == 0 {
             None
         } else {
             self.remaining -= 1;
             unsafe {
-                let r = Some((&(*self.head).key, &mut (*self.head).value));
-                self.head = (*self.head).prev;
-                r
+                let n = self.nodes.ptr(self.head);
+                self.head = (*n).next;
+                Some((&(*n).key, &mut (*n).value))
             }
         }
     }
@@ -1069,7 +2051,7 @@ impl<'a, K, V> Iterator for IterMut<'a, K, V> {
     }
 }
 
-impl<K, V> Iterator for IntoIter<K, V> {
+impl<K, V, A: NodeStorage<K, V>> Iterator for IntoIter<K, V, A> {
     type Item = (K, V);
 
     fn next(&mut self) -> Option<(K, V)> {
@@ -1078,10 +2060,12 @@ impl<K, V> Iterator for IntoIter<K, V> {
         }
         self.remaining -= 1;
         unsafe {
-            let prev = (*self.head).prev;
-            let e = *Box::from_raw(self.head);
-            self.head = prev;
-            Some((e.key, e.value))
+            let node = self.head;
+            let n = self.nodes.ptr(node);
+            self.head = (*n).next;
+            let e = (ptr::read(&(*n).key), ptr::read(&(*n).value));
+            self.nodes.release(node);
+            Some(e)
         }
     }
 
@@ -1090,24 +2074,22 @@ impl<K, V> Iterator for IntoIter<K, V> {
     }
 }
 
-impl<'a, K, V, S: BuildHasher> Iterator for Entries<'a, K, V, S> {
-    type Item = OccupiedEntry<'a, K, V, S>;
+impl<'a, K, V, S: BuildHasher, A: NodeStorage<K, V>> Iterator for Entries<'a, K, V, S, A> {
+    type Item = OccupiedEntry<'a, K, V, S, A>;
 
-    fn next(&mut self) -> Option<OccupiedEntry<'a, K, V, S>> {
+    fn next(&mut self) -> Option<OccupiedEntry<'a, K, V, S, A>> {
         if self.remaining == 0 {
             None
         } else {
             self.remaining -= 1;
-            unsafe {
-                let r = Some(OccupiedEntry {
-                    map: self.map,
-                    entry: self.head,
-                    marker: marker::PhantomData,
-                });
-
-                self.head = (*self.head).prev;
-                r
-            }
+            let r = OccupiedEntry {
+                map: self.map,
+                entry: self.cur,
+                key: None,
+                marker: marker::PhantomData,
+            };
+            self.cur = unsafe { (*self.map).next(self.cur) };
+            Some(r)
         }
     }
 
@@ -1116,184 +2098,255 @@ impl<'a, K, V, S: BuildHasher> Iterator for Entries<'a, K, V, S> {
     }
 }
 
-impl<'a, K, V> DoubleEndedIterator for Iter<'a, K, V> {
+impl<'a, K, V, A: NodeStorage<K, V>> DoubleEndedIterator for Iter<'a, K, V, A> {
     fn next_back(&mut self) -> Option<(&'a K, &'a V)> {
-        if self.head == self.tail {
+        if self.remaining == 0 {
             None
         } else {
             self.remaining -= 1;
             unsafe {
-                self.tail = (*self.tail).next;
-                Some((&(*self.tail).key, &(*self.tail).value))
+                let n = self.nodes.ptr(self.tail);
+                self.tail = (*n).prev;
+                Some((&(*n).key, &(*n).value))
             }
         }
     }
 }
 
-impl<'a, K, V> DoubleEndedIterator for IterMut<'a, K, V> {
+impl<'a, K, V, A: NodeStorage<K, V>> DoubleEndedIterator for IterMut<'a, K, V, A> {
     fn next_back(&mut self) -> Option<(&'a K, &'a mut V)> {
-        if self.head == self.tail {
+        if self.remaining == 0 {
             None
         } else {
             self.remaining -= 1;
             unsafe {
-                self.tail = (*self.tail).next;
-                Some((&(*self.tail).key, &mut (*self.tail).value))
+                let n = self.nodes.ptr(self.tail);
+                self.tail = (*n).prev;
+                Some((&(*n).key, &mut (*n).value))
             }
         }
     }
 }
 
-impl<K, V> DoubleEndedIterator for IntoIter<K, V> {
+impl<K, V, A: NodeStorage<K, V>> DoubleEndedIterator for IntoIter<K, V, A> {
     fn next_back(&mut self) -> Option<(K, V)> {
         if self.remaining == 0 {
             return None
         }
         self.remaining -= 1;
         unsafe {
-            let next = (*self.tail).next;
-            let e = *Box::from_raw(self.tail);
-            self.tail = next;
-            Some((e.key, e.value))
+            let node = self.tail;
+            let n = self.nodes.ptr(node);
+            self.tail = (*n).prev;
+            let e = (ptr::read(&(*n).key), ptr::read(&(*n).value));
+            self.nodes.release(node);
+            Some(e)
         }
     }
 }
 
-impl<'a, K, V> ExactSizeIterator for Iter<'a, K, V> {
+impl<'a, K, V, A: NodeStorage<K, V>> ExactSizeIterator for Iter<'a, K, V, A> {
     fn len(&self) -> usize { self.remaining }
 }
 
-impl<'a, K, V> ExactSizeIterator for IterMut<'a, K, V> {
+impl<'a, K, V, A: NodeStorage<K, V>> ExactSizeIterator for IterMut<'a, K, V, A> {
     fn len(&self) -> usize { self.remaining }
 }
 
-impl<K, V> ExactSizeIterator for IntoIter<K, V> {
+impl<K, V, A: NodeStorage<K, V>> ExactSizeIterator for IntoIter<K, V, A> {
     fn len(&self) -> usize { self.remaining }
 }
 
-impl<K, V> Drop for IntoIter<K, V> {
+impl<K, V, A: NodeStorage<K, V>> Drop for IntoIter<K, V, A> {
     fn drop(&mut self) {
-        for _ in 0..self.remaining {
-            unsafe {
-                let next = (*self.tail).next;
-                Box::from_raw(self.tail);
-                self.tail = next;
-            }
-        }
+        for _ in self {}
     }
 }
 
 /// An insertion-order iterator over a `LinkedHashMap`'s keys.
-pub struct Keys<'a, K: 'a, V: 'a> {
-    inner: Iter<'a, K, V>,
+pub struct Keys<'a, K: 'a, V: 'a, A: 'a + NodeStorage<K, V> = BoxedNodes<K, V>> {
+    inner: Iter<'a, K, V, A>,
 }
 
-impl<'a, K, V> Clone for Keys<'a, K, V> {
+impl<'a, K, V, A: NodeStorage<K, V>> Clone for Keys<'a, K, V, A> {
     fn clone(&self) -> Self { Keys { inner: self.inner.clone() } }
 }
 
-impl<'a, K, V> Iterator for Keys<'a, K, V> {
+impl<'a, K, V, A: NodeStorage<K, V>> Iterator for Keys<'a, K, V, A> {
     type Item = &'a K;
 
     #[inline] fn next(&mut self) -> Option<&'a K> { self.inner.next().map(|e| e.0) }
     #[inline] fn size_hint(&self) -> (usize, Option<usize>) { self.inner.size_hint() }
 }
 
-impl<'a, K, V> DoubleEndedIterator for Keys<'a, K, V> {
+impl<'a, K, V, A: NodeStorage<K, V>> DoubleEndedIterator for Keys<'a, K, V, A> {
     #[inline] fn next_back(&mut self) -> Option<&'a K> { self.inner.next_back().map(|e| e.0) }
 }
 
-impl<'a, K, V> ExactSizeIterator for Keys<'a, K, V> {
+impl<'a, K, V, A: NodeStorage<K, V>> ExactSizeIterator for Keys<'a, K, V, A> {
     fn len(&self) -> usize { self.inner.len() }
 }
 
 /// An insertion-order iterator over a `LinkedHashMap`'s values.
-pub struct Values<'a, K: 'a, V: 'a> {
-    inner: Iter<'a, K, V>,
+pub struct Values<'a, K: 'a, V: 'a, A: 'a + NodeStorage<K, V> = BoxedNodes<K, V>> {
+    inner: Iter<'a, K, V, A>,
 }
 
-impl<'a, K, V> Clone for Values<'a, K, V> {
+impl<'a, K, V, A: NodeStorage<K, V>> Clone for Values<'a, K, V, A> {
     fn clone(&self) -> Self { Values { inner: self.inner.clone() } }
 }
 
-impl<'a, K, V> Iterator for Values<'a, K, V> {
+impl<'a, K, V, A: NodeStorage<K, V>> Iterator for Values<'a, K, V, A> {
     type Item = &'a V;
 
     #[inline] fn next(&mut self) -> Option<&'a V> { self.inner.next().map(|e| e.1) }
     #[inline] fn size_hint(&self) -> (usize, Option<usize>) { self.inner.size_hint() }
 }
 
-impl<'a, K, V> DoubleEndedIterator for Values<'a, K, V> {
+impl<'a, K, V, A: NodeStorage<K, V>> DoubleEndedIterator for Values<'a, K, V, A> {
     #[inline] fn next_back(&mut self) -> Option<&'a V> { self.inner.next_back().map(|e| e.1) }
 }
 
-impl<'a, K, V> ExactSizeIterator for Values<'a, K, V> {
+impl<'a, K, V, A: NodeStorage<K, V>> ExactSizeIterator for Values<'a, K, V, A> {
     fn len(&self) -> usize { self.inner.len() }
 }
 
-impl<'a, K: Hash + Eq, V, S: BuildHasher> IntoIterator for &'a LinkedHashMap<K, V, S> {
-    type Item = (&'a K, &'a V);
-    type IntoIter = Iter<'a, K, V>;
-    fn into_iter(self) -> Iter<'a, K, V> { self.iter() }
+/// A draining insertion-order iterator over a range of `LinkedHashMap`'s entries.
+pub struct Drain<'a, K, V, S = hash_map::RandomState, A = BoxedNodes<K, V>>
+    where K: 'a + Hash + Eq, V: 'a, S: 'a + BuildHasher, A: 'a + NodeStorage<K, V>
+{
+    map: &'a mut LinkedHashMap<K, V, S, A>,
+    cur: A::Handle,
+    remaining: usize,
 }
 
-impl<'a, K: Hash + Eq, V, S: BuildHasher> IntoIterator for &'a mut LinkedHashMap<K, V, S> {
-    type Item = (&'a K, &'a mut V);
-    type IntoIter = IterMut<'a, K, V>;
-    fn into_iter(self) -> IterMut<'a, K, V> { self.iter_mut() }
-}
-
-impl<K: Hash + Eq, V, S: BuildHasher> IntoIterator for LinkedHashMap<K, V, S> {
+impl<'a, K: Hash + Eq, V, S: BuildHasher, A: NodeStorage<K, V>> Iterator for Drain<'a, K, V, S, A> {
     type Item = (K, V);
-    type IntoIter = IntoIter<K, V>;
-    fn into_iter(mut self) -> IntoIter<K, V> {
-        let (head, tail) = if !self.head.is_null() {
-            unsafe { ((*self.head).prev, (*self.head).next) }
+
+    fn next(&mut self) -> Option<(K, V)> {
+        if self.remaining == 0 {
+            None
         } else {
-            (ptr::null_mut(), ptr::null_mut())
-        };
-        let len = self.len();
+            self.remaining -= 1;
+            let node = self.cur;
+            self.cur = self.map.next(node);
+            Some(unsafe { self.map.take_node(node) })
+        }
+    }
 
-        if !self.head.is_null() {
-            unsafe { drop_empty_node(self.head) }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, K: Hash + Eq, V, S: BuildHasher, A: NodeStorage<K, V>> ExactSizeIterator for Drain<'a, K, V, S, A> {
+    fn len(&self) -> usize { self.remaining }
+}
+
+impl<'a, K: Hash + Eq, V, S: BuildHasher, A: NodeStorage<K, V>> Drop for Drain<'a, K, V, S, A> {
+    fn drop(&mut self) {
+        for _ in self {}
+    }
+}
+
+/// An insertion-order iterator removing `LinkedHashMap`'s entries matching a predicate.
+pub struct ExtractIf<'a, K: 'a, V: 'a, S: 'a, A: 'a + NodeStorage<K, V>, F> {
+    map: &'a mut LinkedHashMap<K, V, S, A>,
+    cur: A::Handle,
+    pred: F,
+}
+
+impl<'a, K, V, S, A, F> Iterator for ExtractIf<'a, K, V, S, A, F>
+    where K: Hash + Eq, S: BuildHasher, A: NodeStorage<K, V>, F: FnMut(&K, &mut V) -> bool
+{
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<(K, V)> {
+        let head = self.map.head?;
+        while self.cur != head {
+            let node = self.cur;
+            self.cur = self.map.next(node);
+            unsafe {
+                let n = self.map.ptr(node);
+                if (self.pred)(&(*n).key, &mut (*n).value) {
+                    return Some(self.map.take_node(node));
+                }
+            }
         }
-        self.clear_free_list();
-        // drop the HashMap but not the LinkedHashMap
-        let map = mem::MaybeUninit::uninit();
-        self.map = unsafe { map.assume_init() };
-        mem::forget(self);
+        None
+    }
 
-        IntoIter {
-            head: head,
-            tail: tail,
-            remaining: len,
-            marker: marker::PhantomData,
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.map.len()))
+    }
+}
+
+impl<'a, K: Hash + Eq, V, S: BuildHasher, A: NodeStorage<K, V>> IntoIterator for &'a LinkedHashMap<K, V, S, A> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V, A>;
+    fn into_iter(self) -> Iter<'a, K, V, A> { self.iter() }
+}
+
+impl<'a, K: Hash + Eq, V, S: BuildHasher, A: NodeStorage<K, V>> IntoIterator for &'a mut LinkedHashMap<K, V, S, A> {
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = IterMut<'a, K, V, A>;
+    fn into_iter(self) -> IterMut<'a, K, V, A> { self.iter_mut() }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher, A: NodeStorage<K, V>> IntoIterator for LinkedHashMap<K, V, S, A> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V, A>;
+    fn into_iter(mut self) -> IntoIter<K, V, A> {
+        let (head, tail) = self.ends();
+        let len = self.len();
+
+        unsafe {
+            if let Some(guard) = self.head {
+                self.nodes.release(guard);
+            }
+            let nodes = ptr::read(&self.nodes);
+            // drop the remaining fields but not the LinkedHashMap
+            ptr::drop_in_place(&mut self.table);
+            ptr::drop_in_place(&mut self.hash_builder);
+            ptr::drop_in_place(&mut self.order);
+            mem::forget(self);
+
+            IntoIter {
+                nodes,
+                head,
+                tail,
+                remaining: len,
+                marker: marker::PhantomData,
+            }
         }
     }
 }
 
 /// A view into a single location in a map, which may be vacant or occupied.
-pub enum Entry<'a, K: 'a, V: 'a, S: 'a = hash_map::RandomState> {
+pub enum Entry<'a, K: 'a, V: 'a, S: 'a = hash_map::RandomState, A: 'a + NodeStorage<K, V> = BoxedNodes<K, V>> {
     /// An occupied Entry.
-    Occupied(OccupiedEntry<'a, K, V, S>),
+    Occupied(OccupiedEntry<'a, K, V, S, A>),
     /// A vacant Entry.
-    Vacant(VacantEntry<'a, K, V, S>),
+    Vacant(VacantEntry<'a, K, V, S, A>),
 }
 
 /// A view into a single occupied location in a `LinkedHashMap`.
-pub struct OccupiedEntry<'a, K: 'a, V: 'a, S: 'a = hash_map::RandomState> {
-    entry: *mut Node<K, V>,
-    map: *mut LinkedHashMap<K, V, S>,
+pub struct OccupiedEntry<'a, K: 'a, V: 'a, S: 'a = hash_map::RandomState, A: 'a + NodeStorage<K, V> = BoxedNodes<K, V>> {
+    entry: A::Handle,
+    map: *mut LinkedHashMap<K, V, S, A>,
+    // key passed to `LinkedHashMap::entry`, used by `replace_key`
+    key: Option<K>,
     marker: marker::PhantomData<&'a K>,
 }
 
 /// A view into a single empty location in a `LinkedHashMap`.
-pub struct VacantEntry<'a, K: 'a, V: 'a, S: 'a = hash_map::RandomState> {
+pub struct VacantEntry<'a, K: 'a, V: 'a, S: 'a = hash_map::RandomState, A: 'a + NodeStorage<K, V> = BoxedNodes<K, V>> {
     key: K,
-    map: &'a mut LinkedHashMap<K, V, S>,
+    hash: u64,
+    map: &'a mut LinkedHashMap<K, V, S, A>,
 }
 
-impl<'a, K: Hash + Eq, V, S: BuildHasher> Entry<'a, K, V, S> {
+impl<'a, K: Hash + Eq, V, S: BuildHasher, A: NodeStorage<K, V>> Entry<'a, K, V, S, A> {
     /// Returns the entry key
     ///
     /// # Examples
@@ -1329,9 +2382,78 @@ impl<'a, K: Hash + Eq, V, S: BuildHasher> Entry<'a, K, V, S> {
             Entry::Vacant(entry) => entry.insert(default()),
         }
     }
+
+    /// Ensures a value is in the entry by inserting the result of the default function, called
+    /// with the entry key, if empty, and returns a mutable reference to the value in the entry.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use kg_utils::collections::LinkedHashMap;
+    ///
+    /// let mut map = LinkedHashMap::new();
+    ///
+    /// map.entry("poneyland").or_insert_with_key(|k| k.len());
+    /// assert_eq!(map["poneyland"], 9);
+    /// ```
+    pub fn or_insert_with_key<F: FnOnce(&K) -> V>(self, default: F) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let value = default(entry.key());
+                entry.insert(value)
+            }
+        }
+    }
+
+    /// Ensures a value is in the entry by inserting the default value if empty, and returns
+    /// a mutable reference to the value in the entry.
+    pub fn or_default(self) -> &'a mut V where V: Default {
+        self.or_insert_with(V::default)
+    }
+
+    /// Provides in-place mutable access to an occupied entry before any potential inserts into
+    /// the map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use kg_utils::collections::LinkedHashMap;
+    ///
+    /// let mut map = LinkedHashMap::new();
+    ///
+    /// map.entry("poneyland").and_modify(|e| *e += 1).or_insert(42);
+    /// assert_eq!(map["poneyland"], 42);
+    /// map.entry("poneyland").and_modify(|e| *e += 1).or_insert(42);
+    /// assert_eq!(map["poneyland"], 43);
+    /// ```
+    pub fn and_modify<F: FnOnce(&mut V)>(mut self, f: F) -> Self {
+        if let Entry::Occupied(ref mut entry) = self {
+            f(entry.get_mut());
+        }
+        self
+    }
+
+    /// Sets the value of the entry, and returns an `OccupiedEntry`. An existing entry is moved
+    /// to the back of the map, like with [`OccupiedEntry::insert`].
+    pub fn insert_entry(self, value: V) -> OccupiedEntry<'a, K, V, S, A> {
+        match self {
+            Entry::Occupied(mut entry) => {
+                entry.insert(value);
+                entry
+            }
+            Entry::Vacant(entry) => entry.insert_entry(value),
+        }
+    }
 }
 
-impl<'a, K: Hash + Eq, V, S: BuildHasher> OccupiedEntry<'a, K, V, S> {
+
+impl<'a, K: Hash + Eq, V, S: BuildHasher, A: NodeStorage<K, V>> OccupiedEntry<'a, K, V, S, A> {
+    #[inline]
+    fn node(&self) -> *mut Node<K, V, A::Handle> {
+        unsafe { (*self.map).ptr(self.entry) }
+    }
+
     /// Gets a reference to the entry key
     ///
     /// # Examples
@@ -1345,36 +2467,33 @@ impl<'a, K: Hash + Eq, V, S: BuildHasher> OccupiedEntry<'a, K, V, S> {
     /// assert_eq!("foo", map.entry("foo".to_string()).key());
     /// ```
     pub fn key(&self) -> &K {
-        unsafe { &(*self.entry).key }
+        unsafe { &(*self.node()).key }
     }
 
     /// Gets a reference to the value in the entry.
     pub fn get(&self) -> &V {
-        unsafe { &(*self.entry).value }
+        unsafe { &(*self.node()).value }
     }
 
     /// Gets a mutable reference to the value in the entry.
     pub fn get_mut(&mut self) -> &mut V {
-        unsafe { &mut (*self.entry).value }
+        unsafe { &mut (*self.node()).value }
     }
 
     /// Converts the OccupiedEntry into a mutable reference to the value in the entry
     /// with a lifetime bound to the map itself
     pub fn into_mut(self) -> &'a mut V {
-        unsafe { &mut (*self.entry).value }
+        unsafe { &mut (*self.node()).value }
     }
 
     /// Sets the value of the entry, and returns the entry's old value
     pub fn insert(&mut self, value: V) -> V {
         unsafe {
-            (*self.map).ensure_guard_node();
-
-            let old_val = mem::replace(&mut (*self.entry).value, value);
-            let node_ptr: *mut Node<K, V> = self.entry;
+            let old_val = mem::replace(&mut (*self.node()).value, value);
 
             // Existing node, just update LRU position
-            (*self.map).detach(node_ptr);
-            (*self.map).attach(node_ptr);
+            (*self.map).detach(self.entry);
+            (*self.map).attach(self.entry);
 
             old_val
         }
@@ -1382,11 +2501,77 @@ impl<'a, K: Hash + Eq, V, S: BuildHasher> OccupiedEntry<'a, K, V, S> {
 
     /// Takes the value out of the entry, and returns it
     pub fn remove(self) -> V {
-        unsafe { (*self.map).remove(&(*self.entry).key) }.unwrap()
+        self.remove_entry().1
+    }
+
+    /// Takes the key and value out of the entry, and returns them
+    pub fn remove_entry(self) -> (K, V) {
+        unsafe { (*self.map).take_node(self.entry) }
+    }
+
+    /// Replaces the key in the map with the key used to create this entry, and returns
+    /// the old key.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the entry was not created by [`LinkedHashMap::entry`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::rc::Rc;
+    /// use kg_utils::collections::LinkedHashMap;
+    /// use kg_utils::collections::linked_hash_map::Entry;
+    ///
+    /// let mut map: LinkedHashMap<Rc<String>, u32> = LinkedHashMap::new();
+    /// let known = Rc::new("a".to_string());
+    /// map.insert(Rc::new("a".to_string()), 1);
+    ///
+    /// if let Entry::Occupied(e) = map.entry(known.clone()) {
+    ///     e.replace_key();
+    /// }
+    /// assert!(Rc::ptr_eq(map.keys().next().unwrap(), &known));
+    /// ```
+    pub fn replace_key(mut self) -> K {
+        let key = self.key.take().expect("entry was not created by LinkedHashMap::entry");
+        unsafe { mem::replace(&mut (*self.node()).key, key) }
+    }
+
+    /// Moves the entry to the back of the map.
+    pub fn move_to_back(&mut self) {
+        unsafe {
+            (*self.map).detach(self.entry);
+            (*self.map).attach(self.entry);
+        }
+    }
+
+    /// Moves the entry to the front of the map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use kg_utils::collections::LinkedHashMap;
+    /// use kg_utils::collections::linked_hash_map::Entry;
+    ///
+    /// let mut map = LinkedHashMap::new();
+    /// map.insert(1, "a");
+    /// map.insert(2, "b");
+    ///
+    /// if let Entry::Occupied(mut e) = map.entry(2) {
+    ///     e.move_to_front();
+    /// }
+    /// assert_eq!(map.keys().cloned().collect::<Vec<_>>(), [2, 1]);
+    /// ```
+    pub fn move_to_front(&mut self) {
+        unsafe {
+            let head = (*self.map).ensure_guard_node();
+            (*self.map).detach(self.entry);
+            (*self.map).attach_after(self.entry, head);
+        }
     }
 }
 
-impl<'a, K: 'a + Hash + Eq, V: 'a, S: BuildHasher> VacantEntry<'a, K, V, S> {
+impl<'a, K: 'a + Hash + Eq, V: 'a, S: BuildHasher, A: NodeStorage<K, V>> VacantEntry<'a, K, V, S, A> {
     /// Gets a reference to the entry key
     ///
     /// # Examples
@@ -1405,37 +2590,833 @@ impl<'a, K: 'a + Hash + Eq, V: 'a, S: BuildHasher> VacantEntry<'a, K, V, S> {
     /// Sets the value of the entry with the VacantEntry's key,
     /// and returns a mutable reference to it
     pub fn insert(self, value: V) -> &'a mut V {
-        self.map.ensure_guard_node();
+        let (map, node) = self.insert_node(value);
+        unsafe { &mut (*map.ptr(node)).value }
+    }
+
+    /// Sets the value of the entry with the VacantEntry's key, and returns an `OccupiedEntry`.
+    pub fn insert_entry(self, value: V) -> OccupiedEntry<'a, K, V, S, A> {
+        let (map, node) = self.insert_node(value);
+        OccupiedEntry {
+            entry: node,
+            map,
+            key: None,
+            marker: marker::PhantomData,
+        }
+    }
+
+    fn insert_node(self, value: V) -> (&'a mut LinkedHashMap<K, V, S, A>, A::Handle) {
+        let node = self.map.new_node(self.hash, self.key, value);
+        self.map.attach(node);
+        (self.map, node)
+    }
+
+    /// Sets the value of the entry with the VacantEntry's key, inserting it at the front of
+    /// the map, and returns a mutable reference to it
+    pub fn insert_front(self, value: V) -> &'a mut V {
+        let node = self.map.new_node(self.hash, self.key, value);
+        let head = self.map.ensure_guard_node();
+        self.map.attach_after(node, head);
+        unsafe { &mut (*self.map.ptr(node)).value }
+    }
 
-        let node = if self.map.free.is_null() {
-            Box::into_raw(Box::new(Node::new(self.key, value)))
+    /// Sets the value of the entry with the VacantEntry's key, inserting it at position `index`,
+    /// and returns a mutable reference to it
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index > len`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use kg_utils::collections::LinkedHashMap;
+    /// use kg_utils::collections::linked_hash_map::Entry;
+    ///
+    /// let mut map = LinkedHashMap::new();
+    /// map.insert("a", 1);
+    /// map.insert("c", 3);
+    ///
+    /// if let Entry::Vacant(e) = map.entry("b") {
+    ///     e.insert_at(1, 2);
+    /// }
+    /// if let Entry::Vacant(e) = map.entry("z") {
+    ///     e.insert_front(0);
+    /// }
+    /// assert_eq!(map.keys().cloned().collect::<Vec<_>>(), ["z", "a", "b", "c"]);
+    /// ```
+    pub fn insert_at(self, index: usize, value: V) -> &'a mut V {
+        let len = self.map.len();
+        assert!(index <= len, "insertion index (is {}) should be <= len (is {})", index, len);
+
+        let node = self.map.insert_new_at(index, self.hash, self.key, value);
+        unsafe { &mut (*self.map.ptr(node)).value }
+    }
+}
+
+/// A builder for looking up entries of a `LinkedHashMap` by precomputed hashes, created by
+/// [`LinkedHashMap::raw_entry`].
+pub struct RawEntryBuilder<'a, K: 'a, V: 'a, S: 'a = hash_map::RandomState, A: 'a + NodeStorage<K, V> = BoxedNodes<K, V>> {
+    map: &'a LinkedHashMap<K, V, S, A>,
+}
+
+impl<'a, K: Hash + Eq, V, S: BuildHasher, A: NodeStorage<K, V>> RawEntryBuilder<'a, K, V, S, A> {
+    /// Looks up an entry by its key.
+    pub fn from_key<Q>(self, k: &Q) -> Option<(&'a K, &'a V)>
+        where K: Borrow<Q>, Q: ?Sized + Hash + Eq
+    {
+        let hash = self.map.hash_key(k);
+        self.from_key_hashed_nocheck(hash, k)
+    }
+
+    /// Looks up an entry by its key and precomputed hash of the key.
+    pub fn from_key_hashed_nocheck<Q>(self, hash: u64, k: &Q) -> Option<(&'a K, &'a V)>
+        where K: Borrow<Q>, Q: ?Sized + Eq
+    {
+        self.from_hash(hash, |q| q.borrow() == k)
+    }
+
+    /// Looks up an entry by a precomputed hash, and a function matching the key.
+    pub fn from_hash<F>(self, hash: u64, is_match: F) -> Option<(&'a K, &'a V)>
+        where F: FnMut(&K) -> bool
+    {
+        let map = self.map;
+        map.find_hashed(hash, is_match).map(|node| unsafe {
+            let n = map.ptr(node);
+            (&(*n).key, &(*n).value)
+        })
+    }
+}
+
+/// A builder for raw mutable entries of a `LinkedHashMap`, created by
+/// [`LinkedHashMap::raw_entry_mut`].
+pub struct RawEntryBuilderMut<'a, K: 'a, V: 'a, S: 'a = hash_map::RandomState, A: 'a + NodeStorage<K, V> = BoxedNodes<K, V>> {
+    map: &'a mut LinkedHashMap<K, V, S, A>,
+}
+
+impl<'a, K: Hash + Eq, V, S: BuildHasher, A: NodeStorage<K, V>> RawEntryBuilderMut<'a, K, V, S, A> {
+    /// Gets the raw entry for the given key.
+    pub fn from_key<Q>(self, k: &Q) -> RawEntryMut<'a, K, V, S, A>
+        where K: Borrow<Q>, Q: ?Sized + Hash + Eq
+    {
+        let hash = self.map.hash_key(k);
+        self.from_key_hashed_nocheck(hash, k)
+    }
+
+    /// Gets the raw entry for the given key and precomputed hash of the key.
+    pub fn from_key_hashed_nocheck<Q>(self, hash: u64, k: &Q) -> RawEntryMut<'a, K, V, S, A>
+        where K: Borrow<Q>, Q: ?Sized + Eq
+    {
+        self.from_hash(hash, |q| q.borrow() == k)
+    }
+
+    /// Gets the raw entry for a precomputed hash, and a function matching the key.
+    pub fn from_hash<F>(self, hash: u64, is_match: F) -> RawEntryMut<'a, K, V, S, A>
+        where F: FnMut(&K) -> bool
+    {
+        match self.map.find_hashed(hash, is_match) {
+            Some(node) => RawEntryMut::Occupied(RawOccupiedEntryMut {
+                node,
+                hash,
+                map: self.map,
+            }),
+            None => RawEntryMut::Vacant(RawVacantEntryMut {
+                map: self.map,
+            }),
+        }
+    }
+}
+
+/// A raw view into a single location in a `LinkedHashMap`, which may be vacant or occupied.
+pub enum RawEntryMut<'a, K: 'a, V: 'a, S: 'a = hash_map::RandomState, A: 'a + NodeStorage<K, V> = BoxedNodes<K, V>> {
+    /// An occupied entry.
+    Occupied(RawOccupiedEntryMut<'a, K, V, S, A>),
+    /// A vacant entry.
+    Vacant(RawVacantEntryMut<'a, K, V, S, A>),
+}
+
+/// A raw view into a single occupied location in a `LinkedHashMap`.
+pub struct RawOccupiedEntryMut<'a, K: 'a, V: 'a, S: 'a = hash_map::RandomState, A: 'a + NodeStorage<K, V> = BoxedNodes<K, V>> {
+    node: A::Handle,
+    hash: u64,
+    map: &'a mut LinkedHashMap<K, V, S, A>,
+}
+
+/// A raw view into a single empty location in a `LinkedHashMap`.
+pub struct RawVacantEntryMut<'a, K: 'a, V: 'a, S: 'a = hash_map::RandomState, A: 'a + NodeStorage<K, V> = BoxedNodes<K, V>> {
+    map: &'a mut LinkedHashMap<K, V, S, A>,
+}
+
+impl<'a, K: Hash + Eq, V, S: BuildHasher, A: NodeStorage<K, V>> RawEntryMut<'a, K, V, S, A> {
+    /// Ensures a value is in the entry by inserting the given key and value if empty, and returns
+    /// references to the key and value in the entry.
+    pub fn or_insert(self, default_key: K, default_val: V) -> (&'a mut K, &'a mut V) {
+        match self {
+            RawEntryMut::Occupied(entry) => entry.into_key_value(),
+            RawEntryMut::Vacant(entry) => entry.insert(default_key, default_val),
+        }
+    }
+
+    /// Ensures a value is in the entry by inserting the key and value returned by the default
+    /// function if empty, and returns references to the key and value in the entry.
+    pub fn or_insert_with<F>(self, default: F) -> (&'a mut K, &'a mut V)
+        where F: FnOnce() -> (K, V)
+    {
+        match self {
+            RawEntryMut::Occupied(entry) => entry.into_key_value(),
+            RawEntryMut::Vacant(entry) => {
+                let (k, v) = default();
+                entry.insert(k, v)
+            }
+        }
+    }
+}
+
+impl<'a, K: Hash + Eq, V, S: BuildHasher, A: NodeStorage<K, V>> RawOccupiedEntryMut<'a, K, V, S, A> {
+    /// Gets a reference to the entry key
+    pub fn key(&self) -> &K {
+        unsafe { &(*self.map.ptr(self.node)).key }
+    }
+
+    /// Gets a reference to the value in the entry.
+    pub fn get(&self) -> &V {
+        unsafe { &(*self.map.ptr(self.node)).value }
+    }
+
+    /// Gets a mutable reference to the value in the entry.
+    pub fn get_mut(&mut self) -> &mut V {
+        unsafe { &mut (*self.map.ptr(self.node)).value }
+    }
+
+    /// Converts the entry into a mutable reference to the value in the entry
+    /// with a lifetime bound to the map itself
+    pub fn into_mut(self) -> &'a mut V {
+        unsafe { &mut (*self.map.ptr(self.node)).value }
+    }
+
+    /// Converts the entry into mutable references to the key and value in the entry
+    /// with a lifetime bound to the map itself. Changing the key in a way that changes its hash
+    /// or equality is a logic error.
+    pub fn into_key_value(self) -> (&'a mut K, &'a mut V) {
+        unsafe {
+            let n = self.map.ptr(self.node);
+            (&mut (*n).key, &mut (*n).value)
+        }
+    }
+
+    /// Sets the value of the entry, moves the entry to the back of the map, and returns
+    /// the entry's old value
+    pub fn insert(&mut self, value: V) -> V {
+        let old_val = mem::replace(self.get_mut(), value);
+        self.map.detach(self.node);
+        self.map.attach(self.node);
+        old_val
+    }
+
+    /// Takes the value out of the entry, and returns it
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+
+    /// Takes the key and value out of the entry, and returns them
+    pub fn remove_entry(self) -> (K, V) {
+        // the hash given to the builder is used, the key is not rehashed
+        self.map.remove_handle(self.hash, self.node);
+        self.map.detach(self.node);
+        unsafe { self.map.recycle_node(self.node) }
+    }
+}
+
+impl<'a, K: Hash + Eq, V, S: BuildHasher, A: NodeStorage<K, V>> RawVacantEntryMut<'a, K, V, S, A> {
+    /// Inserts the key and value at the back of the map, and returns references to them.
+    pub fn insert(self, k: K, v: V) -> (&'a mut K, &'a mut V) {
+        let hash = self.map.hash_key(&k);
+        self.insert_hashed_nocheck(hash, k, v)
+    }
+
+    /// Inserts the key and value at the back of the map, using the precomputed hash of the key,
+    /// and returns references to them.
+    pub fn insert_hashed_nocheck(self, hash: u64, k: K, v: V) -> (&'a mut K, &'a mut V) {
+        let node = self.map.new_node(hash, k, v);
+        self.map.attach(node);
+        unsafe {
+            let n = self.map.ptr(node);
+            (&mut (*n).key, &mut (*n).value)
+        }
+    }
+}
+
+/// A cursor over a `LinkedHashMap` with editing operations.
+///
+/// A cursor points either at an entry of the map, or at a "ghost" position between the last
+/// and the first entry, like the cursor of `std::collections::LinkedList`. Moving and editing
+/// around the current position is O(1).
+pub struct CursorMut<'a, K: 'a, V: 'a, S: 'a = hash_map::RandomState, A: 'a + NodeStorage<K, V> = BoxedNodes<K, V>> {
+    cur: A::Handle,
+    // guard node of the map, which is the "ghost" position
+    head: A::Handle,
+    map: &'a mut LinkedHashMap<K, V, S, A>,
+}
+
+impl<'a, K: Hash + Eq, V, S: BuildHasher, A: NodeStorage<K, V>> CursorMut<'a, K, V, S, A> {
+    #[inline]
+    fn is_ghost(&self) -> bool {
+        self.cur == self.head
+    }
+
+    /// Moves the cursor to the next entry. If the cursor points at the last entry, it is moved
+    /// to the "ghost" position, and from the "ghost" position it is moved to the first entry.
+    #[inline]
+    pub fn move_next(&mut self) {
+        self.cur = self.map.next(self.cur);
+    }
+
+    /// Moves the cursor to the previous entry. If the cursor points at the first entry, it is
+    /// moved to the "ghost" position, and from the "ghost" position it is moved to the last entry.
+    #[inline]
+    pub fn move_prev(&mut self) {
+        self.cur = self.map.prev(self.cur);
+    }
+
+    /// Returns the key of the current entry, or `None` at the "ghost" position.
+    #[inline]
+    pub fn key(&self) -> Option<&K> {
+        if self.is_ghost() {
+            None
+        } else {
+            Some(unsafe { &(*self.map.ptr(self.cur)).key })
+        }
+    }
+
+    /// Returns a reference to the value of the current entry, or `None` at the "ghost" position.
+    #[inline]
+    pub fn value(&self) -> Option<&V> {
+        if self.is_ghost() {
+            None
+        } else {
+            Some(unsafe { &(*self.map.ptr(self.cur)).value })
+        }
+    }
+
+    /// Returns a mutable reference to the value of the current entry, or `None` at the "ghost"
+    /// position.
+    #[inline]
+    pub fn value_mut(&mut self) -> Option<&mut V> {
+        if self.is_ghost() {
+            None
+        } else {
+            Some(unsafe { &mut (*self.map.ptr(self.cur)).value })
+        }
+    }
+
+    /// Returns the key and a mutable reference to the value of the current entry, or `None` at
+    /// the "ghost" position.
+    #[inline]
+    pub fn current(&mut self) -> Option<(&K, &mut V)> {
+        if self.is_ghost() {
+            None
         } else {
-            // use a recycled box
             unsafe {
-                let free = self.map.free;
-                self.map.free = (*free).next;
-                ptr::write(free, Node::new(self.key, value));
-                free
+                let n = self.map.ptr(self.cur);
+                Some((&(*n).key, &mut (*n).value))
             }
-        };
+        }
+    }
 
-        let keyref = unsafe { &(*node).key };
+    /// Inserts a key-value pair before the current entry. At the "ghost" position the entry is
+    /// inserted at the back of the map.
+    ///
+    /// If the key already existed, its entry is moved before the current entry (unless it is the
+    /// current entry) and the old value is returned.
+    pub fn insert_before(&mut self, k: K, v: V) -> Option<V> {
+        let (node, old_val) = self.upsert(k, v);
+        if node != self.cur {
+            self.map.attach_before(node, self.cur);
+        }
+        old_val
+    }
 
-        self.map.attach(node);
+    /// Inserts a key-value pair after the current entry. At the "ghost" position the entry is
+    /// inserted at the front of the map.
+    ///
+    /// If the key already existed, its entry is moved after the current entry (unless it is the
+    /// current entry) and the old value is returned.
+    pub fn insert_after(&mut self, k: K, v: V) -> Option<V> {
+        let (node, old_val) = self.upsert(k, v);
+        if node != self.cur {
+            self.map.attach_after(node, self.cur);
+        }
+        old_val
+    }
+
+    /// Removes the current entry and returns it, moving the cursor to the next entry.
+    /// Returns `None` at the "ghost" position.
+    pub fn remove_current(&mut self) -> Option<(K, V)> {
+        if self.is_ghost() {
+            return None;
+        }
+        let node = self.cur;
+        self.cur = self.map.next(node);
+        Some(unsafe { self.map.take_node(node) })
+    }
+
+    /// Returns a node for the key, which is detached if it is not the current node.
+    fn upsert(&mut self, k: K, v: V) -> (A::Handle, Option<V>) {
+        let hash = self.map.hash_key(&k);
+        match self.map.find_hashed(hash, |q| *q == k) {
+            Some(node) => {
+                let old_val = unsafe { mem::replace(&mut (*self.map.ptr(node)).value, v) };
+                if node != self.cur {
+                    self.map.detach(node);
+                }
+                (node, Some(old_val))
+            }
+            None => (self.map.new_node(hash, k, v), None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Checks the map against a model of its entries in iteration order.
+    fn assert_model(map: &LinkedHashMap<u32, u32>, model: &[(u32, u32)]) {
+        assert_eq!(map.len(), model.len());
+        assert!(map.iter().map(|(k, v)| (*k, *v)).eq(model.iter().cloned()));
+        assert!(map.iter().rev().map(|(k, v)| (*k, *v)).eq(model.iter().rev().cloned()));
+        for (i, &(k, v)) in model.iter().enumerate() {
+            assert_eq!(map.get(&k), Some(&v));
+            assert_eq!(map.get_index(i), Some((&k, &v)));
+            assert_eq!(map.get_index_of(&k), Some(i));
+        }
+    }
+
+    fn map_and_model(len: u32) -> (LinkedHashMap<u32, u32>, Vec<(u32, u32)>) {
+        let model: Vec<_> = (0..len).map(|i| (i, i)).collect();
+        (model.iter().cloned().collect(), model)
+    }
+
+    #[test]
+    fn cursor_wraps_through_ghost() {
+        let (mut map, mut model) = map_and_model(3);
+        {
+            let mut cursor = map.cursor_front_mut();
+            for &k in &[Some(1), Some(2), None, Some(0), Some(1)] {
+                cursor.move_next();
+                assert_eq!(cursor.key().cloned(), k);
+            }
+            for &k in &[Some(0), None, Some(2), Some(1)] {
+                cursor.move_prev();
+                assert_eq!(cursor.key().cloned(), k);
+            }
+            cursor.move_next();
+            assert_eq!(cursor.remove_current(), Some((2, 2)));
+            assert_eq!(cursor.key(), None);
+            assert_eq!(cursor.value_mut(), None);
+            assert_eq!(cursor.remove_current(), None);
+            // at the ghost position entries are inserted at the back and at the front
+            assert_eq!(cursor.insert_before(3, 3), None);
+            assert_eq!(cursor.insert_after(4, 4), None);
+            assert_eq!(cursor.key(), None);
+            cursor.move_prev();
+            assert_eq!(cursor.key(), Some(&3));
+        }
+        model.remove(2);
+        model.push((3, 3));
+        model.insert(0, (4, 4));
+        assert_model(&map, &model);
+
+        let mut map = LinkedHashMap::<u32, u32>::new();
+        {
+            let mut cursor = map.cursor_back_mut();
+            assert_eq!(cursor.key(), None);
+            cursor.move_next();
+            assert_eq!(cursor.key(), None);
+            cursor.insert_after(1, 1);
+            cursor.move_prev();
+            assert_eq!(cursor.key(), Some(&1));
+        }
+        assert_model(&map, &[(1, 1)]);
+    }
 
-        let ret = self.map.map.entry(KeyRef(keyref)).or_insert(node);
-        unsafe { &mut (**ret).value }
+    #[test]
+    fn cursor_insert_existing_key() {
+        let (mut map, _) = map_and_model(5);
+        {
+            let mut cursor = map.cursor_at_key_mut(&1).unwrap();
+            assert_eq!(cursor.insert_after(3, 30), Some(3));
+            assert_eq!(cursor.insert_before(4, 40), Some(4));
+            // the current entry and its neighbours stay in place
+            assert_eq!(cursor.insert_before(1, 10), Some(1));
+            assert_eq!(cursor.insert_after(3, 31), Some(30));
+            assert_eq!(cursor.insert_before(4, 41), Some(40));
+            assert_eq!(cursor.key(), Some(&1));
+        }
+        assert_model(&map, &[(0, 0), (4, 41), (1, 10), (3, 31), (2, 2)]);
+        {
+            let mut cursor = map.cursor_front_mut();
+            cursor.move_prev();
+            assert_eq!(cursor.insert_before(0, 1), Some(0));
+            assert_eq!(cursor.insert_after(2, 3), Some(2));
+            assert_eq!(cursor.key(), None);
+        }
+        assert_model(&map, &[(2, 3), (4, 41), (1, 10), (3, 31), (0, 1)]);
+    }
+
+    #[test]
+    fn swap_adjacent_and_identical() {
+        let (mut map, mut model) = map_and_model(5);
+        for &(a, b) in &[(1, 2), (1, 2), (4, 3), (0, 4), (2, 2), (3, 0)] {
+            assert!(map.swap(&a, &b));
+            let i = model.iter().position(|e| e.0 == a).unwrap();
+            let j = model.iter().position(|e| e.0 == b).unwrap();
+            model.swap(i, j);
+            assert_model(&map, &model);
+        }
+        assert!(!map.swap(&1, &5));
+        assert_model(&map, &model);
+    }
+
+    #[test]
+    fn move_next_to_self_and_neighbours() {
+        let (mut map, _) = map_and_model(4);
+        assert!(map.move_before(&2, &2));
+        assert!(map.move_after(&1, &1));
+        assert!(map.move_before(&1, &2));
+        assert!(map.move_after(&2, &1));
+        assert_model(&map, &[(0, 0), (1, 1), (2, 2), (3, 3)]);
+        assert!(map.move_before(&2, &1));
+        assert!(map.move_after(&0, &3));
+        assert_model(&map, &[(2, 2), (1, 1), (3, 3), (0, 0)]);
+    }
+
+    #[test]
+    fn swap_indices_adjacent_and_identical() {
+        let (mut map, mut model) = map_and_model(5);
+        for &(a, b) in &[(1, 2), (2, 1), (4, 3), (0, 4), (2, 2), (3, 0)] {
+            assert!(map.swap_indices(a, b));
+            model.swap(a, b);
+            assert_model(&map, &model);
+        }
+        assert!(!map.swap_indices(5, 0));
+        assert!(!map.swap_indices(0, 5));
+        assert_model(&map, &model);
+    }
+
+    #[test]
+    fn insert_at_and_remove_at_keep_index() {
+        let (mut map, mut model) = map_and_model(0);
+        for i in 0..60 {
+            let len = model.len();
+            let k = i * 7 % 23;
+            let index = i as usize * 5 % (len + 1);
+            match model.iter().position(|e| e.0 == k) {
+                Some(pos) if index < len => {
+                    assert_eq!(map.insert_at(index, k, i), Ok(Some(model[pos].1)));
+                    model.remove(pos);
+                    model.insert(index, (k, i));
+                }
+                Some(_) => assert_eq!(map.insert_at(index, k, i), Err((k, i))),
+                None => {
+                    assert_eq!(map.insert_at(index, k, i), Ok(None));
+                    model.insert(index, (k, i));
+                }
+            }
+            assert_model(&map, &model);
+            if i % 3 == 2 {
+                let index = i as usize % model.len();
+                assert_eq!(map.remove_at(index), Some(model.remove(index).1));
+                assert_eq!(map.remove_at(model.len()), None);
+                assert_model(&map, &model);
+            }
+            if i % 10 == 9 {
+                // reorders an indexed map
+                let k = model[model.len() / 2].0;
+                assert!(map.move_to_front(&k));
+                let pos = model.iter().position(|e| e.0 == k).unwrap();
+                let e = model.remove(pos);
+                model.insert(0, e);
+                assert_model(&map, &model);
+            }
+        }
+        assert_eq!(map.insert_at(model.len() + 1, 100, 100), Err((100, 100)));
+        assert_model(&map, &model);
+    }
+
+    #[test]
+    fn indexed_map_tracks_reordering() {
+        let (mut map, mut model) = map_and_model(40);
+        map.reindex();
+        let mut seed = 11u32;
+        for i in 40..400 {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            let r = (seed >> 8) as usize;
+            let pos = r % model.len();
+            let k = model[pos].0;
+            match r % 8 {
+                0 => {
+                    assert_eq!(map.remove(&k), Some(model.remove(pos).1));
+                    map.insert(i, i);
+                    model.push((i, i));
+                }
+                1 => {
+                    assert!(map.move_to_front(&k));
+                    let e = model.remove(pos);
+                    model.insert(0, e);
+                }
+                2 => {
+                    let target = model[r / 8 % model.len()].0;
+                    assert!(map.move_after(&k, &target));
+                    if target != k {
+                        let e = model.remove(pos);
+                        let at = model.iter().position(|e| e.0 == target).unwrap();
+                        model.insert(at + 1, e);
+                    }
+                }
+                3 => {
+                    let b = r / 8 % model.len();
+                    assert!(map.swap(&k, &model[b].0));
+                    model.swap(pos, b);
+                }
+                4 => {
+                    let mut cursor = map.cursor_at_key_mut(&k).unwrap();
+                    assert_eq!(cursor.insert_before(i, i), None);
+                    model.insert(pos, (i, i));
+                }
+                5 => {
+                    assert_eq!(map.pop_front(), Some(model.remove(0)));
+                    map.insert(i, i);
+                    model.push((i, i));
+                }
+                6 => {
+                    map.reverse();
+                    model.reverse();
+                }
+                _ => {
+                    map.sort_by_key(|k, _| k % 7);
+                    model.sort_by_key(|e| e.0 % 7);
+                }
+            }
+            assert!(map.indexed);
+            assert_model(&map, &model);
+        }
+        map.shrink_to_fit();
+        assert_model(&map, &model);
+        map.clear();
+        map.insert(1, 1);
+        assert!(map.indexed);
+        assert_eq!(map.get_index_of(&1), Some(0));
+    }
+
+    #[test]
+    fn indexed_slab_map_survives_compaction() {
+        let mut map: SlabLinkedHashMap<u32, u32> = (0..20).map(|i| (i, i)).collect();
+        map.reindex();
+        for k in (0..20).step_by(3) {
+            map.remove(&k);
+        }
+        map.shrink_to_fit();
+        let keys: Vec<u32> = (0..20).filter(|k| k % 3 != 0).collect();
+        for (i, k) in keys.iter().enumerate() {
+            assert_eq!(map.get_index_of(k), Some(i));
+            assert_eq!(map.get_index(i), Some((k, k)));
+        }
+    }
+
+    #[test]
+    fn early_dropped_extract_if_and_drain() {
+        let (mut map, mut model) = map_and_model(10);
+        {
+            let mut iter = map.extract_if(|k, v| {
+                *v += 10;
+                k % 2 == 0
+            });
+            assert_eq!(iter.next(), Some((0, 10)));
+            assert_eq!(iter.next(), Some((2, 12)));
+        }
+        // only visited entries are removed
+        model.retain(|e| e.0 != 0 && e.0 != 2);
+        model[0].1 = 11;
+        assert_model(&map, &model);
+
+        {
+            let mut drain = map.drain_range(2..6);
+            assert_eq!(drain.next(), Some((4, 4)));
+        }
+        // unconsumed entries of the range are removed anyway
+        model.drain(2..6);
+        assert_model(&map, &model);
+
+        drop(map.drain());
+        assert_model(&map, &[]);
+        map.insert(1, 1);
+        assert_model(&map, &[(1, 1)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "greater than range end")]
+    fn drain_range_start_after_end() {
+        let (mut map, _) = map_and_model(5);
+        map.drain_range((Bound::Included(3), Bound::Excluded(2)));
+    }
+
+    #[test]
+    #[should_panic(expected = "out of range")]
+    fn drain_range_end_after_len() {
+        let (mut map, _) = map_and_model(5);
+        map.drain_range(..=5);
+    }
+
+    #[test]
+    #[should_panic(expected = "overflows")]
+    fn drain_range_start_overflow() {
+        let (mut map, _) = map_and_model(5);
+        map.drain_range((Bound::Excluded(usize::MAX), Bound::Unbounded));
+    }
+
+    #[test]
+    fn raw_occupied_entry_remove_entry() {
+        let (mut map, mut model) = map_and_model(6);
+        for &k in &[3, 0, 5, 1] {
+            let hash = map.hasher().hash_one(k);
+            let entry = if k % 2 == 0 {
+                map.raw_entry_mut().from_hash(hash, |q| *q == k)
+            } else {
+                map.raw_entry_mut().from_key_hashed_nocheck(hash, &k)
+            };
+            match entry {
+                RawEntryMut::Occupied(e) => assert_eq!(e.remove_entry(), (k, k)),
+                RawEntryMut::Vacant(_) => panic!("key {} not found", k),
+            }
+            model.retain(|e| e.0 != k);
+            assert_model(&map, &model);
+            assert_eq!(map.get(&k), None);
+            assert_eq!(map.raw_entry().from_hash(hash, |q| *q == k), None);
+        }
+        assert_eq!(free_nodes(&map.nodes), 4);
+
+        let hash = map.hasher().hash_one(7);
+        match map.raw_entry_mut().from_key_hashed_nocheck(hash, &7) {
+            RawEntryMut::Occupied(_) => panic!("key 7 found"),
+            RawEntryMut::Vacant(e) => assert_eq!(e.insert_hashed_nocheck(hash, 7, 7), (&mut 7, &mut 7)),
+        }
+        model.push((7, 7));
+        assert_model(&map, &model);
+    }
+
+    #[cfg(feature = "heapsize_impl")]
+    #[test]
+    fn heap_size_counts_table_and_nodes() {
+        use ::heapsize::HeapSizeOf;
+
+        let (mut map, _) = map_and_model(100);
+        let table_size = map.table.capacity() * (mem::size_of::<NodePtr<u32, u32>>() + 1);
+        let nodes_size = 101 * mem::size_of::<Node<u32, u32, NodePtr<u32, u32>>>();
+        let size = map.heap_size_of_children();
+        assert!(size >= table_size + nodes_size);
+        assert!(size <= 2 * (table_size + nodes_size));
+
+        map.reindex();
+        assert!(map.heap_size_of_children() > size);
+    }
+
+    #[test]
+    fn entry_positions() {
+        let (mut map, mut model) = map_and_model(4);
+        match map.entry(10) {
+            Entry::Vacant(e) => assert_eq!(*e.insert_at(2, 10), 10),
+            Entry::Occupied(_) => unreachable!(),
+        }
+        model.insert(2, (10, 10));
+        assert_model(&map, &model);
+        match map.entry(11) {
+            Entry::Vacant(e) => assert_eq!(*e.insert_at(5, 11), 11),
+            Entry::Occupied(_) => unreachable!(),
+        }
+        model.push((11, 11));
+        assert_model(&map, &model);
+        match map.entry(12) {
+            Entry::Vacant(e) => assert_eq!(*e.insert_front(12), 12),
+            Entry::Occupied(_) => unreachable!(),
+        }
+        model.insert(0, (12, 12));
+        assert_model(&map, &model);
+        match map.entry(10) {
+            Entry::Occupied(mut e) => {
+                e.move_to_back();
+                e.move_to_front();
+                assert_eq!(e.remove_entry(), (10, 10));
+            }
+            Entry::Vacant(_) => unreachable!(),
+        }
+        model.retain(|e| e.0 != 10);
+        assert_model(&map, &model);
+        for (i, mut e) in map.entries().enumerate() {
+            if i % 2 == 0 {
+                e.move_to_back();
+            }
+        }
+        let (even, odd): (Vec<_>, Vec<_>) = model.iter().enumerate().partition(|e| e.0 % 2 == 0);
+        model = odd.into_iter().chain(even).map(|e| *e.1).collect();
+        assert_model(&map, &model);
+    }
+
+    #[test]
+    #[should_panic(expected = "entry was not created by LinkedHashMap::entry")]
+    fn replace_key_of_entries_entry() {
+        let (mut map, _) = map_and_model(3);
+        let entry = map.entries().next().unwrap();
+        entry.replace_key();
+    }
+
+    #[test]
+    #[should_panic(expected = "insertion index (is 4) should be <= len (is 3)")]
+    fn vacant_entry_insert_after_end() {
+        let (mut map, _) = map_and_model(3);
+        if let Entry::Vacant(e) = map.entry(3) {
+            e.insert_at(4, 3);
+        }
+    }
+
+    fn free_nodes<K, V>(nodes: &BoxedNodes<K, V>) -> usize {
+        let mut count = 0;
+        let mut cur = nodes.free;
+        while let Some(node) = cur {
+            count += 1;
+            let next = unsafe { (*node.0.as_ptr()).next };
+            cur = if next == node { None } else { Some(next) };
+        }
+        count
+    }
+
+    #[test]
+    fn drain_range_recycles_nodes() {
+        let mut map: LinkedHashMap<u32, String> = (0..10).map(|i| (i, i.to_string())).collect();
+        assert_eq!(map.drain_range(2..5).map(|e| e.0).collect::<Vec<_>>(), [2, 3, 4]);
+        assert_eq!(free_nodes(&map.nodes), 3);
+        drop(map.drain_range(..=1));
+        assert_eq!(free_nodes(&map.nodes), 5);
+        assert!(map.keys().cloned().eq(5..10));
+
+        for i in 10..15 {
+            map.insert(i, i.to_string());
+        }
+        assert_eq!(free_nodes(&map.nodes), 0);
+        assert!(map.keys().cloned().eq(5..15));
+        assert_eq!(map.drain().count(), 10);
+        assert_eq!(free_nodes(&map.nodes), 10);
     }
 }
 
-#[cfg(all(feature = "nightly", test))]
+#[cfg(test)]
 mod bench {
-    extern crate test;
+    use test::Bencher;
 
-    use super::LinkedHashMap;
+    use super::{LinkedHashMap, SlabLinkedHashMap};
 
     #[bench]
-    fn not_recycled_cycling(b: &mut test::Bencher) {
+    fn not_recycled_cycling(b: &mut Bencher) {
         let mut hash_map = LinkedHashMap::with_capacity(1000);
         for i in 0usize..1000 {
             hash_map.insert(i, i);
@@ -1452,7 +3433,7 @@ mod bench {
     }
 
     #[bench]
-    fn recycled_cycling(b: &mut test::Bencher) {
+    fn recycled_cycling(b: &mut Bencher) {
         let mut hash_map = LinkedHashMap::with_capacity(1000);
         for i in 0usize..1000 {
             hash_map.insert(i, i);
@@ -1466,4 +3447,49 @@ mod bench {
             }
         })
     }
+
+    #[bench]
+    fn slab_not_recycled_cycling(b: &mut Bencher) {
+        let mut hash_map = SlabLinkedHashMap::with_capacity_in(1000);
+        for i in 0usize..1000 {
+            hash_map.insert(i, i);
+        }
+        b.iter(|| {
+            for i in 0usize..1000 {
+                hash_map.remove(&i);
+            }
+            hash_map.clear_free_list();
+            for i in 0usize..1000 {
+                hash_map.insert(i, i);
+            }
+        })
+    }
+
+    #[bench]
+    fn slab_recycled_cycling(b: &mut Bencher) {
+        let mut hash_map = SlabLinkedHashMap::with_capacity_in(1000);
+        for i in 0usize..1000 {
+            hash_map.insert(i, i);
+        }
+        b.iter(|| {
+            for i in 0usize..1000 {
+                hash_map.remove(&i);
+            }
+            for i in 0usize..1000 {
+                hash_map.insert(i, i);
+            }
+        })
+    }
+
+    #[bench]
+    fn iter(b: &mut Bencher) {
+        let hash_map: LinkedHashMap<usize, usize> = (0..1000).map(|i| (i, i)).collect();
+        b.iter(|| hash_map.values().sum::<usize>())
+    }
+
+    #[bench]
+    fn slab_iter(b: &mut Bencher) {
+        let hash_map: SlabLinkedHashMap<usize, usize> = (0..1000).map(|i| (i, i)).collect();
+        b.iter(|| hash_map.values().sum::<usize>())
+    }
 }
@@ -0,0 +1,318 @@
+//! Order-statistic tree of node handles, used for positional operations of a `LinkedHashMap`.
+//!
+//! The tree is an implicit treap: tree nodes are ordered by position in the list only, and each
+//! of them stores the size of its subtree, so that both the position of a handle and the handle
+//! at a position are found in expected O(log n). Tree nodes are kept in a `Vec`, and their slots
+//! in a hash map by handle, so that nodes of the list don't grow for maps which never use
+//! positions.
+
+use std::hash::{BuildHasherDefault, Hash, Hasher};
+
+use hashbrown::HashMap;
+
+const NIL: usize = usize::MAX;
+
+/// Hasher of node handles, used for slots of handles in an [`OrderTree`].
+/// Handles are already unique, so they only need to be mixed.
+#[derive(Default)]
+pub(super) struct HandleHasher(u64);
+
+impl Hasher for HandleHasher {
+    #[inline]
+    fn finish(&self) -> u64 {
+        let m = (self.0 as u128).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        (m as u64) ^ ((m >> 64) as u64)
+    }
+
+    #[inline]
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 = self.0.rotate_left(8) ^ u64::from(b);
+        }
+    }
+
+    #[inline]
+    fn write_u32(&mut self, n: u32) { self.0 ^= u64::from(n); }
+
+    #[inline]
+    fn write_usize(&mut self, n: usize) { self.0 ^= n as u64; }
+}
+
+#[derive(Clone, Copy)]
+pub(super) struct TreeNode<H> {
+    handle: H,
+    parent: usize,
+    left: usize,
+    right: usize,
+    // number of nodes in the subtree rooted at this node
+    size: usize,
+    priority: u64,
+}
+
+/// Positions of node handles in iteration order.
+pub(super) struct OrderTree<H> {
+    pub(super) nodes: Vec<TreeNode<H>>,
+    // slots of handles in `nodes`
+    pub(super) slots: HashMap<H, usize, BuildHasherDefault<HandleHasher>>,
+    root: usize,
+    // state of the xorshift generator of priorities
+    seed: u64,
+}
+
+impl<H: Copy + Eq + Hash> OrderTree<H> {
+    pub fn new() -> OrderTree<H> {
+        OrderTree {
+            nodes: Vec::new(),
+            slots: HashMap::default(),
+            root: NIL,
+            seed: 0x2545_f491_4f6c_dd1d,
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+        self.slots.clear();
+        self.root = NIL;
+    }
+
+    pub fn reserve(&mut self, additional: usize) {
+        self.nodes.reserve(additional);
+        self.slots.reserve(additional);
+    }
+
+    pub fn shrink_to_fit(&mut self) {
+        self.nodes.shrink_to_fit();
+        self.slots.shrink_to_fit();
+    }
+
+    /// Appends `handle` at the last position.
+    pub fn push(&mut self, handle: H) {
+        let node = self.new_node(handle);
+        let root = self.merge(self.root, node);
+        self.set_root(root);
+    }
+
+    /// Inserts `handle` at position `index <= len()`.
+    pub fn insert(&mut self, index: usize, handle: H) {
+        debug_assert!(index <= self.len());
+        let node = self.new_node(handle);
+        let (left, right) = self.split(self.root, index);
+        let left = self.merge(left, node);
+        let root = self.merge(left, right);
+        self.set_root(root);
+    }
+
+    /// Removes `handle`, shifting positions of the following handles down by one.
+    pub fn remove(&mut self, handle: &H) {
+        let slot = self.slots.remove(handle).expect("handle missing from the order tree");
+        let TreeNode { parent, left, right, .. } = self.nodes[slot];
+        let child = self.merge(left, right);
+        if child != NIL {
+            self.nodes[child].parent = parent;
+        }
+        self.replace_child(parent, slot, child);
+        let mut cur = parent;
+        while cur != NIL {
+            self.nodes[cur].size -= 1;
+            cur = self.nodes[cur].parent;
+        }
+        self.release(slot);
+    }
+
+    /// Returns position of `handle`.
+    pub fn rank(&self, handle: &H) -> usize {
+        let mut cur = self.slots[handle];
+        let mut rank = self.size(self.nodes[cur].left);
+        loop {
+            let parent = self.nodes[cur].parent;
+            if parent == NIL {
+                return rank;
+            }
+            if self.nodes[parent].right == cur {
+                rank += self.size(self.nodes[parent].left) + 1;
+            }
+            cur = parent;
+        }
+    }
+
+    /// Returns the handle at position `index`, or `None` if `index >= len()`.
+    pub fn select(&self, index: usize) -> Option<H> {
+        let mut cur = self.root;
+        let mut index = index;
+        while cur != NIL {
+            let n = &self.nodes[cur];
+            let left = self.size(n.left);
+            if index < left {
+                cur = n.left;
+            } else if index == left {
+                return Some(n.handle);
+            } else {
+                index -= left + 1;
+                cur = n.right;
+            }
+        }
+        None
+    }
+
+    #[inline]
+    fn size(&self, node: usize) -> usize {
+        if node == NIL { 0 } else { self.nodes[node].size }
+    }
+
+    fn new_node(&mut self, handle: H) -> usize {
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 7;
+        self.seed ^= self.seed << 17;
+        let slot = self.nodes.len();
+        self.nodes.push(TreeNode {
+            handle,
+            parent: NIL,
+            left: NIL,
+            right: NIL,
+            size: 1,
+            priority: self.seed,
+        });
+        self.slots.insert(handle, slot);
+        slot
+    }
+
+    #[inline]
+    fn set_root(&mut self, root: usize) {
+        self.root = root;
+        if root != NIL {
+            self.nodes[root].parent = NIL;
+        }
+    }
+
+    /// Recomputes size of `node` after its children changed, and links the children back to it.
+    #[inline]
+    fn update(&mut self, node: usize) {
+        let TreeNode { left, right, .. } = self.nodes[node];
+        self.nodes[node].size = self.size(left) + self.size(right) + 1;
+        if left != NIL {
+            self.nodes[left].parent = node;
+        }
+        if right != NIL {
+            self.nodes[right].parent = node;
+        }
+    }
+
+    fn replace_child(&mut self, parent: usize, old: usize, new: usize) {
+        if parent == NIL {
+            self.root = new;
+        } else if self.nodes[parent].left == old {
+            self.nodes[parent].left = new;
+        } else {
+            self.nodes[parent].right = new;
+        }
+    }
+
+    /// Splits the subtree rooted at `node` into subtrees of the first `index` nodes and the rest.
+    fn split(&mut self, node: usize, index: usize) -> (usize, usize) {
+        if node == NIL {
+            return (NIL, NIL);
+        }
+        let TreeNode { left, right, .. } = self.nodes[node];
+        let left_size = self.size(left);
+        if index <= left_size {
+            let (l, r) = self.split(left, index);
+            self.nodes[node].left = r;
+            self.update(node);
+            (l, node)
+        } else {
+            let (l, r) = self.split(right, index - left_size - 1);
+            self.nodes[node].right = l;
+            self.update(node);
+            (node, r)
+        }
+    }
+
+    /// Joins subtrees rooted at `a` and `b`, with all nodes of `a` preceding nodes of `b`.
+    fn merge(&mut self, a: usize, b: usize) -> usize {
+        if a == NIL {
+            b
+        } else if b == NIL {
+            a
+        } else if self.nodes[a].priority > self.nodes[b].priority {
+            let right = self.merge(self.nodes[a].right, b);
+            self.nodes[a].right = right;
+            self.update(a);
+            a
+        } else {
+            let left = self.merge(a, self.nodes[b].left);
+            self.nodes[b].left = left;
+            self.update(b);
+            b
+        }
+    }
+
+    /// Frees `slot` of an unlinked node, moving the last node in its place.
+    fn release(&mut self, slot: usize) {
+        let last = self.nodes.len() - 1;
+        self.nodes.swap_remove(slot);
+        if slot != last {
+            let TreeNode { handle, parent, left, right, .. } = self.nodes[slot];
+            self.replace_child(parent, last, slot);
+            if left != NIL {
+                self.nodes[left].parent = slot;
+            }
+            if right != NIL {
+                self.nodes[right].parent = slot;
+            }
+            self.slots.insert(handle, slot);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_order(tree: &OrderTree<u32>, model: &[u32]) {
+        assert_eq!(tree.len(), model.len());
+        for (i, h) in model.iter().enumerate() {
+            assert_eq!(tree.select(i), Some(*h));
+            assert_eq!(tree.rank(h), i);
+        }
+        assert_eq!(tree.select(model.len()), None);
+    }
+
+    #[test]
+    fn operations_match_vec() {
+        let mut tree = OrderTree::new();
+        let mut model = Vec::new();
+        let mut seed = 5u32;
+        for h in 0..3000u32 {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            let r = (seed >> 8) as usize;
+            match r % 4 {
+                0 => {
+                    tree.push(h);
+                    model.push(h);
+                }
+                1 if !model.is_empty() => {
+                    let removed = model.remove(r % model.len());
+                    tree.remove(&removed);
+                }
+                _ => {
+                    let index = r % (model.len() + 1);
+                    tree.insert(index, h);
+                    model.insert(index, h);
+                }
+            }
+            if h % 100 == 99 {
+                assert_order(&tree, &model);
+            }
+        }
+        assert_order(&tree, &model);
+        for h in model.drain(..) {
+            tree.remove(&h);
+        }
+        assert_order(&tree, &model);
+    }
+}
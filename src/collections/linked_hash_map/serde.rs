@@ -2,20 +2,30 @@
 
 extern crate serde;
 
+use std::collections::hash_map::RandomState;
 use std::fmt::{Formatter, Result as FmtResult};
 use std::hash::{BuildHasher, Hash};
 use std::marker::PhantomData;
 
-use super::LinkedHashMap;
+use super::{BoxedNodes, LinkedHashMap, NodeStorage};
 
 use self::serde::{Deserialize, Deserializer, Serialize, Serializer};
 use self::serde::de::{Error, MapAccess, Visitor};
 use self::serde::ser::SerializeMap;
 
-impl<K, V, S> Serialize for LinkedHashMap<K, V, S>
+/// Upper bound of capacity preallocated from a size hint, which comes from untrusted input.
+const MAX_PREALLOC: usize = 4096;
+
+#[inline]
+fn cautious_capacity(hint: Option<usize>) -> usize {
+    std::cmp::min(hint.unwrap_or(0), MAX_PREALLOC)
+}
+
+impl<K, V, S, A> Serialize for LinkedHashMap<K, V, S, A>
     where K: Serialize + Eq + Hash,
           V: Serialize,
-          S: BuildHasher
+          S: BuildHasher,
+          A: NodeStorage<K, V>,
 {
     #[inline]
     fn serialize<T>(&self, serializer:T) -> Result<T::Ok, T::Error>
@@ -32,11 +42,11 @@ impl<K, V, S> Serialize for LinkedHashMap<K, V, S>
 
 #[derive(Debug)]
 /// `serde::de::Visitor` for a linked hash map.
-pub struct LinkedHashMapVisitor<K, V> {
-    marker: PhantomData<LinkedHashMap<K, V>>,
+pub struct LinkedHashMapVisitor<K, V, S = RandomState, A: NodeStorage<K, V> = BoxedNodes<K, V>> {
+    marker: PhantomData<LinkedHashMap<K, V, S, A>>,
 }
 
-impl<K, V> LinkedHashMapVisitor<K, V> {
+impl<K, V, S, A: NodeStorage<K, V>> LinkedHashMapVisitor<K, V, S, A> {
     /// Creates a new visitor for a linked hash map.
     pub fn new() -> Self {
         LinkedHashMapVisitor {
@@ -45,17 +55,19 @@ impl<K, V> LinkedHashMapVisitor<K, V> {
     }
 }
 
-impl<K, V> Default for LinkedHashMapVisitor<K, V> {
+impl<K, V, S, A: NodeStorage<K, V>> Default for LinkedHashMapVisitor<K, V, S, A> {
     fn default() -> Self {
         LinkedHashMapVisitor::new()
     }
 }
 
-impl<'de, K, V> Visitor<'de> for LinkedHashMapVisitor<K, V>
+impl<'de, K, V, S, A> Visitor<'de> for LinkedHashMapVisitor<K, V, S, A>
     where K: Deserialize<'de> + Eq + Hash,
           V: Deserialize<'de>,
+          S: BuildHasher + Default,
+          A: NodeStorage<K, V>,
 {
-    type Value = LinkedHashMap<K, V>;
+    type Value = LinkedHashMap<K, V, S, A>;
 
     fn expecting(&self, formatter: &mut Formatter) -> FmtResult {
         write!(formatter, "a map")
@@ -65,14 +77,14 @@ impl<'de, K, V> Visitor<'de> for LinkedHashMapVisitor<K, V>
     fn visit_unit<E>(self) -> Result<Self::Value, E>
         where E: Error,
     {
-        Ok(LinkedHashMap::new())
+        Ok(LinkedHashMap::with_parts(0, S::default()))
     }
 
     #[inline]
     fn visit_map<M>(self, mut map: M) -> Result<Self::Value, M::Error>
         where M: MapAccess<'de>,
     {
-        let mut values = LinkedHashMap::with_capacity(map.size_hint().unwrap_or(0));
+        let mut values = LinkedHashMap::with_parts(cautious_capacity(map.size_hint()), S::default());
 
         while let Some((key, value)) = map.next_entry()? {
             values.insert(key, value);
@@ -82,13 +94,100 @@ impl<'de, K, V> Visitor<'de> for LinkedHashMapVisitor<K, V>
     }
 }
 
-impl<'de, K, V> Deserialize<'de> for LinkedHashMap<K, V>
+impl<'de, K, V, S, A> Deserialize<'de> for LinkedHashMap<K, V, S, A>
     where K: Deserialize<'de> + Eq + Hash,
           V: Deserialize<'de>,
+          S: BuildHasher + Default,
+          A: NodeStorage<K, V>,
 {
-    fn deserialize<D>(deserializer: D) -> Result<LinkedHashMap<K, V>, D::Error>
+    fn deserialize<D>(deserializer: D) -> Result<LinkedHashMap<K, V, S, A>, D::Error>
         where D: Deserializer<'de>,
     {
         deserializer.deserialize_map(LinkedHashMapVisitor::new())
     }
 }
+
+/// Serialization of a linked hash map as a sequence of `[key, value]` pairs, for use with
+/// `#[serde(with = "...")]` attribute.
+///
+/// Useful for formats that do not preserve the order of map entries, or do not support
+/// non-string keys.
+///
+/// ```
+/// # extern crate serde_json;
+/// # extern crate kg_utils;
+/// use kg_utils::collections::LinkedHashMap;
+/// use kg_utils::collections::linked_hash_map::serde::as_pairs;
+///
+/// let mut map = LinkedHashMap::new();
+/// map.insert((1, 2), "a");
+/// map.insert((0, 0), "b");
+///
+/// let mut buf = Vec::new();
+/// as_pairs::serialize(&map, &mut serde_json::Serializer::new(&mut buf)).unwrap();
+/// assert_eq!(String::from_utf8(buf).unwrap(), r#"[[[1,2],"a"],[[0,0],"b"]]"#);
+///
+/// let mut de = serde_json::Deserializer::from_str(r#"[[[0,0],"b"],[[1,2],"a"]]"#);
+/// let map: LinkedHashMap<(i32, i32), String> = as_pairs::deserialize(&mut de).unwrap();
+/// assert_eq!(map.keys().collect::<Vec<_>>(), [&(0, 0), &(1, 2)]);
+/// ```
+pub mod as_pairs {
+    use super::*;
+
+    use super::serde::de::SeqAccess;
+    use super::serde::ser::SerializeSeq;
+
+    /// Serializes the map as a sequence of `[key, value]` pairs, in map order.
+    pub fn serialize<K, V, S, A, T>(map: &LinkedHashMap<K, V, S, A>, serializer: T) -> Result<T::Ok, T::Error>
+        where K: Serialize + Eq + Hash,
+              V: Serialize,
+              S: BuildHasher,
+              A: NodeStorage<K, V>,
+              T: Serializer,
+    {
+        let mut seq = serializer.serialize_seq(Some(map.len()))?;
+        for e in map {
+            seq.serialize_element(&e)?;
+        }
+        seq.end()
+    }
+
+    /// Deserializes the map from a sequence of `[key, value]` pairs. For duplicate keys the last
+    /// value is kept, as with [`LinkedHashMap::insert`].
+    pub fn deserialize<'de, K, V, S, A, D>(deserializer: D) -> Result<LinkedHashMap<K, V, S, A>, D::Error>
+        where K: Deserialize<'de> + Eq + Hash,
+              V: Deserialize<'de>,
+              S: BuildHasher + Default,
+              A: NodeStorage<K, V>,
+              D: Deserializer<'de>,
+    {
+        deserializer.deserialize_seq(PairsVisitor(PhantomData))
+    }
+
+    struct PairsVisitor<K, V, S, A: NodeStorage<K, V>>(PhantomData<LinkedHashMap<K, V, S, A>>);
+
+    impl<'de, K, V, S, A> Visitor<'de> for PairsVisitor<K, V, S, A>
+        where K: Deserialize<'de> + Eq + Hash,
+              V: Deserialize<'de>,
+              S: BuildHasher + Default,
+              A: NodeStorage<K, V>,
+    {
+        type Value = LinkedHashMap<K, V, S, A>;
+
+        fn expecting(&self, formatter: &mut Formatter) -> FmtResult {
+            write!(formatter, "a sequence of key-value pairs")
+        }
+
+        fn visit_seq<Q>(self, mut seq: Q) -> Result<Self::Value, Q::Error>
+            where Q: SeqAccess<'de>,
+        {
+            let mut values = LinkedHashMap::with_parts(cautious_capacity(seq.size_hint()), S::default());
+
+            while let Some((key, value)) = seq.next_element()? {
+                values.insert(key, value);
+            }
+
+            Ok(values)
+        }
+    }
+}
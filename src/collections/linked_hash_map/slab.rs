@@ -0,0 +1,294 @@
+//! Node storage keeping all nodes of a `LinkedHashMap` in a single `Vec`-backed slab.
+//!
+//! Nodes are linked with `u32` indices instead of pointers to separately allocated nodes,
+//! which improves cache locality of iteration and avoids per-entry allocations. Slots of removed
+//! entries are recycled by subsequent inserts, and compacted by `shrink_to_fit`.
+
+use std::cell::UnsafeCell;
+use std::collections::hash_map;
+use std::mem::MaybeUninit;
+use std::ptr;
+
+use super::{sealed, LinkedHashMap, Node, NodeStorage};
+
+const NIL: u32 = u32::MAX;
+
+/// Node storage keeping all nodes of a [`LinkedHashMap`] in a single `Vec`. Slots of removed
+/// entries are kept in a free list and reused by subsequent inserts.
+pub struct SlabNodes<K, V> {
+    slots: Vec<UnsafeCell<MaybeUninit<Node<K, V, u32>>>>,
+    // free slots linked by `next`, terminated by `NIL`
+    free: u32,
+}
+
+impl<K, V> SlabNodes<K, V> {
+    fn alloc_slot(&mut self) -> u32 {
+        if self.free != NIL {
+            let slot = self.free;
+            self.free = unsafe { (*self.ptr(slot)).next };
+            slot
+        } else {
+            assert!(self.slots.len() < NIL as usize, "capacity overflow");
+            self.slots.push(UnsafeCell::new(MaybeUninit::uninit()));
+            (self.slots.len() - 1) as u32
+        }
+    }
+}
+
+impl<K, V> sealed::Sealed for SlabNodes<K, V> {}
+
+impl<K, V> NodeStorage<K, V> for SlabNodes<K, V> {
+    type Handle = u32;
+
+    #[inline]
+    fn with_capacity(capacity: usize) -> Self {
+        SlabNodes {
+            slots: Vec::with_capacity(capacity),
+            free: NIL,
+        }
+    }
+
+    #[inline]
+    fn reserve(&mut self, additional: usize) {
+        self.slots.reserve(additional);
+    }
+
+    #[inline]
+    fn dangling() -> u32 {
+        NIL
+    }
+
+    #[inline]
+    fn alloc(&mut self, k: K, v: V) -> u32 {
+        let slot = self.alloc_slot();
+        unsafe {
+            let p = self.ptr(slot);
+            ptr::addr_of_mut!((*p).key).write(k);
+            ptr::addr_of_mut!((*p).value).write(v);
+        }
+        slot
+    }
+
+    #[inline]
+    fn alloc_guard(&mut self) -> u32 {
+        self.alloc_slot()
+    }
+
+    #[inline]
+    fn ptr(&self, node: u32) -> *mut Node<K, V, u32> {
+        debug_assert!((node as usize) < self.slots.len());
+        unsafe { UnsafeCell::raw_get(self.slots.as_ptr().add(node as usize)) as *mut _ }
+    }
+
+    #[inline]
+    unsafe fn release(&mut self, node: u32) {
+        ptr::addr_of_mut!((*self.ptr(node)).next).write(self.free);
+        self.free = node;
+    }
+
+    unsafe fn shrink_to_fit(&mut self, head: Option<&mut u32>) -> bool {
+        let head = match head {
+            Some(head) if self.free != NIL => head,
+            Some(_) => {
+                self.slots.shrink_to_fit();
+                return false;
+            }
+            None => {
+                // without the guard node all slots are free
+                self.slots = Vec::new();
+                self.free = NIL;
+                return false;
+            }
+        };
+
+        // move nodes to the front of a new slab in list order, starting with the guard node
+        let mut count = 1;
+        let mut cur = (*self.ptr(*head)).next;
+        while cur != *head {
+            count += 1;
+            cur = (*self.ptr(cur)).next;
+        }
+        let mut slots: Vec<UnsafeCell<MaybeUninit<Node<K, V, u32>>>> = Vec::with_capacity(count);
+        let mut cur = *head;
+        for i in 0..count {
+            let next = (*self.ptr(cur)).next;
+            slots.push(UnsafeCell::new(ptr::read(self.ptr(cur) as *const MaybeUninit<_>)));
+            let p = UnsafeCell::raw_get(slots.as_ptr().add(i)) as *mut Node<K, V, u32>;
+            (*p).prev = ((i + count - 1) % count) as u32;
+            (*p).next = ((i + 1) % count) as u32;
+            cur = next;
+        }
+        self.slots = slots;
+        self.free = NIL;
+        *head = 0;
+        true
+    }
+
+    #[cfg(feature = "heapsize_impl")]
+    unsafe fn heap_size_of_nodes(&self, _head: Option<u32>) -> usize {
+        if self.slots.capacity() > 0 {
+            ::heapsize::heap_size_of(self.slots.as_ptr() as *const _)
+        } else {
+            0
+        }
+    }
+}
+
+unsafe impl<K: Send, V: Send> Send for SlabNodes<K, V> {}
+
+unsafe impl<K: Sync, V: Sync> Sync for SlabNodes<K, V> {}
+
+/// A [`LinkedHashMap`] keeping all entries in a single slab, see [`SlabNodes`].
+///
+/// Maps are created with the constructors generic over node storage, like
+/// [new_in()][LinkedHashMap::new_in()], or with `Default`.
+///
+/// # Examples
+///
+/// ```
+/// use kg_utils::collections::SlabLinkedHashMap;
+///
+/// let mut map = SlabLinkedHashMap::new_in();
+/// map.insert("a", 1);
+/// map.insert("b", 2);
+/// map.insert("c", 3);
+/// map.remove("b");
+/// map.shrink_to_fit();
+///
+/// assert_eq!(map.iter().collect::<Vec<_>>(), [(&"a", &1), (&"c", &3)]);
+/// ```
+pub type SlabLinkedHashMap<K, V, S = hash_map::RandomState> = LinkedHashMap<K, V, S, SlabNodes<K, V>>;
+
+#[cfg(test)]
+mod tests {
+    use std::hash::{BuildHasherDefault, Hasher};
+
+    use super::super::{BoxedNodes, Entry};
+    use super::*;
+
+    fn assert_same(map: &SlabLinkedHashMap<u32, u32>, model: &LinkedHashMap<u32, u32>) {
+        assert_eq!(map.len(), model.len());
+        assert!(map.iter().eq(model.iter()));
+        assert!(map.iter().rev().eq(model.iter().rev()));
+        for k in 0..64 {
+            assert_eq!(map.get(&k), model.get(&k));
+        }
+    }
+
+    #[test]
+    fn operations_match_linked_hash_map() {
+        let mut map = SlabLinkedHashMap::new_in();
+        let mut model = LinkedHashMap::new();
+        let mut seed = 1u32;
+        for i in 0..5000 {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            let k = (seed >> 8) % 64;
+            match (seed >> 4) % 8 {
+                0..=2 => assert_eq!(map.insert(k, i), model.insert(k, i)),
+                3 | 4 => assert_eq!(map.remove(&k), model.remove(&k)),
+                5 => assert_eq!(map.pop_front(), model.pop_front()),
+                6 => assert_eq!(map.get_refresh(&k), model.get_refresh(&k)),
+                _ => {
+                    map.retain(|k, _| k % 7 != 0);
+                    model = model.into_iter().filter(|e| e.0 % 7 != 0).collect();
+                }
+            }
+            if i % 1000 == 999 {
+                map.shrink_to_fit();
+            }
+            assert_same(&map, &model);
+        }
+    }
+
+    #[test]
+    fn entry_api_and_drain() {
+        let mut map = SlabLinkedHashMap::new_in();
+        for c in "a short treatise on fungi".chars() {
+            *map.entry(c).or_insert(0) += 1;
+        }
+        assert_eq!(map[&'t'], 3);
+        match map.entry('s') {
+            Entry::Occupied(e) => assert_eq!(e.remove(), 2),
+            Entry::Vacant(_) => unreachable!(),
+        }
+        assert!(!map.contains_key(&'s'));
+        let drained: Vec<_> = map.drain().collect();
+        assert_eq!(drained.len(), 12);
+        assert_eq!(drained[0], ('a', 2));
+        assert!(map.is_empty());
+        map.insert('x', 1);
+        assert_eq!(map.front(), Some((&'x', &1)));
+    }
+
+    #[test]
+    fn clone_and_into_iter() {
+        let mut map: SlabLinkedHashMap<String, usize> = (0..10).map(|i| (i.to_string(), i)).collect();
+        map.remove("3");
+        map.move_to_front("7");
+        let copy = map.clone();
+        assert_eq!(copy, map);
+        let keys: Vec<String> = map.into_iter().rev().map(|e| e.0).collect();
+        assert_eq!(keys, ["9", "8", "6", "5", "4", "2", "1", "0", "7"]);
+    }
+
+    #[test]
+    fn shrink_to_fit_compacts_slots() {
+        let mut map: SlabLinkedHashMap<u32, String> = (0..100).map(|i| (i, i.to_string())).collect();
+        map.retain(|k, _| k % 3 == 0);
+        map.move_to_front(&42);
+        map.shrink_to_fit();
+        assert_eq!(map.nodes.slots.len(), map.len() + 1);
+        assert_eq!(map.keys().next(), Some(&42));
+        assert!(map.keys().skip(1).cloned().eq((0..100).filter(|k| k % 3 == 0 && *k != 42)));
+        for k in 0..100 {
+            assert_eq!(map.get(&k).is_some(), k % 3 == 0);
+        }
+        assert_eq!(map.get_index(1), Some((&0, &"0".to_string())));
+    }
+
+    /// Hasher mapping all keys to the same hash.
+    #[derive(Default)]
+    struct ZeroHasher;
+
+    impl Hasher for ZeroHasher {
+        fn finish(&self) -> u64 { 0 }
+
+        fn write(&mut self, _bytes: &[u8]) {}
+    }
+
+    fn check_colliding_hashes<A: NodeStorage<u32, u32>>(mut map: LinkedHashMap<u32, u32, BuildHasherDefault<ZeroHasher>, A>) {
+        for i in 0..300 {
+            assert_eq!(map.insert(i, i), None);
+        }
+        for i in (0..300).step_by(2) {
+            assert_eq!(map.remove(&i), Some(i));
+        }
+        for i in 0..300 {
+            assert_eq!(map.get(&i), if i % 2 == 1 { Some(&i) } else { None });
+        }
+        map.shrink_to_fit();
+        for i in (0..300).step_by(2) {
+            assert_eq!(map.insert(i, i * 10), None);
+        }
+        assert_eq!(map.len(), 300);
+        assert!(map.keys().cloned().eq((1..300).step_by(2).chain((0..300).step_by(2))));
+        assert_eq!(map.get(&298), Some(&2980));
+        assert_eq!(map.get(&300), None);
+        map.clear();
+        assert!(map.is_empty());
+        assert_eq!(map.get(&1), None);
+    }
+
+    #[test]
+    fn colliding_hashes() {
+        check_colliding_hashes(LinkedHashMap::<_, _, _, BoxedNodes<_, _>>::with_hasher(Default::default()));
+        check_colliding_hashes(SlabLinkedHashMap::with_hasher_in(Default::default()));
+    }
+
+    #[cfg(feature = "serde_impl")]
+    #[test]
+    fn serde_round_trip() {
+        let map: SlabLinkedHashMap<String, u32> = serde_json::from_str(r#"{"b": 1, "a": 2}"#).unwrap();
+        assert_eq!(serde_json::to_string(&map).unwrap(), r#"{"b":1,"a":2}"#);
+    }
+}
@@ -1,10 +1,23 @@
-pub use self::linked_hash_map::LinkedHashMap;
+pub use self::bit_set::BitSet;
+pub use self::comparator::{ByKey, Comparator, Natural};
+pub use self::linked_hash_map::{LinkedHashMap, SlabLinkedHashMap};
 pub use self::lru_cache::LruCache;
+pub use self::ord_map::OrdMap;
 pub use self::ord_set::OrdSet;
-pub use self::sparse_set::SparseSet;
+pub use self::ord_set_by::OrdSetBy;
+pub use self::small_ord_set::SmallOrdSet;
+pub use self::sparse_index::{SparseIndex, SparsePos};
+pub use self::sparse_map::SparseMap;
+pub use self::sparse_set::{OutOfRange, SafeSparseSet, SparseSet};
 
-mod ord_set;
-mod sparse_set;
+mod sparse_index;
+pub mod bit_set;
+pub mod comparator;
 pub mod linked_hash_map;
 pub mod lru_cache;
-
+pub mod ord_map;
+pub mod ord_set;
+pub mod ord_set_by;
+pub mod small_ord_set;
+pub mod sparse_map;
+pub mod sparse_set;
@@ -0,0 +1,590 @@
+use std::borrow::Borrow;
+use std::iter::FromIterator;
+use std::ops::{Bound, Deref, Index, RangeBounds};
+use std::slice;
+use std::vec::IntoIter;
+
+/// Simple implementation of an ordered map, using `std::vec::Vec<(K, V)>` as underlying storage.
+///
+/// Entries in the map are ordered by key and keys are unique.
+/// This collection is efficient only for relatively small number of entries as the cost of
+/// insert into underlying `Vec<_>` is O(n).
+/// Key lookup is performed with binary search algorithm.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct OrdMap<K: Ord, V>(Vec<(K, V)>);
+
+impl<K: Ord, V> OrdMap<K, V> {
+    pub fn new() -> OrdMap<K, V> {
+        OrdMap(Vec::new())
+    }
+
+    #[inline]
+    pub fn with_capacity(capacity: usize) -> OrdMap<K, V> {
+        OrdMap(Vec::with_capacity(capacity))
+    }
+
+    /// Creates a map from a vector of entries, which is sorted by key in the process.
+    /// For duplicate keys, the last entry wins.
+    pub fn from_vec(mut entries: Vec<(K, V)>) -> OrdMap<K, V> {
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        entries.dedup_by(|next, prev| {
+            if next.0 == prev.0 {
+                std::mem::swap(next, prev);
+                true
+            } else {
+                false
+            }
+        });
+        OrdMap(entries)
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.0.capacity()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    #[inline]
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+
+    #[inline]
+    pub fn as_slice(&self) -> &[(K, V)] {
+        self.0.as_slice()
+    }
+
+    #[inline]
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+        where K: Borrow<Q>, Q: ?Sized + Ord
+    {
+        self.search(key).is_ok()
+    }
+
+    #[inline]
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+        where K: Borrow<Q>, Q: ?Sized + Ord
+    {
+        match self.search(key) {
+            Ok(index) => Some(&self.0[index].1),
+            Err(_) => None,
+        }
+    }
+
+    #[inline]
+    pub fn get_key_value<Q>(&self, key: &Q) -> Option<(&K, &V)>
+        where K: Borrow<Q>, Q: ?Sized + Ord
+    {
+        match self.search(key) {
+            Ok(index) => {
+                let e = &self.0[index];
+                Some((&e.0, &e.1))
+            }
+            Err(_) => None,
+        }
+    }
+
+    #[inline]
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+        where K: Borrow<Q>, Q: ?Sized + Ord
+    {
+        match self.search(key) {
+            Ok(index) => Some(&mut self.0[index].1),
+            Err(_) => None,
+        }
+    }
+
+    /// Inserts a key-value pair into the map. If the key already existed, the old value is
+    /// returned and the key is not updated.
+    #[inline]
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        match self.search(&key) {
+            Ok(index) => Some(std::mem::replace(&mut self.0[index].1, value)),
+            Err(index) => {
+                self.0.insert(index, (key, value));
+                None
+            }
+        }
+    }
+
+    #[inline]
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+        where K: Borrow<Q>, Q: ?Sized + Ord
+    {
+        self.remove_entry(key).map(|e| e.1)
+    }
+
+    #[inline]
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(K, V)>
+        where K: Borrow<Q>, Q: ?Sized + Ord
+    {
+        match self.search(key) {
+            Ok(index) => Some(self.0.remove(index)),
+            Err(_) => None,
+        }
+    }
+
+    #[inline]
+    pub fn retain<F>(&mut self, mut f: F) where F: FnMut(&K, &mut V) -> bool {
+        self.0.retain_mut(|e| f(&e.0, &mut e.1))
+    }
+
+    /// Gets the given key's corresponding entry in the map for in-place manipulation.
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V> {
+        match self.search(&key) {
+            Ok(index) => Entry::Occupied(OccupiedEntry { map: self, index }),
+            Err(index) => Entry::Vacant(VacantEntry { map: self, key, index }),
+        }
+    }
+
+    /// Returns the entry with the smallest key.
+    #[inline]
+    pub fn first_key_value(&self) -> Option<(&K, &V)> {
+        self.0.first().map(|e| (&e.0, &e.1))
+    }
+
+    /// Returns the entry with the greatest key.
+    #[inline]
+    pub fn last_key_value(&self) -> Option<(&K, &V)> {
+        self.0.last().map(|e| (&e.0, &e.1))
+    }
+
+    /// Returns a slice of entries with keys contained in the given range. The slice is empty if
+    /// range start is greater than range end.
+    ///
+    /// ```
+    /// use kg_utils::collections::OrdMap;
+    ///
+    /// let map: OrdMap<_, _> = vec![(1, "a"), (3, "b"), (5, "c")].into_iter().collect();
+    /// assert_eq!(map.range(2..), &[(3, "b"), (5, "c")]);
+    /// ```
+    pub fn range<Q, R>(&self, range: R) -> &[(K, V)]
+        where K: Borrow<Q>, Q: ?Sized + Ord, R: RangeBounds<Q>
+    {
+        let start = match range.start_bound() {
+            Bound::Included(k) => self.0.partition_point(|e| e.0.borrow() < k),
+            Bound::Excluded(k) => self.0.partition_point(|e| e.0.borrow() <= k),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(k) => self.0.partition_point(|e| e.0.borrow() <= k),
+            Bound::Excluded(k) => self.0.partition_point(|e| e.0.borrow() < k),
+            Bound::Unbounded => self.0.len(),
+        };
+        &self.0[start..std::cmp::max(start, end)]
+    }
+
+    /// Returns an iterator visiting all keys in ascending order.
+    pub fn keys(&self) -> Keys<'_, K, V> {
+        Keys { inner: self.0.iter() }
+    }
+
+    /// Returns an iterator visiting all values in ascending order of keys.
+    pub fn values(&self) -> Values<'_, K, V> {
+        Values { inner: self.0.iter() }
+    }
+
+    /// Returns an iterator visiting all values mutably in ascending order of keys.
+    pub fn values_mut(&mut self) -> ValuesMut<'_, K, V> {
+        ValuesMut { inner: self.0.iter_mut() }
+    }
+
+    /// Returns an iterator visiting all entries in ascending order of keys, with mutable
+    /// references to the values.
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        IterMut { inner: self.0.iter_mut() }
+    }
+
+    #[inline]
+    fn search<Q>(&self, key: &Q) -> Result<usize, usize>
+        where K: Borrow<Q>, Q: ?Sized + Ord
+    {
+        self.0.binary_search_by(|e| e.0.borrow().cmp(key))
+    }
+}
+
+impl<K: Ord, V> Default for OrdMap<K, V> {
+    fn default() -> Self {
+        OrdMap::new()
+    }
+}
+
+impl<K: Ord, V> FromIterator<(K, V)> for OrdMap<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        OrdMap::from_vec(iter.into_iter().collect())
+    }
+}
+
+impl<K: Ord, V> Extend<(K, V)> for OrdMap<K, V> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (k, v) in iter {
+            self.insert(k, v);
+        }
+    }
+}
+
+impl<K: Ord, V> IntoIterator for OrdMap<K, V> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<(K, V)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a, K: Ord, V> IntoIterator for &'a OrdMap<K, V> {
+    type Item = &'a (K, V);
+    type IntoIter = slice::Iter<'a, (K, V)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+impl<K, V> Deref for OrdMap<K, V>
+    where K: Ord
+{
+    type Target = [(K, V)];
+
+    fn deref(&self) -> &[(K, V)] {
+        self.0.deref()
+    }
+}
+
+impl<'a, K, V, Q: ?Sized> Index<&'a Q> for OrdMap<K, V>
+    where K: Ord + Borrow<Q>, Q: Ord
+{
+    type Output = V;
+
+    fn index(&self, key: &'a Q) -> &V {
+        self.get(key).expect("no entry found for key")
+    }
+}
+
+impl<K, V> std::fmt::Debug for OrdMap<K, V>
+    where K: Ord + std::fmt::Debug, V: std::fmt::Debug
+{
+    #[inline]
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_map().entries(self.0.iter().map(|e| (&e.0, &e.1))).finish()
+    }
+}
+
+/// An ascending-order iterator over an `OrdMap`'s keys.
+pub struct Keys<'a, K: 'a, V: 'a> {
+    inner: slice::Iter<'a, (K, V)>,
+}
+
+impl<'a, K, V> Iterator for Keys<'a, K, V> {
+    type Item = &'a K;
+
+    #[inline] fn next(&mut self) -> Option<&'a K> { self.inner.next().map(|e| &e.0) }
+    #[inline] fn size_hint(&self) -> (usize, Option<usize>) { self.inner.size_hint() }
+}
+
+impl<'a, K, V> DoubleEndedIterator for Keys<'a, K, V> {
+    #[inline] fn next_back(&mut self) -> Option<&'a K> { self.inner.next_back().map(|e| &e.0) }
+}
+
+impl<'a, K, V> ExactSizeIterator for Keys<'a, K, V> {}
+
+/// An iterator over an `OrdMap`'s values, in ascending order of keys.
+pub struct Values<'a, K: 'a, V: 'a> {
+    inner: slice::Iter<'a, (K, V)>,
+}
+
+impl<'a, K, V> Iterator for Values<'a, K, V> {
+    type Item = &'a V;
+
+    #[inline] fn next(&mut self) -> Option<&'a V> { self.inner.next().map(|e| &e.1) }
+    #[inline] fn size_hint(&self) -> (usize, Option<usize>) { self.inner.size_hint() }
+}
+
+impl<'a, K, V> DoubleEndedIterator for Values<'a, K, V> {
+    #[inline] fn next_back(&mut self) -> Option<&'a V> { self.inner.next_back().map(|e| &e.1) }
+}
+
+impl<'a, K, V> ExactSizeIterator for Values<'a, K, V> {}
+
+/// An iterator over mutable references to an `OrdMap`'s values, in ascending order of keys.
+pub struct ValuesMut<'a, K: 'a, V: 'a> {
+    inner: slice::IterMut<'a, (K, V)>,
+}
+
+impl<'a, K, V> Iterator for ValuesMut<'a, K, V> {
+    type Item = &'a mut V;
+
+    #[inline] fn next(&mut self) -> Option<&'a mut V> { self.inner.next().map(|e| &mut e.1) }
+    #[inline] fn size_hint(&self) -> (usize, Option<usize>) { self.inner.size_hint() }
+}
+
+impl<'a, K, V> DoubleEndedIterator for ValuesMut<'a, K, V> {
+    #[inline] fn next_back(&mut self) -> Option<&'a mut V> { self.inner.next_back().map(|e| &mut e.1) }
+}
+
+impl<'a, K, V> ExactSizeIterator for ValuesMut<'a, K, V> {}
+
+/// An ascending-order iterator over an `OrdMap`'s entries, with mutable references to the values.
+pub struct IterMut<'a, K: 'a, V: 'a> {
+    inner: slice::IterMut<'a, (K, V)>,
+}
+
+impl<'a, K, V> Iterator for IterMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+
+    #[inline] fn next(&mut self) -> Option<(&'a K, &'a mut V)> { self.inner.next().map(|e| (&e.0, &mut e.1)) }
+    #[inline] fn size_hint(&self) -> (usize, Option<usize>) { self.inner.size_hint() }
+}
+
+impl<'a, K, V> DoubleEndedIterator for IterMut<'a, K, V> {
+    #[inline] fn next_back(&mut self) -> Option<(&'a K, &'a mut V)> { self.inner.next_back().map(|e| (&e.0, &mut e.1)) }
+}
+
+impl<'a, K, V> ExactSizeIterator for IterMut<'a, K, V> {}
+
+/// A view into a single entry in an `OrdMap`, which may be vacant or occupied.
+pub enum Entry<'a, K: Ord + 'a, V: 'a> {
+    /// An occupied entry.
+    Occupied(OccupiedEntry<'a, K, V>),
+    /// A vacant entry.
+    Vacant(VacantEntry<'a, K, V>),
+}
+
+/// A view into an occupied entry in an `OrdMap`.
+pub struct OccupiedEntry<'a, K: Ord + 'a, V: 'a> {
+    map: &'a mut OrdMap<K, V>,
+    index: usize,
+}
+
+/// A view into a vacant entry in an `OrdMap`.
+pub struct VacantEntry<'a, K: Ord + 'a, V: 'a> {
+    map: &'a mut OrdMap<K, V>,
+    key: K,
+    index: usize,
+}
+
+impl<'a, K: Ord, V> Entry<'a, K, V> {
+    pub fn key(&self) -> &K {
+        match *self {
+            Entry::Occupied(ref e) => e.key(),
+            Entry::Vacant(ref e) => e.key(),
+        }
+    }
+
+    /// Ensures a value is in the entry by inserting the default if empty, and returns
+    /// a mutable reference to the value in the entry.
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        match self {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => e.insert(default),
+        }
+    }
+
+    /// Ensures a value is in the entry by inserting the result of the default function if empty,
+    /// and returns a mutable reference to the value in the entry.
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a mut V {
+        match self {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => e.insert(default()),
+        }
+    }
+
+    /// Ensures a value is in the entry by inserting the default value if empty, and returns
+    /// a mutable reference to the value in the entry.
+    pub fn or_default(self) -> &'a mut V where V: Default {
+        self.or_insert_with(V::default)
+    }
+
+    /// Provides in-place mutable access to an occupied entry.
+    pub fn and_modify<F: FnOnce(&mut V)>(self, f: F) -> Self {
+        match self {
+            Entry::Occupied(mut e) => {
+                f(e.get_mut());
+                Entry::Occupied(e)
+            }
+            Entry::Vacant(e) => Entry::Vacant(e),
+        }
+    }
+}
+
+impl<'a, K: Ord, V> OccupiedEntry<'a, K, V> {
+    pub fn key(&self) -> &K {
+        &self.map.0[self.index].0
+    }
+
+    pub fn get(&self) -> &V {
+        &self.map.0[self.index].1
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.map.0[self.index].1
+    }
+
+    pub fn into_mut(self) -> &'a mut V {
+        &mut self.map.0[self.index].1
+    }
+
+    /// Sets the value of the entry, and returns the entry's old value.
+    pub fn insert(&mut self, value: V) -> V {
+        std::mem::replace(&mut self.map.0[self.index].1, value)
+    }
+
+    /// Takes the value out of the entry, and returns it.
+    pub fn remove(self) -> V {
+        self.map.0.remove(self.index).1
+    }
+}
+
+impl<'a, K: Ord, V> VacantEntry<'a, K, V> {
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Sets the value of the entry, and returns a mutable reference to it.
+    pub fn insert(self, value: V) -> &'a mut V {
+        self.map.0.insert(self.index, (self.key, value));
+        &mut self.map.0[self.index].1
+    }
+}
+
+#[cfg(feature = "serde_impl")]
+mod serde {
+    extern crate serde;
+
+    use std::marker::PhantomData;
+
+    use super::*;
+
+    use self::serde::{de, ser};
+
+    impl<K, V> ser::Serialize for OrdMap<K, V>
+        where K: Ord + ser::Serialize, V: ser::Serialize
+    {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+            where S: ser::Serializer
+        {
+            serializer.collect_map(self.0.iter().map(|e| (&e.0, &e.1)))
+        }
+    }
+
+    struct OrdMapVisitor<K, V> {
+        marker: PhantomData<(K, V)>,
+    }
+
+    impl<'de, K, V> de::Visitor<'de> for OrdMapVisitor<K, V>
+        where K: Ord + de::Deserialize<'de>, V: de::Deserialize<'de>
+    {
+        type Value = OrdMap<K, V>;
+
+        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            f.write_str("a map")
+        }
+
+        fn visit_map<M>(self, mut map: M) -> Result<Self::Value, M::Error>
+            where M: de::MapAccess<'de>
+        {
+            let mut entries = Vec::with_capacity(std::cmp::min(map.size_hint().unwrap_or(0), 4096));
+            while let Some(entry) = map.next_entry()? {
+                entries.push(entry);
+            }
+            Ok(OrdMap::from_vec(entries))
+        }
+    }
+
+    impl<'de, K, V> de::Deserialize<'de> for OrdMap<K, V>
+        where K: Ord + de::Deserialize<'de>, V: de::Deserialize<'de>
+    {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+            where D: de::Deserializer<'de>
+        {
+            deserializer.deserialize_map(OrdMapVisitor { marker: PhantomData })
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    #[test]
+    fn operations_match_btree_map() {
+        let mut map = OrdMap::new();
+        let mut bmap = BTreeMap::new();
+        let mut seed = 1u32;
+        for i in 0..2000 {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            let k = (seed >> 8) % 300;
+            match (seed >> 4) % 3 {
+                0 => assert_eq!(map.remove(&k), bmap.remove(&k)),
+                _ => assert_eq!(map.insert(k, i), bmap.insert(k, i)),
+            }
+        }
+        assert!(map.iter().map(|e| (&e.0, &e.1)).eq(bmap.iter()));
+        for k in 0..300 {
+            assert_eq!(map.get(&k), bmap.get(&k));
+        }
+        assert!(map.range(100..200).iter().map(|e| (&e.0, &e.1)).eq(bmap.range(100..200)));
+    }
+
+    #[test]
+    fn borrowed_key_lookup() {
+        let mut map = OrdMap::new();
+        map.insert("b".to_string(), 2);
+        map.insert("a".to_string(), 1);
+        assert_eq!(map.get("a"), Some(&1));
+        assert_eq!(map["b"], 2);
+        assert_eq!(map.range::<str, _>((Bound::Included("b"), Bound::Unbounded)).len(), 1);
+        assert_eq!(map.remove("a"), Some(1));
+        assert!(!map.contains_key("a"));
+    }
+
+    #[test]
+    fn entry_api() {
+        let mut map = OrdMap::new();
+        for c in "a short treatise on fungi".chars() {
+            *map.entry(c).or_insert(0) += 1;
+        }
+        assert_eq!(map[&'s'], 2);
+        assert_eq!(map[&'t'], 3);
+        map.entry('t').and_modify(|v| *v = 10).or_insert(0);
+        assert_eq!(map[&'t'], 10);
+        match map.entry('u') {
+            Entry::Occupied(e) => assert_eq!(e.remove(), 1),
+            Entry::Vacant(_) => unreachable!(),
+        }
+        assert!(map.keys().zip(map.keys().skip(1)).all(|(a, b)| a < b));
+    }
+
+    #[test]
+    fn from_iter_keeps_last_duplicate() {
+        let map: OrdMap<_, _> = vec![(2, "a"), (1, "b"), (2, "c")].into_iter().collect();
+        assert_eq!(map.as_slice(), &[(1, "b"), (2, "c")]);
+    }
+
+    #[cfg(feature = "serde_impl")]
+    #[test]
+    fn serde_accepts_unsorted_map() {
+        let map: OrdMap<String, u32> = serde_json::from_str(r#"{"c": 3, "a": 1, "b": 2}"#).unwrap();
+        assert_eq!(map.keys().map(|k| k.as_str()).collect::<Vec<_>>(), vec!["a", "b", "c"]);
+        assert_eq!(serde_json::to_string(&map).unwrap(), r#"{"a":1,"b":2,"c":3}"#);
+    }
+
+    #[cfg(feature = "serde_impl")]
+    #[test]
+    fn serde_does_not_trust_length_prefix() {
+        let bytes = [0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x0f];
+        assert!(bincode::deserialize::<OrdMap<u32, u32>>(&bytes).is_err());
+    }
+}
@@ -1,5 +1,9 @@
-use std::ops::Deref;
-use std::vec::IntoIter;
+use std::cmp::Ordering;
+use std::iter::Peekable;
+use std::slice;
+
+use super::comparator::{Comparator, Natural};
+use super::ord_set_by::OrdSetBy;
 
 /// Simple implementation of an ordered set, using `std::vec::Vec<_>` as underlying storage.
 ///
@@ -7,158 +11,266 @@ use std::vec::IntoIter;
 /// This collection is efficient only for relatively small number of elements as the cost of
 /// insert into underlying `Vec<_>` is O(n).
 /// Element lookup is performed with binary search algorithm.
-#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
-pub struct OrdSet<T: Ord>(Vec<T>);
+///
+/// Of elements comparing equal, the set keeps the one added last, be it by
+/// [insert()][OrdSetBy::insert()], [from_vec()][OrdSet::from_vec()], `collect()`, `extend()` or
+/// [extend_sorted()][OrdSetBy::extend_sorted()].
+///
+/// This is an [`OrdSetBy`] ordering elements by their natural order, which provides the rest of
+/// the API.
+pub type OrdSet<T> = OrdSetBy<T, Natural>;
 
 impl<T: Ord> OrdSet<T> {
     pub fn new() -> OrdSet<T> {
-        OrdSet(Vec::new())
+        OrdSetBy::with_comparator(Natural)
     }
 
     #[inline]
     pub fn with_capacity(capacity: usize) -> OrdSet<T> {
-        OrdSet(Vec::with_capacity(capacity))
+        OrdSetBy::with_capacity_and_comparator(capacity, Natural)
     }
 
-    #[inline]
-    pub fn len(&self) -> usize {
-        self.0.len()
+    /// Creates a set from a vector of elements, which is sorted and deduplicated in the process,
+    /// keeping the last of equal elements.
+    pub fn from_vec(elems: Vec<T>) -> OrdSet<T> {
+        OrdSetBy::from_vec_with_comparator(elems, Natural)
     }
 
-    #[inline]
-    pub fn capacity(&self) -> usize {
-        self.0.capacity()
+    /// Creates a set from a vector of elements, which must already be sorted in ascending order
+    /// and contain no duplicates. This is checked only in debug builds.
+    pub fn from_sorted_unchecked(elems: Vec<T>) -> OrdSet<T> {
+        debug_assert!(elems.windows(2).all(|w| w[0] < w[1]), "elements are not sorted or unique");
+        OrdSetBy::from_sorted(elems, Natural)
     }
+}
 
-    #[inline]
-    pub fn is_empty(&self) -> bool {
-        self.0.is_empty()
-    }
+/// Lazy iterator over union of two `OrdSet`s or `OrdSetBy`s, see [OrdSetBy::union()].
+pub struct Union<'a, T: 'a, C: 'a = Natural> {
+    pub(super) a: Peekable<slice::Iter<'a, T>>,
+    pub(super) b: Peekable<slice::Iter<'a, T>>,
+    pub(super) cmp: &'a C,
+}
 
-    #[inline]
-    pub fn contains(&self, elem: &T) -> bool {
-        self.0.binary_search(elem).is_ok()
-    }
+impl<'a, T, C: Comparator<T>> Iterator for Union<'a, T, C> {
+    type Item = &'a T;
 
-    #[inline]
-    pub fn insert(&mut self, elem: T) -> Option<T> {
-        match self.0.binary_search(&elem) {
-            Ok(index) => {
-                Some(std::mem::replace(&mut self.0[index], elem))
-            }
-            Err(index) => {
-                self.0.insert(index, elem);
-                None
+    fn next(&mut self) -> Option<&'a T> {
+        let ord = match (self.a.peek(), self.b.peek()) {
+            (Some(a), Some(b)) => self.cmp.compare(a, b),
+            (Some(_), None) => Ordering::Less,
+            (None, _) => return self.b.next(),
+        };
+        match ord {
+            Ordering::Less => self.a.next(),
+            Ordering::Equal => {
+                self.b.next();
+                self.a.next()
             }
+            Ordering::Greater => self.b.next(),
         }
     }
 
-    #[inline]
-    pub fn remove(&mut self, elem: &T) -> bool {
-        if let Ok(index) = self.0.binary_search(elem) {
-            self.0.remove(index);
-            true
-        } else {
-            false
-        }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (a, b) = (self.a.len(), self.b.len());
+        (std::cmp::max(a, b), Some(a + b))
     }
+}
 
-    #[inline]
-    pub fn clear(&mut self) {
-        self.0.clear();
-    }
+/// Lazy iterator over intersection of two `OrdSet`s or `OrdSetBy`s, see [OrdSetBy::intersection()].
+pub struct Intersection<'a, T: 'a, C: 'a = Natural> {
+    pub(super) a: Peekable<slice::Iter<'a, T>>,
+    pub(super) b: Peekable<slice::Iter<'a, T>>,
+    pub(super) cmp: &'a C,
+}
 
-    #[inline]
-    pub fn as_slice(&self)-> &[T] {
-        self.0.as_slice()
-    }
+impl<'a, T, C: Comparator<T>> Iterator for Intersection<'a, T, C> {
+    type Item = &'a T;
 
-    #[inline]
-    pub fn retain<F>(&mut self, f: F) where F: FnMut(&T) -> bool {
-        self.0.retain(f)
+    fn next(&mut self) -> Option<&'a T> {
+        loop {
+            let ord = match (self.a.peek(), self.b.peek()) {
+                (Some(a), Some(b)) => self.cmp.compare(a, b),
+                _ => return None,
+            };
+            match ord {
+                Ordering::Less => {
+                    self.a.next();
+                }
+                Ordering::Equal => {
+                    self.b.next();
+                    return self.a.next();
+                }
+                Ordering::Greater => {
+                    self.b.next();
+                }
+            }
+        }
     }
 
-    pub fn append<I>(&mut self, iter: I) -> bool
-        where I: Iterator<Item = T>
-    {
-        let mut changed = false;
-        for i in iter {
-            changed |= self.insert(i).is_none();
-        }
-        changed
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(std::cmp::min(self.a.len(), self.b.len())))
     }
 }
 
-impl<T: Ord> IntoIterator for OrdSet<T> {
-    type Item = T;
-    type IntoIter = IntoIter<T>;
+/// Lazy iterator over difference of two `OrdSet`s or `OrdSetBy`s, see [OrdSetBy::difference()].
+pub struct Difference<'a, T: 'a, C: 'a = Natural> {
+    pub(super) a: Peekable<slice::Iter<'a, T>>,
+    pub(super) b: Peekable<slice::Iter<'a, T>>,
+    pub(super) cmp: &'a C,
+}
+
+impl<'a, T, C: Comparator<T>> Iterator for Difference<'a, T, C> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        loop {
+            let ord = match (self.a.peek(), self.b.peek()) {
+                (Some(a), Some(b)) => self.cmp.compare(a, b),
+                _ => return self.a.next(),
+            };
+            match ord {
+                Ordering::Less => return self.a.next(),
+                Ordering::Equal => {
+                    self.a.next();
+                    self.b.next();
+                }
+                Ordering::Greater => {
+                    self.b.next();
+                }
+            }
+        }
+    }
 
-    fn into_iter(self) -> Self::IntoIter {
-        self.0.into_iter()
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.a.len().saturating_sub(self.b.len()), Some(self.a.len()))
     }
 }
 
-impl<T> Deref for OrdSet<T>
-    where T: Ord
-{
-    type Target = [T];
+/// Lazy iterator over symmetric difference of two `OrdSet`s or
+/// `OrdSetBy`s, see [OrdSetBy::symmetric_difference()].
+pub struct SymmetricDifference<'a, T: 'a, C: 'a = Natural> {
+    pub(super) a: Peekable<slice::Iter<'a, T>>,
+    pub(super) b: Peekable<slice::Iter<'a, T>>,
+    pub(super) cmp: &'a C,
+}
+
+impl<'a, T, C: Comparator<T>> Iterator for SymmetricDifference<'a, T, C> {
+    type Item = &'a T;
 
-    fn deref(&self) -> &[T] {
-        self.0.deref()
+    fn next(&mut self) -> Option<&'a T> {
+        loop {
+            let ord = match (self.a.peek(), self.b.peek()) {
+                (Some(a), Some(b)) => self.cmp.compare(a, b),
+                (Some(_), None) => Ordering::Less,
+                (None, _) => return self.b.next(),
+            };
+            match ord {
+                Ordering::Less => return self.a.next(),
+                Ordering::Equal => {
+                    self.a.next();
+                    self.b.next();
+                }
+                Ordering::Greater => return self.b.next(),
+            }
+        }
     }
-}
 
-impl<T> std::fmt::Debug for OrdSet<T>
-    where T: Ord + std::fmt::Debug
-{
-    #[inline]
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        f.debug_set().entries(self.0.iter()).finish()
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.a.len() + self.b.len()))
     }
 }
 
 #[cfg(feature = "serde_impl")]
-mod serde {
+pub mod serde {
+    //! Serialization of `OrdSet` as a sequence of elements.
+    //!
+    //! Deserialization with the `Deserialize` impl sorts elements and silently removes duplicates.
+    //! Submodules of this module can be used with `#[serde(with = "...")]` attribute to change
+    //! this behaviour.
+
     extern crate serde;
 
     use super::*;
 
     use self::serde::{de, ser};
 
-    impl<T> ser::Serialize for OrdSet<T>
-        where T: Ord + ser::Serialize
-    {
-        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-            where S: ser::Serializer
+    /// Sorts elements and silently removes duplicates. Same as the `Deserialize` impl.
+    pub mod dedup {
+        use super::*;
+
+        pub fn serialize<T, S>(set: &OrdSet<T>, serializer: S) -> Result<S::Ok, S::Error>
+            where T: Ord + ser::Serialize, S: ser::Serializer
         {
-            use self::ser::SerializeSeq;
+            ser::Serialize::serialize(set, serializer)
+        }
 
-            let mut seq = serializer.serialize_seq(Some(self.0.len()))?;
-            for e in self.0.iter() {
-                seq.serialize_element(e)?;
-            }
-            seq.end()
+        pub fn deserialize<'de, T, D>(deserializer: D) -> Result<OrdSet<T>, D::Error>
+            where T: Ord + de::Deserialize<'de>, D: de::Deserializer<'de>
+        {
+            de::Deserialize::deserialize(deserializer)
         }
     }
 
-    impl<'de, T> de::Deserialize<'de> for OrdSet<T>
-        where T: Ord + de::Deserialize<'de>
-    {
-        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
-            where D: de::Deserializer<'de>
+    /// Sorts elements and fails if any element occurs more than once.
+    pub mod strict {
+        use super::*;
+
+        pub use super::dedup::serialize;
+
+        pub fn deserialize<'de, T, D>(deserializer: D) -> Result<OrdSet<T>, D::Error>
+            where T: Ord + de::Deserialize<'de>, D: de::Deserializer<'de>
         {
-            let mut elems: Vec<T> = Vec::deserialize(deserializer)?;
+            let mut elems: Vec<T> = de::Deserialize::deserialize(deserializer)?;
             elems.sort();
-            Ok(OrdSet(elems))
+            if elems.windows(2).any(|w| w[0].cmp(&w[1]) == Ordering::Equal) {
+                return Err(de::Error::custom("duplicate element in set"));
+            }
+            Ok(OrdSet::from_sorted_unchecked(elems))
         }
     }
-}
 
+    /// Expects elements in strictly ascending order, skipping the sort. Fails if elements are not
+    /// sorted or contain duplicates.
+    pub mod sorted {
+        use super::*;
+
+        pub use super::dedup::serialize;
+
+        pub fn deserialize<'de, T, D>(deserializer: D) -> Result<OrdSet<T>, D::Error>
+            where T: Ord + de::Deserialize<'de>, D: de::Deserializer<'de>
+        {
+            let elems: Vec<T> = de::Deserialize::deserialize(deserializer)?;
+            for w in elems.windows(2) {
+                match w[0].cmp(&w[1]) {
+                    Ordering::Less => {}
+                    Ordering::Equal => return Err(de::Error::custom("duplicate element in set")),
+                    Ordering::Greater => return Err(de::Error::custom("elements are not sorted")),
+                }
+            }
+            Ok(OrdSet::from_sorted_unchecked(elems))
+        }
+    }
+}
 
 #[cfg(test)]
 mod tests {
+    use std::collections::BTreeSet;
+    use test::Bencher;
+
     use super::*;
 
+    fn random_sets(seed: &mut u32, count: usize, max: u32) -> (OrdSet<u32>, BTreeSet<u32>) {
+        let mut set = OrdSet::new();
+        let mut bset = BTreeSet::new();
+        for _ in 0..count {
+            *seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            let v = (*seed >> 8) % max;
+            set.insert(v);
+            bset.insert(v);
+        }
+        (set, bset)
+    }
+
     #[test]
     fn insert_should_return_previous_element() {
         let mut set = OrdSet::new();
@@ -166,6 +278,21 @@ mod tests {
         assert_eq!(set.insert(4), Some(4));
     }
 
+    #[test]
+    fn borrowed_lookup() {
+        let mut set: OrdSet<String> = vec!["b", "a", "c"].into_iter().map(String::from).collect();
+        assert!(set.contains("a"));
+        assert!(!set.contains("d"));
+        assert_eq!(set.get("b").map(|s| s.as_str()), Some("b"));
+        assert_eq!(set.position("c"), Some(2));
+        assert_eq!(set.position("d"), None);
+        assert_eq!(set.take("b"), Some("b".to_string()));
+        assert_eq!(set.take("b"), None);
+        assert!(set.remove("a"));
+        assert_eq!(set.replace("c".to_string()), Some("c".to_string()));
+        assert_eq!(set.as_slice(), &["c"]);
+    }
+
     #[test]
     fn elements_must_be_ordered() {
         let mut set = OrdSet::new();
@@ -175,4 +302,331 @@ mod tests {
         set.insert(6);
         assert_eq!(set.as_slice(), &[1, 2, 4, 6]);
     }
+
+    #[test]
+    fn set_algebra_matches_btree_set() {
+        let mut seed = 1;
+        for i in 0..30 {
+            let (a, ba) = random_sets(&mut seed, i * 3, 100);
+            let (b, bb) = random_sets(&mut seed, 40, 60 + i as u32);
+
+            assert!(a.union(&b).eq(ba.union(&bb)));
+            assert!(a.intersection(&b).eq(ba.intersection(&bb)));
+            assert!(a.difference(&b).eq(ba.difference(&bb)));
+            assert!(a.symmetric_difference(&b).eq(ba.symmetric_difference(&bb)));
+
+            assert_eq!((&a | &b).as_slice(), (&ba | &bb).into_iter().collect::<Vec<_>>().as_slice());
+            assert_eq!((&a & &b).as_slice(), (&ba & &bb).into_iter().collect::<Vec<_>>().as_slice());
+            assert_eq!((&a - &b).as_slice(), (&ba - &bb).into_iter().collect::<Vec<_>>().as_slice());
+            assert_eq!((&a ^ &b).as_slice(), (&ba ^ &bb).into_iter().collect::<Vec<_>>().as_slice());
+
+            assert_eq!(a.is_subset(&b), ba.is_subset(&bb));
+            assert_eq!(a.is_superset(&b), ba.is_superset(&bb));
+            assert_eq!(a.is_disjoint(&b), ba.is_disjoint(&bb));
+        }
+    }
+
+    #[test]
+    fn subset_and_disjoint() {
+        let mut a = OrdSet::new();
+        let mut b = OrdSet::new();
+        assert!(a.is_subset(&b));
+        assert!(a.is_disjoint(&b));
+        a.append(vec![2, 4].into_iter());
+        b.append(vec![1, 2, 3, 4, 5].into_iter());
+        assert!(a.is_subset(&b));
+        assert!(b.is_superset(&a));
+        assert!(!b.is_subset(&a));
+        assert!(!a.is_disjoint(&b));
+        a.insert(6);
+        assert!(!a.is_subset(&b));
+    }
+
+    #[test]
+    fn ordered_navigation() {
+        let mut set = OrdSet::new();
+        assert_eq!(set.first(), None);
+        assert_eq!(set.floor(&5), None);
+        set.append(vec![10, 20, 30, 40].into_iter());
+        assert_eq!(set.first(), Some(&10));
+        assert_eq!(set.last(), Some(&40));
+        assert_eq!(set.lower_bound(&20), 1);
+        assert_eq!(set.upper_bound(&20), 2);
+        assert_eq!(set.lower_bound(&45), 4);
+        assert_eq!(set.floor(&25), Some(&20));
+        assert_eq!(set.floor(&20), Some(&20));
+        assert_eq!(set.floor(&5), None);
+        assert_eq!(set.ceiling(&25), Some(&30));
+        assert_eq!(set.ceiling(&30), Some(&30));
+        assert_eq!(set.ceiling(&45), None);
+        assert_eq!(set.pop_first(), Some(10));
+        assert_eq!(set.pop_last(), Some(40));
+        assert_eq!(set.as_slice(), &[20, 30]);
+    }
+
+    #[test]
+    fn range_matches_btree_set() {
+        let mut seed = 3;
+        let (set, bset) = random_sets(&mut seed, 50, 100);
+        for a in (0..100).step_by(7) {
+            for b in (0..100).step_by(11) {
+                if a <= b {
+                    assert!(set.range(a..b).iter().eq(bset.range(a..b)));
+                    assert!(set.range(a..=b).iter().eq(bset.range(a..=b)));
+                }
+            }
+            assert!(set.range(a..).iter().eq(bset.range(a..)));
+            assert!(set.range(..a).iter().eq(bset.range(..a)));
+        }
+        // unlike `BTreeSet`, a decreasing range is empty instead of panicking
+        let (start, end) = (50, 10);
+        assert!(set.range(start..end).is_empty());
+    }
+
+    #[test]
+    fn split_off_at_element() {
+        let mut set = OrdSet::new();
+        set.append(vec![1, 2, 3, 4, 5].into_iter());
+        let tail = set.split_off(&3);
+        assert_eq!(set.as_slice(), &[1, 2]);
+        assert_eq!(tail.as_slice(), &[3, 4, 5]);
+        let mut tail = tail;
+        let rest = tail.split_off(&10);
+        assert!(rest.is_empty());
+        assert_eq!(tail.len(), 3);
+    }
+
+    #[test]
+    fn bulk_construction() {
+        let set: OrdSet<i32> = vec![5, 1, 3, 1, 5].into_iter().collect();
+        assert_eq!(set.as_slice(), &[1, 3, 5]);
+        let set = OrdSet::from_vec(vec![3, 3, 2]);
+        assert_eq!(set.as_slice(), &[2, 3]);
+        let set = OrdSet::from_sorted_unchecked(vec![1, 2, 4]);
+        assert_eq!(set.as_slice(), &[1, 2, 4]);
+    }
+
+    #[test]
+    fn extend_sorted_merges_runs() {
+        let mut seed = 9;
+        for i in 0..20 {
+            let (mut set, mut bset) = random_sets(&mut seed, i * 5, 200);
+            let (run, brun) = random_sets(&mut seed, 30, 300);
+            set.extend_sorted(run.clone());
+            bset.extend(brun.iter().cloned());
+            assert!(set.iter().eq(bset.iter()));
+
+            let (other, bother) = random_sets(&mut seed, 30, 300);
+            set.extend(other.iter().rev().cloned());
+            bset.extend(bother);
+            assert!(set.iter().eq(bset.iter()));
+        }
+    }
+
+    #[test]
+    fn extend_sorted_deduplicates_input() {
+        let mut set = OrdSet::from_vec(vec![2, 4]);
+        set.extend_sorted(vec![1, 1, 2, 3, 3, 5]);
+        assert_eq!(set.as_slice(), &[1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    #[should_panic(expected = "not sorted")]
+    fn extend_sorted_rejects_unsorted_input() {
+        let mut set = OrdSet::new();
+        set.extend_sorted(vec![3, 1]);
+    }
+
+    #[test]
+    fn extend_sorted_keeps_contents_on_panic() {
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+
+        let mut set = OrdSet::from_vec(vec![2, 4, 6]);
+        let r = catch_unwind(AssertUnwindSafe(|| set.extend_sorted(vec![1, 5, 3])));
+        assert!(r.is_err());
+        assert_eq!(set.as_slice(), &[2, 4, 6]);
+
+        let r = catch_unwind(AssertUnwindSafe(|| {
+            set.extend_sorted((1..10).map(|e| if e == 5 { panic!("iterator") } else { e }))
+        }));
+        assert!(r.is_err());
+        assert_eq!(set.as_slice(), &[2, 4, 6]);
+
+        let mut set = OrdSetBy::with_comparator(|a: &u32, b: &u32| {
+            if *a == 5 || *b == 5 {
+                panic!("comparator");
+            }
+            a.cmp(b)
+        });
+        set.extend_sorted(vec![2, 4, 6]);
+        let r = catch_unwind(AssertUnwindSafe(|| set.extend_sorted(vec![1, 3, 5, 7])));
+        assert!(r.is_err());
+        assert_eq!(set.as_slice(), &[2, 4, 6]);
+    }
+
+    #[test]
+    fn last_equal_element_is_kept() {
+        /// Element ordered by key only, with a tag telling equal elements apart.
+        #[derive(Debug, Clone, Copy)]
+        struct Tagged(u32, char);
+
+        impl PartialEq for Tagged {
+            fn eq(&self, other: &Tagged) -> bool {
+                self.0 == other.0
+            }
+        }
+
+        impl Eq for Tagged {}
+
+        impl PartialOrd for Tagged {
+            fn partial_cmp(&self, other: &Tagged) -> Option<Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+
+        impl Ord for Tagged {
+            fn cmp(&self, other: &Tagged) -> Ordering {
+                self.0.cmp(&other.0)
+            }
+        }
+
+        fn tags(set: &OrdSet<Tagged>) -> String {
+            set.iter().map(|e| e.1).collect()
+        }
+
+        let input = vec![Tagged(2, 'a'), Tagged(1, 'a'), Tagged(2, 'b'), Tagged(1, 'b'), Tagged(2, 'c')];
+        assert_eq!(tags(&OrdSet::from_vec(input.clone())), "bc");
+        assert_eq!(tags(&input.iter().cloned().collect()), "bc");
+
+        let mut set = OrdSet::from_vec(vec![Tagged(1, 'x'), Tagged(2, 'x')]);
+        set.extend(input.iter().cloned());
+        assert_eq!(tags(&set), "bc");
+
+        let mut set = OrdSet::from_vec(vec![Tagged(1, 'x'), Tagged(2, 'x'), Tagged(3, 'x')]);
+        set.extend_sorted(vec![Tagged(1, 'a'), Tagged(1, 'b'), Tagged(3, 'a')]);
+        assert_eq!(tags(&set), "bxa");
+
+        set.insert(Tagged(2, 'y'));
+        assert_eq!(tags(&set), "bya");
+    }
+
+    #[cfg(feature = "serde_impl")]
+    #[test]
+    fn deserialize_deduplicates() {
+        let set: OrdSet<u32> = serde_json::from_str("[3, 1, 3, 2, 1]").unwrap();
+        assert_eq!(set.as_slice(), &[1, 2, 3]);
+        assert!(set.contains(&2));
+        assert_eq!(serde_json::to_string(&set).unwrap(), "[1,2,3]");
+
+        let mut de = serde_json::Deserializer::from_str("[2, 2, 1]");
+        let set: OrdSet<u32> = super::serde::dedup::deserialize(&mut de).unwrap();
+        assert_eq!(set.as_slice(), &[1, 2]);
+    }
+
+    #[cfg(feature = "serde_impl")]
+    #[test]
+    fn deserialize_strict_rejects_duplicates() {
+        let mut de = serde_json::Deserializer::from_str("[3, 1, 2]");
+        let set: OrdSet<u32> = super::serde::strict::deserialize(&mut de).unwrap();
+        assert_eq!(set.as_slice(), &[1, 2, 3]);
+
+        let mut de = serde_json::Deserializer::from_str("[1, 2, 1]");
+        let err = super::serde::strict::deserialize::<u32, _>(&mut de).unwrap_err();
+        assert!(err.to_string().contains("duplicate"));
+    }
+
+    #[cfg(feature = "serde_impl")]
+    #[test]
+    fn deserialize_strict_uses_ord_for_duplicates() {
+        /// Equality sees all digits, ordering only the tens.
+        #[derive(Debug, PartialEq, Eq)]
+        struct Tens(u32);
+
+        impl PartialOrd for Tens {
+            fn partial_cmp(&self, other: &Tens) -> Option<Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+
+        impl Ord for Tens {
+            fn cmp(&self, other: &Tens) -> Ordering {
+                (self.0 / 10).cmp(&(other.0 / 10))
+            }
+        }
+
+        impl<'de> ::serde::Deserialize<'de> for Tens {
+            fn deserialize<D: ::serde::Deserializer<'de>>(deserializer: D) -> Result<Tens, D::Error> {
+                u32::deserialize(deserializer).map(Tens)
+            }
+        }
+
+        let mut de = serde_json::Deserializer::from_str("[11, 12]");
+        let err = super::serde::strict::deserialize::<Tens, _>(&mut de).unwrap_err();
+        assert!(err.to_string().contains("duplicate"));
+
+        let mut de = serde_json::Deserializer::from_str("[11, 12]");
+        let err = super::serde::sorted::deserialize::<Tens, _>(&mut de).unwrap_err();
+        assert!(err.to_string().contains("duplicate"));
+    }
+
+    #[cfg(feature = "serde_impl")]
+    #[test]
+    fn deserialize_sorted_validates_order() {
+        let mut de = serde_json::Deserializer::from_str("[1, 2, 5]");
+        let set: OrdSet<u32> = super::serde::sorted::deserialize(&mut de).unwrap();
+        assert_eq!(set.as_slice(), &[1, 2, 5]);
+
+        let mut de = serde_json::Deserializer::from_str("[1, 5, 2]");
+        let err = super::serde::sorted::deserialize::<u32, _>(&mut de).unwrap_err();
+        assert!(err.to_string().contains("not sorted"));
+
+        let mut de = serde_json::Deserializer::from_str("[1, 1]");
+        let err = super::serde::sorted::deserialize::<u32, _>(&mut de).unwrap_err();
+        assert!(err.to_string().contains("duplicate"));
+    }
+
+    fn bench_input() -> Vec<u32> {
+        let mut seed = 1u32;
+        (0..5000).map(|_| {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            seed >> 8
+        }).collect()
+    }
+
+    #[bench]
+    fn ord_set_insert_bench(b: &mut Bencher) {
+        let input = bench_input();
+        b.iter(|| {
+            let mut set = OrdSet::new();
+            for &e in input.iter() {
+                set.insert(e);
+            }
+            set
+        });
+    }
+
+    #[bench]
+    fn ord_set_from_iter_bench(b: &mut Bencher) {
+        let input = bench_input();
+        b.iter(|| input.iter().cloned().collect::<OrdSet<_>>());
+    }
+
+    #[bench]
+    fn ord_set_extend_sorted_bench(b: &mut Bencher) {
+        let input = bench_input();
+        let (first, second) = input.split_at(input.len() / 2);
+        let first = OrdSet::from_vec(first.to_vec());
+        let second = OrdSet::from_vec(second.to_vec());
+        b.iter(|| {
+            let mut set = first.clone();
+            set.extend_sorted(second.iter().cloned());
+            set
+        });
+    }
+
+    #[bench]
+    fn btree_set_insert_bench(b: &mut Bencher) {
+        let input = bench_input();
+        b.iter(|| input.iter().cloned().collect::<BTreeSet<_>>());
+    }
 }
\ No newline at end of file
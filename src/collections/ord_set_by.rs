@@ -0,0 +1,861 @@
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::iter::FromIterator;
+use std::marker::PhantomData;
+use std::ops::{BitAnd, BitOr, BitXor, Bound, Deref, RangeBounds, Sub};
+use std::slice;
+
+use super::comparator::{ByKey, Comparator};
+use super::ord_set::{Difference, Intersection, SymmetricDifference, Union};
+
+/// Ordered set with elements ordered by a comparator `C`, stored in a sorted array `S`.
+/// [`OrdSet`](super::OrdSet) is an `OrdSetBy` ordering elements by their natural order, and
+/// [`SmallOrdSet`](super::SmallOrdSet) is one storing a few elements inline.
+///
+/// Elements are unique with respect to the comparator. Of elements comparing equal, the set keeps
+/// the one added last: inserting an element equal to an existing one replaces it, and
+/// constructors, [extend_sorted()][Self::extend_sorted()] and the `FromIterator` and `Extend`
+/// impls keep the last of equal input elements. Lookups accept any borrowed form `Q` of the element
+/// type, provided the comparator implements `Comparator<Q>` consistently with `Comparator<T>`.
+#[derive(Clone)]
+pub struct OrdSetBy<T, C: Comparator<T>, S: SetStorage<T> = Vec<T>> {
+    elems: S,
+    cmp: C,
+    marker: PhantomData<T>,
+}
+
+/// Contiguous storage of elements of an [`OrdSetBy`].
+///
+/// Implemented for `Vec<T>`, and for [`InlineVec`](super::small_ord_set::InlineVec) used by
+/// [`SmallOrdSet`](super::SmallOrdSet).
+pub trait SetStorage<T>: FromIterator<T> + IntoIterator<Item = T> {
+    #[doc(hidden)]
+    fn with_capacity(capacity: usize) -> Self;
+
+    #[doc(hidden)]
+    fn capacity(&self) -> usize;
+
+    #[doc(hidden)]
+    fn as_slice(&self) -> &[T];
+
+    #[doc(hidden)]
+    fn as_mut_slice(&mut self) -> &mut [T];
+
+    #[doc(hidden)]
+    fn insert(&mut self, index: usize, elem: T);
+
+    #[doc(hidden)]
+    fn remove(&mut self, index: usize) -> T;
+
+    #[doc(hidden)]
+    fn truncate(&mut self, len: usize);
+
+    #[doc(hidden)]
+    fn split_off(&mut self, at: usize) -> Self;
+
+    #[doc(hidden)]
+    fn retain<F: FnMut(&T) -> bool>(&mut self, f: F);
+
+    #[doc(hidden)]
+    fn shrink_to_fit(&mut self);
+
+    #[doc(hidden)]
+    #[inline]
+    fn push(&mut self, elem: T) {
+        let len = self.as_slice().len();
+        self.insert(len, elem);
+    }
+}
+
+impl<T> SetStorage<T> for Vec<T> {
+    #[inline]
+    fn with_capacity(capacity: usize) -> Self {
+        Vec::with_capacity(capacity)
+    }
+
+    #[inline]
+    fn capacity(&self) -> usize {
+        Vec::capacity(self)
+    }
+
+    #[inline]
+    fn as_slice(&self) -> &[T] {
+        self
+    }
+
+    #[inline]
+    fn as_mut_slice(&mut self) -> &mut [T] {
+        self
+    }
+
+    #[inline]
+    fn insert(&mut self, index: usize, elem: T) {
+        Vec::insert(self, index, elem)
+    }
+
+    #[inline]
+    fn remove(&mut self, index: usize) -> T {
+        Vec::remove(self, index)
+    }
+
+    #[inline]
+    fn truncate(&mut self, len: usize) {
+        Vec::truncate(self, len)
+    }
+
+    #[inline]
+    fn split_off(&mut self, at: usize) -> Self {
+        Vec::split_off(self, at)
+    }
+
+    #[inline]
+    fn retain<F: FnMut(&T) -> bool>(&mut self, f: F) {
+        Vec::retain(self, f)
+    }
+
+    #[inline]
+    fn shrink_to_fit(&mut self) {
+        Vec::shrink_to_fit(self)
+    }
+
+    #[inline]
+    fn push(&mut self, elem: T) {
+        Vec::push(self, elem)
+    }
+}
+
+impl<T, C: Comparator<T>> OrdSetBy<T, C> {
+    pub fn with_comparator(cmp: C) -> OrdSetBy<T, C> {
+        OrdSetBy {
+            elems: Vec::new(),
+            cmp,
+            marker: PhantomData,
+        }
+    }
+
+    #[inline]
+    pub fn with_capacity_and_comparator(capacity: usize, cmp: C) -> OrdSetBy<T, C> {
+        OrdSetBy {
+            elems: Vec::with_capacity(capacity),
+            cmp,
+            marker: PhantomData,
+        }
+    }
+
+    /// Creates a set from a vector of elements, which is sorted and deduplicated in the process,
+    /// keeping the last of equal elements.
+    pub fn from_vec_with_comparator(elems: Vec<T>, cmp: C) -> OrdSetBy<T, C> {
+        OrdSetBy::from_unsorted(elems, cmp)
+    }
+}
+
+impl<T, C: Comparator<T>, S: SetStorage<T>> OrdSetBy<T, C, S> {
+    /// Creates a set from elements already sorted and deduplicated with respect to `cmp`.
+    #[inline]
+    pub(super) fn from_sorted(elems: S, cmp: C) -> OrdSetBy<T, C, S> {
+        OrdSetBy { elems, cmp, marker: PhantomData }
+    }
+
+    /// Creates a set from elements in any order, sorting and deduplicating them in place.
+    pub(super) fn from_unsorted(mut elems: S, cmp: C) -> OrdSetBy<T, C, S> {
+        let slice = elems.as_mut_slice();
+        slice.sort_by(|a, b| cmp.compare(a, b));
+        let len = dedup_sorted(slice, &cmp);
+        elems.truncate(len);
+        OrdSetBy { elems, cmp, marker: PhantomData }
+    }
+
+    #[inline]
+    pub(super) fn storage(&self) -> &S {
+        &self.elems
+    }
+
+    #[inline]
+    pub fn comparator(&self) -> &C {
+        &self.cmp
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.elems.as_slice().len()
+    }
+
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.elems.capacity()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Shrinks capacity of the underlying storage as much as possible.
+    #[inline]
+    pub fn shrink_to_fit(&mut self) {
+        self.elems.shrink_to_fit()
+    }
+
+    #[inline]
+    pub fn contains<Q: ?Sized>(&self, value: &Q) -> bool
+        where T: Borrow<Q>, C: Comparator<Q>
+    {
+        self.search(value).is_ok()
+    }
+
+    /// Returns a reference to the element equal to `value`, if any.
+    #[inline]
+    pub fn get<Q: ?Sized>(&self, value: &Q) -> Option<&T>
+        where T: Borrow<Q>, C: Comparator<Q>
+    {
+        match self.search(value) {
+            Ok(index) => Some(&self.elems.as_slice()[index]),
+            Err(_) => None,
+        }
+    }
+
+    /// Returns index of the element equal to `value` in the underlying slice, if any.
+    #[inline]
+    pub fn position<Q: ?Sized>(&self, value: &Q) -> Option<usize>
+        where T: Borrow<Q>, C: Comparator<Q>
+    {
+        self.search(value).ok()
+    }
+
+    #[inline]
+    pub fn insert(&mut self, elem: T) -> Option<T> {
+        match self.elems.as_slice().binary_search_by(|e| self.cmp.compare(e, &elem)) {
+            Ok(index) => {
+                Some(std::mem::replace(&mut self.elems.as_mut_slice()[index], elem))
+            }
+            Err(index) => {
+                self.elems.insert(index, elem);
+                None
+            }
+        }
+    }
+
+    /// Adds an element to the set, replacing the existing element equal to it, if any.
+    /// Returns the replaced element.
+    #[inline]
+    pub fn replace(&mut self, elem: T) -> Option<T> {
+        self.insert(elem)
+    }
+
+    #[inline]
+    pub fn remove<Q: ?Sized>(&mut self, value: &Q) -> bool
+        where T: Borrow<Q>, C: Comparator<Q>
+    {
+        self.take(value).is_some()
+    }
+
+    /// Removes and returns the element equal to `value`, if any.
+    #[inline]
+    pub fn take<Q: ?Sized>(&mut self, value: &Q) -> Option<T>
+        where T: Borrow<Q>, C: Comparator<Q>
+    {
+        match self.search(value) {
+            Ok(index) => Some(self.elems.remove(index)),
+            Err(_) => None,
+        }
+    }
+
+    #[inline]
+    pub fn clear(&mut self) {
+        self.elems.truncate(0);
+    }
+
+    #[inline]
+    pub fn as_slice(&self)-> &[T] {
+        self.elems.as_slice()
+    }
+
+    #[inline]
+    pub fn retain<F>(&mut self, f: F) where F: FnMut(&T) -> bool {
+        self.elems.retain(f)
+    }
+
+    /// Merges elements from an iterator yielding elements in ascending order into the set,
+    /// in a single O(n + m) pass. Elements equal to the existing ones replace them, like in
+    /// [insert()][Self::insert()], and of equal elements yielded by the iterator the last one
+    /// is kept.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the iterator does not yield elements in ascending order. The set is left
+    /// unchanged if this method panics, or if the iterator or the comparator panics.
+    pub fn extend_sorted<I>(&mut self, iter: I)
+        where I: IntoIterator<Item = T>
+    {
+        // all comparisons are done before the set is modified, so that a panic leaves it intact
+        let elems: Vec<T> = iter.into_iter().collect();
+        let old = self.elems.as_slice();
+        // for each new element: position of the first old element not less than it, whether that
+        // element is equal to it, and whether the new element is superseded by the next one
+        let mut plan: Vec<(usize, bool, bool)> = Vec::with_capacity(elems.len());
+        let mut pos = 0;
+        for (i, elem) in elems.iter().enumerate() {
+            if i > 0 {
+                match self.cmp.compare(&elems[i - 1], elem) {
+                    Ordering::Less => {}
+                    Ordering::Equal => plan[i - 1].2 = true,
+                    Ordering::Greater => panic!("elements are not sorted"),
+                }
+            }
+            while pos < old.len() && self.cmp.compare(&old[pos], elem) == Ordering::Less {
+                pos += 1;
+            }
+            let equal = pos < old.len() && self.cmp.compare(&old[pos], elem) == Ordering::Equal;
+            plan.push((pos, equal, false));
+        }
+
+        let mut merged = S::with_capacity(old.len() + elems.len());
+        let mut old = std::mem::replace(&mut self.elems, S::with_capacity(0)).into_iter();
+        let mut taken = 0;
+        for (elem, (pos, equal, superseded)) in elems.into_iter().zip(plan) {
+            if superseded {
+                continue;
+            }
+            for o in old.by_ref().take(pos - taken) {
+                merged.push(o);
+            }
+            taken = pos;
+            if equal {
+                old.next();
+                taken += 1;
+            }
+            merged.push(elem);
+        }
+        for o in old {
+            merged.push(o);
+        }
+        self.elems = merged;
+    }
+
+    pub fn append<I>(&mut self, iter: I) -> bool
+        where I: Iterator<Item = T>
+    {
+        let mut changed = false;
+        for i in iter {
+            changed |= self.insert(i).is_none();
+        }
+        changed
+    }
+
+    /// Returns the smallest element of the set.
+    #[inline]
+    pub fn first(&self) -> Option<&T> {
+        self.elems.as_slice().first()
+    }
+
+    /// Returns the greatest element of the set.
+    #[inline]
+    pub fn last(&self) -> Option<&T> {
+        self.elems.as_slice().last()
+    }
+
+    /// Removes and returns the smallest element of the set. This operation is O(n).
+    #[inline]
+    pub fn pop_first(&mut self) -> Option<T> {
+        if self.is_empty() {
+            None
+        } else {
+            Some(self.elems.remove(0))
+        }
+    }
+
+    /// Removes and returns the greatest element of the set.
+    #[inline]
+    pub fn pop_last(&mut self) -> Option<T> {
+        match self.len() {
+            0 => None,
+            len => Some(self.elems.remove(len - 1)),
+        }
+    }
+
+    /// Returns index of the first element not less than `value`, or length of the set if there is
+    /// no such element.
+    #[inline]
+    pub fn lower_bound<Q: ?Sized>(&self, value: &Q) -> usize
+        where T: Borrow<Q>, C: Comparator<Q>
+    {
+        self.elems.as_slice().partition_point(|e| self.cmp.compare(e.borrow(), value) == Ordering::Less)
+    }
+
+    /// Returns index of the first element greater than `value`, or length of the set if there is
+    /// no such element.
+    #[inline]
+    pub fn upper_bound<Q: ?Sized>(&self, value: &Q) -> usize
+        where T: Borrow<Q>, C: Comparator<Q>
+    {
+        self.elems.as_slice().partition_point(|e| self.cmp.compare(e.borrow(), value) != Ordering::Greater)
+    }
+
+    /// Returns the greatest element less than or equal to `value`.
+    #[inline]
+    pub fn floor<Q: ?Sized>(&self, value: &Q) -> Option<&T>
+        where T: Borrow<Q>, C: Comparator<Q>
+    {
+        match self.upper_bound(value) {
+            0 => None,
+            index => Some(&self.elems.as_slice()[index - 1]),
+        }
+    }
+
+    /// Returns the smallest element greater than or equal to `value`.
+    #[inline]
+    pub fn ceiling<Q: ?Sized>(&self, value: &Q) -> Option<&T>
+        where T: Borrow<Q>, C: Comparator<Q>
+    {
+        self.elems.as_slice().get(self.lower_bound(value))
+    }
+
+    /// Returns a slice of elements contained in the given range. The slice is empty if range
+    /// start is greater than range end.
+    ///
+    /// ```
+    /// use kg_utils::collections::OrdSet;
+    ///
+    /// let mut set = OrdSet::new();
+    /// set.append(vec![1, 3, 5, 7, 9].into_iter());
+    /// assert_eq!(set.range(3..9), &[3, 5, 7]);
+    /// assert_eq!(set.range(4..=9), &[5, 7, 9]);
+    /// assert_eq!(set.range(..4), &[1, 3]);
+    /// ```
+    pub fn range<Q: ?Sized, R>(&self, range: R) -> &[T]
+        where T: Borrow<Q>, C: Comparator<Q>, R: RangeBounds<Q>
+    {
+        let start = match range.start_bound() {
+            Bound::Included(e) => self.lower_bound(e),
+            Bound::Excluded(e) => self.upper_bound(e),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(e) => self.upper_bound(e),
+            Bound::Excluded(e) => self.lower_bound(e),
+            Bound::Unbounded => self.len(),
+        };
+        &self.elems.as_slice()[start..std::cmp::max(start, end)]
+    }
+
+    /// Splits the set in two at `value`. Returns a new set with all elements greater than or
+    /// equal to `value`, leaving smaller elements in this set.
+    #[inline]
+    pub fn split_off<Q: ?Sized>(&mut self, value: &Q) -> OrdSetBy<T, C, S>
+        where T: Borrow<Q>, C: Comparator<Q> + Clone
+    {
+        let index = self.lower_bound(value);
+        OrdSetBy {
+            elems: self.elems.split_off(index),
+            cmp: self.cmp.clone(),
+            marker: PhantomData,
+        }
+    }
+
+    /// Returns a lazy iterator visiting elements present in either set, in ascending order.
+    ///
+    /// Both sets are assumed to be ordered the same way, elements are compared with
+    /// this set's comparator.
+    pub fn union<'a>(&'a self, other: &'a OrdSetBy<T, C, S>) -> Union<'a, T, C> {
+        Union {
+            a: self.elems.as_slice().iter().peekable(),
+            b: other.elems.as_slice().iter().peekable(),
+            cmp: &self.cmp,
+        }
+    }
+
+    /// Returns a lazy iterator visiting elements present in both sets, in ascending order.
+    pub fn intersection<'a>(&'a self, other: &'a OrdSetBy<T, C, S>) -> Intersection<'a, T, C> {
+        Intersection {
+            a: self.elems.as_slice().iter().peekable(),
+            b: other.elems.as_slice().iter().peekable(),
+            cmp: &self.cmp,
+        }
+    }
+
+    /// Returns a lazy iterator visiting elements present in this set but not in `other`,
+    /// in ascending order.
+    pub fn difference<'a>(&'a self, other: &'a OrdSetBy<T, C, S>) -> Difference<'a, T, C> {
+        Difference {
+            a: self.elems.as_slice().iter().peekable(),
+            b: other.elems.as_slice().iter().peekable(),
+            cmp: &self.cmp,
+        }
+    }
+
+    /// Returns a lazy iterator visiting elements present in exactly one of the sets,
+    /// in ascending order.
+    pub fn symmetric_difference<'a>(&'a self, other: &'a OrdSetBy<T, C, S>) -> SymmetricDifference<'a, T, C> {
+        SymmetricDifference {
+            a: self.elems.as_slice().iter().peekable(),
+            b: other.elems.as_slice().iter().peekable(),
+            cmp: &self.cmp,
+        }
+    }
+
+    /// Returns `true` if all elements of this set are present in `other`.
+    pub fn is_subset(&self, other: &OrdSetBy<T, C, S>) -> bool {
+        if self.len() > other.len() {
+            return false;
+        }
+        let mut b = other.elems.as_slice().iter();
+        'outer: for e in self.elems.as_slice().iter() {
+            for o in b.by_ref() {
+                match self.cmp.compare(o, e) {
+                    Ordering::Less => continue,
+                    Ordering::Equal => continue 'outer,
+                    Ordering::Greater => return false,
+                }
+            }
+            return false;
+        }
+        true
+    }
+
+    /// Returns `true` if all elements of `other` are present in this set.
+    #[inline]
+    pub fn is_superset(&self, other: &OrdSetBy<T, C, S>) -> bool {
+        other.is_subset(self)
+    }
+
+    /// Returns `true` if sets have no elements in common.
+    #[inline]
+    pub fn is_disjoint(&self, other: &OrdSetBy<T, C, S>) -> bool {
+        self.intersection(other).next().is_none()
+    }
+
+    #[inline]
+    fn search<Q: ?Sized>(&self, value: &Q) -> Result<usize, usize>
+        where T: Borrow<Q>, C: Comparator<Q>
+    {
+        self.elems.as_slice().binary_search_by(|e| self.cmp.compare(e.borrow(), value))
+    }
+}
+
+/// Moves the last of each run of equal elements of a sorted slice to the front, and returns
+/// the number of unique elements. Remaining elements are left at the back in unspecified order.
+fn dedup_sorted<T, C: Comparator<T>>(elems: &mut [T], cmp: &C) -> usize {
+    if elems.is_empty() {
+        return 0;
+    }
+    let mut w = 0;
+    for r in 1..elems.len() {
+        if cmp.compare(&elems[w], &elems[r]) != Ordering::Equal {
+            w += 1;
+        }
+        elems.swap(w, r);
+    }
+    w + 1
+}
+
+impl<T, K, F, S> OrdSetBy<T, ByKey<F, K>, S>
+    where F: Fn(&T) -> K, K: Ord, S: SetStorage<T>
+{
+    /// Returns `true` if the set contains an element with the given key.
+    #[inline]
+    pub fn contains_by_key(&self, key: &K) -> bool {
+        self.search_by_key(key).is_ok()
+    }
+
+    /// Returns a reference to the element with the given key, if any.
+    #[inline]
+    pub fn get_by_key(&self, key: &K) -> Option<&T> {
+        match self.search_by_key(key) {
+            Ok(index) => Some(&self.elems.as_slice()[index]),
+            Err(_) => None,
+        }
+    }
+
+    /// Removes the element with the given key, returning it if it was present.
+    #[inline]
+    pub fn remove_by_key(&mut self, key: &K) -> Option<T> {
+        match self.search_by_key(key) {
+            Ok(index) => Some(self.elems.remove(index)),
+            Err(_) => None,
+        }
+    }
+
+    #[inline]
+    fn search_by_key(&self, key: &K) -> Result<usize, usize> {
+        self.elems.as_slice().binary_search_by(|e| self.cmp.key(e).cmp(key))
+    }
+}
+
+impl<'b, T, C, S> BitOr<&'b OrdSetBy<T, C, S>> for &OrdSetBy<T, C, S>
+    where T: Clone, C: Comparator<T> + Clone, S: SetStorage<T>
+{
+    type Output = OrdSetBy<T, C, S>;
+
+    /// Returns union of `self` and `rhs` as a new set.
+    fn bitor(self, rhs: &'b OrdSetBy<T, C, S>) -> OrdSetBy<T, C, S> {
+        OrdSetBy::from_sorted(self.union(rhs).cloned().collect(), self.cmp.clone())
+    }
+}
+
+impl<'b, T, C, S> BitAnd<&'b OrdSetBy<T, C, S>> for &OrdSetBy<T, C, S>
+    where T: Clone, C: Comparator<T> + Clone, S: SetStorage<T>
+{
+    type Output = OrdSetBy<T, C, S>;
+
+    /// Returns intersection of `self` and `rhs` as a new set.
+    fn bitand(self, rhs: &'b OrdSetBy<T, C, S>) -> OrdSetBy<T, C, S> {
+        OrdSetBy::from_sorted(self.intersection(rhs).cloned().collect(), self.cmp.clone())
+    }
+}
+
+impl<'b, T, C, S> Sub<&'b OrdSetBy<T, C, S>> for &OrdSetBy<T, C, S>
+    where T: Clone, C: Comparator<T> + Clone, S: SetStorage<T>
+{
+    type Output = OrdSetBy<T, C, S>;
+
+    /// Returns difference of `self` and `rhs` as a new set.
+    fn sub(self, rhs: &'b OrdSetBy<T, C, S>) -> OrdSetBy<T, C, S> {
+        OrdSetBy::from_sorted(self.difference(rhs).cloned().collect(), self.cmp.clone())
+    }
+}
+
+impl<'b, T, C, S> BitXor<&'b OrdSetBy<T, C, S>> for &OrdSetBy<T, C, S>
+    where T: Clone, C: Comparator<T> + Clone, S: SetStorage<T>
+{
+    type Output = OrdSetBy<T, C, S>;
+
+    /// Returns symmetric difference of `self` and `rhs` as a new set.
+    fn bitxor(self, rhs: &'b OrdSetBy<T, C, S>) -> OrdSetBy<T, C, S> {
+        OrdSetBy::from_sorted(self.symmetric_difference(rhs).cloned().collect(), self.cmp.clone())
+    }
+}
+
+impl<T, C: Comparator<T> + Default, S: SetStorage<T>> Default for OrdSetBy<T, C, S> {
+    fn default() -> Self {
+        OrdSetBy::from_sorted(S::with_capacity(0), C::default())
+    }
+}
+
+impl<T, C: Comparator<T> + Default, S: SetStorage<T>> FromIterator<T> for OrdSetBy<T, C, S> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        OrdSetBy::from_unsorted(iter.into_iter().collect(), C::default())
+    }
+}
+
+impl<T, C: Comparator<T>, S: SetStorage<T>> Extend<T> for OrdSetBy<T, C, S> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let mut elems: S = iter.into_iter().collect();
+        elems.as_mut_slice().sort_by(|a, b| self.cmp.compare(a, b));
+        self.extend_sorted(elems);
+    }
+}
+
+impl<T, C: Comparator<T>, S: SetStorage<T>> IntoIterator for OrdSetBy<T, C, S> {
+    type Item = T;
+    type IntoIter = S::IntoIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.elems.into_iter()
+    }
+}
+
+impl<'a, T, C: Comparator<T>, S: SetStorage<T>> IntoIterator for &'a OrdSetBy<T, C, S> {
+    type Item = &'a T;
+    type IntoIter = slice::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.elems.as_slice().iter()
+    }
+}
+
+impl<T, C, S> Deref for OrdSetBy<T, C, S>
+    where C: Comparator<T>, S: SetStorage<T>
+{
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        self.elems.as_slice()
+    }
+}
+
+impl<T: PartialEq, C: Comparator<T>, S: SetStorage<T>> PartialEq for OrdSetBy<T, C, S> {
+    fn eq(&self, other: &Self) -> bool {
+        self.elems.as_slice() == other.elems.as_slice()
+    }
+}
+
+impl<T: Eq, C: Comparator<T>, S: SetStorage<T>> Eq for OrdSetBy<T, C, S> {}
+
+impl<T: PartialOrd, C: Comparator<T>, S: SetStorage<T>> PartialOrd for OrdSetBy<T, C, S> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.elems.as_slice().partial_cmp(other.elems.as_slice())
+    }
+}
+
+impl<T: Ord, C: Comparator<T>, S: SetStorage<T>> Ord for OrdSetBy<T, C, S> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.elems.as_slice().cmp(other.elems.as_slice())
+    }
+}
+
+impl<T, C, S> std::fmt::Debug for OrdSetBy<T, C, S>
+    where T: std::fmt::Debug, C: Comparator<T>, S: SetStorage<T>
+{
+    #[inline]
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_set().entries(self.elems.as_slice().iter()).finish()
+    }
+}
+
+#[cfg(feature = "serde_impl")]
+mod serde {
+    extern crate serde;
+
+    use super::*;
+
+    use self::serde::{de, ser};
+
+    impl<T, C, S> ser::Serialize for OrdSetBy<T, C, S>
+        where T: ser::Serialize, C: Comparator<T>, S: SetStorage<T>
+    {
+        fn serialize<Z>(&self, serializer: Z) -> Result<Z::Ok, Z::Error>
+            where Z: ser::Serializer
+        {
+            serializer.collect_seq(self.elems.as_slice().iter())
+        }
+    }
+
+    struct OrdSetByVisitor<T, C, S> {
+        marker: PhantomData<(T, C, S)>,
+    }
+
+    impl<'de, T, C, S> de::Visitor<'de> for OrdSetByVisitor<T, C, S>
+        where T: de::Deserialize<'de>, C: Comparator<T> + Default, S: SetStorage<T>
+    {
+        type Value = OrdSetBy<T, C, S>;
+
+        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            f.write_str("a sequence")
+        }
+
+        fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+            where A: de::SeqAccess<'de>
+        {
+            // size hint comes from the input, do not trust it with a large allocation
+            let mut elems = S::with_capacity(std::cmp::min(seq.size_hint().unwrap_or(0), 4096));
+            while let Some(e) = seq.next_element()? {
+                elems.push(e);
+            }
+            Ok(OrdSetBy::from_unsorted(elems, C::default()))
+        }
+    }
+
+    impl<'de, T, C, S> de::Deserialize<'de> for OrdSetBy<T, C, S>
+        where T: de::Deserialize<'de>, C: Comparator<T> + Default, S: SetStorage<T>
+    {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+            where D: de::Deserializer<'de>
+        {
+            deserializer.deserialize_seq(OrdSetByVisitor { marker: PhantomData })
+        }
+    }
+}
+
+#[cfg(feature = "heapsize_impl")]
+mod heapsize {
+    extern crate heapsize;
+
+    use super::*;
+
+    use self::heapsize::HeapSizeOf;
+
+    impl<T, C, S> HeapSizeOf for OrdSetBy<T, C, S>
+        where C: Comparator<T>, S: SetStorage<T> + HeapSizeOf
+    {
+        fn heap_size_of_children(&self) -> usize {
+            self.elems.heap_size_of_children()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::super::comparator::Natural;
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct CaseInsensitive;
+
+    impl Comparator<str> for CaseInsensitive {
+        fn compare(&self, a: &str, b: &str) -> Ordering {
+            a.bytes().map(|c| c.to_ascii_lowercase()).cmp(b.bytes().map(|c| c.to_ascii_lowercase()))
+        }
+    }
+
+    impl Comparator<String> for CaseInsensitive {
+        fn compare(&self, a: &String, b: &String) -> Ordering {
+            Comparator::<str>::compare(self, a, b)
+        }
+    }
+
+    #[test]
+    fn custom_comparator_with_borrowed_lookup() {
+        let mut set: OrdSetBy<String, _> = OrdSetBy::with_comparator(CaseInsensitive);
+        for s in &["banana", "Apple", "cherry", "apple"] {
+            set.insert(s.to_string());
+        }
+        assert_eq!(set.as_slice(), &["apple", "banana", "cherry"]);
+        assert!(set.contains("BANANA"));
+        assert_eq!(set.get("Cherry").map(|s| s.as_str()), Some("cherry"));
+        assert_eq!(set.range::<str, _>((Bound::Included("B"), Bound::Excluded("C"))), &["banana"]);
+        assert!(set.remove("APPLE"));
+        assert_eq!(set.len(), 2);
+    }
+
+    #[test]
+    fn closure_comparator() {
+        let mut set = OrdSetBy::with_comparator(|a: &i32, b: &i32| b.cmp(a));
+        set.append(vec![3, 1, 2, 3].into_iter());
+        assert_eq!(set.as_slice(), &[3, 2, 1]);
+        assert_eq!(set.floor(&0), Some(&1));
+        assert_eq!(set.ceiling(&4), Some(&3));
+    }
+
+    #[test]
+    fn key_comparator() {
+        let mut set = OrdSetBy::with_comparator(ByKey::new(|p: &(u32, &str)| p.0));
+        set.insert((3, "c"));
+        set.insert((1, "a"));
+        assert_eq!(set.insert((3, "x")), Some((3, "c")));
+        assert!(set.contains_by_key(&1));
+        assert_eq!(set.get_by_key(&3), Some(&(3, "x")));
+        assert_eq!(set.remove_by_key(&1), Some((1, "a")));
+        assert!(!set.contains_by_key(&1));
+    }
+
+    #[test]
+    fn natural_order_matches_btree_set() {
+        let mut seed = 7u32;
+        for i in 0..20 {
+            let mut a = OrdSetBy::with_comparator(Natural);
+            let mut b = OrdSetBy::with_comparator(Natural);
+            let mut ba = BTreeSet::new();
+            let mut bb = BTreeSet::new();
+            for _ in 0..i * 3 {
+                seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+                let (x, y) = ((seed >> 8) % 60, (seed >> 16) % 80);
+                a.insert(x);
+                ba.insert(x);
+                b.insert(y);
+                bb.insert(y);
+            }
+            assert!(a.union(&b).eq(ba.union(&bb)));
+            assert!(a.intersection(&b).eq(ba.intersection(&bb)));
+            assert!(a.difference(&b).eq(ba.difference(&bb)));
+            assert!(a.symmetric_difference(&b).eq(ba.symmetric_difference(&bb)));
+            assert_eq!(a.is_subset(&b), ba.is_subset(&bb));
+            assert!((&a | &b).iter().eq(ba.union(&bb)));
+
+            let mut c = a.clone();
+            c.extend(b.iter().rev().cloned());
+            assert!(c.iter().eq(ba.union(&bb)));
+        }
+    }
+}
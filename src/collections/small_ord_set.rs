@@ -0,0 +1,612 @@
+use std::iter::FromIterator;
+use std::mem::{ManuallyDrop, MaybeUninit};
+use std::{ptr, slice, vec};
+
+use super::comparator::Natural;
+use super::ord_set_by::{OrdSetBy, SetStorage};
+
+/// Ordered set like [`OrdSet`](super::OrdSet), which stores up to `N` elements inline, without
+/// allocating. When more elements are inserted, storage is moved to the heap.
+///
+/// Once spilled, the set stays on the heap until [shrink_to_fit()][OrdSetBy::shrink_to_fit()]
+/// is called.
+///
+/// This is an [`OrdSetBy`] with [`InlineVec`] storage, which provides the rest of the API.
+pub type SmallOrdSet<T, const N: usize> = OrdSetBy<T, Natural, InlineVec<T, N>>;
+
+impl<T: Ord, const N: usize> SmallOrdSet<T, N> {
+    pub fn new() -> SmallOrdSet<T, N> {
+        OrdSetBy::from_sorted(InlineVec(Data::new()), Natural)
+    }
+
+    /// Creates an empty set able to hold `capacity` elements without reallocating. Storage is
+    /// allocated on the heap only if `capacity` is greater than `N`.
+    #[inline]
+    pub fn with_capacity(capacity: usize) -> SmallOrdSet<T, N> {
+        OrdSetBy::from_sorted(InlineVec(Data::with_capacity(capacity)), Natural)
+    }
+
+    /// Creates a set from a vector of elements, which is sorted and deduplicated in the process,
+    /// keeping the last of equal elements. Elements are moved inline if they fit.
+    pub fn from_vec(elems: Vec<T>) -> SmallOrdSet<T, N> {
+        OrdSetBy::from_unsorted(InlineVec(Data::from_vec(elems)), Natural)
+    }
+
+    /// Returns `true` if elements are stored on the heap.
+    #[inline]
+    pub fn spilled(&self) -> bool {
+        match self.storage().0 {
+            Data::Inline { .. } => false,
+            Data::Heap(_) => true,
+        }
+    }
+}
+
+/// Storage of [`SmallOrdSet`], holding up to `N` elements inline.
+pub struct InlineVec<T, const N: usize>(Data<T, N>);
+
+impl<T, const N: usize> SetStorage<T> for InlineVec<T, N> {
+    #[inline]
+    fn with_capacity(capacity: usize) -> Self {
+        InlineVec(Data::with_capacity(capacity))
+    }
+
+    #[inline]
+    fn capacity(&self) -> usize {
+        self.0.capacity()
+    }
+
+    #[inline]
+    fn as_slice(&self) -> &[T] {
+        self.0.as_slice()
+    }
+
+    #[inline]
+    fn as_mut_slice(&mut self) -> &mut [T] {
+        self.0.as_mut_slice()
+    }
+
+    #[inline]
+    fn insert(&mut self, index: usize, elem: T) {
+        self.0.insert(index, elem)
+    }
+
+    #[inline]
+    fn remove(&mut self, index: usize) -> T {
+        self.0.remove(index)
+    }
+
+    #[inline]
+    fn truncate(&mut self, len: usize) {
+        self.0.truncate(len)
+    }
+
+    #[inline]
+    fn split_off(&mut self, at: usize) -> Self {
+        InlineVec(self.0.split_off(at))
+    }
+
+    #[inline]
+    fn retain<F: FnMut(&T) -> bool>(&mut self, f: F) {
+        self.0.retain(f)
+    }
+
+    /// Moves elements back inline if they fit, otherwise shrinks capacity of the heap storage.
+    fn shrink_to_fit(&mut self) {
+        if let Data::Heap(ref mut v) = self.0 {
+            if v.len() > N {
+                v.shrink_to_fit();
+            } else {
+                let data = std::mem::replace(&mut self.0, Data::new());
+                self.0 = Data::from_vec(data.into_vec());
+            }
+        }
+    }
+
+    #[inline]
+    fn push(&mut self, elem: T) {
+        self.0.push(elem)
+    }
+}
+
+impl<T: Clone, const N: usize> Clone for InlineVec<T, N> {
+    fn clone(&self) -> Self {
+        self.0.as_slice().iter().cloned().collect()
+    }
+}
+
+impl<T, const N: usize> FromIterator<T> for InlineVec<T, N> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        InlineVec(iter.into_iter().collect())
+    }
+}
+
+impl<T, const N: usize> IntoIterator for InlineVec<T, N> {
+    type Item = T;
+    type IntoIter = IntoIter<T, N>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+enum Data<T, const N: usize> {
+    Inline {
+        len: usize,
+        buf: [MaybeUninit<T>; N],
+    },
+    Heap(Vec<T>),
+}
+
+impl<T, const N: usize> Data<T, N> {
+    #[inline]
+    fn new() -> Data<T, N> {
+        Data::Inline {
+            len: 0,
+            // an array of `MaybeUninit` does not require initialization
+            buf: unsafe { MaybeUninit::uninit().assume_init() },
+        }
+    }
+
+    #[inline]
+    fn with_capacity(capacity: usize) -> Data<T, N> {
+        if capacity <= N {
+            Data::new()
+        } else {
+            Data::Heap(Vec::with_capacity(capacity))
+        }
+    }
+
+    fn from_vec(vec: Vec<T>) -> Data<T, N> {
+        if vec.len() <= N {
+            let mut data = Data::new();
+            for e in vec {
+                data.push(e);
+            }
+            data
+        } else {
+            Data::Heap(vec)
+        }
+    }
+
+    fn into_vec(self) -> Vec<T> {
+        let mut data = ManuallyDrop::new(self);
+        match *data {
+            Data::Inline { len, ref buf } => {
+                let mut vec = Vec::with_capacity(len);
+                unsafe {
+                    ptr::copy_nonoverlapping(buf.as_ptr() as *const T, vec.as_mut_ptr(), len);
+                    vec.set_len(len);
+                }
+                vec
+            }
+            Data::Heap(ref mut v) => std::mem::take(v),
+        }
+    }
+
+    #[inline]
+    fn len(&self) -> usize {
+        match *self {
+            Data::Inline { len, .. } => len,
+            Data::Heap(ref v) => v.len(),
+        }
+    }
+
+    #[inline]
+    fn capacity(&self) -> usize {
+        match *self {
+            Data::Inline { .. } => N,
+            Data::Heap(ref v) => v.capacity(),
+        }
+    }
+
+    #[inline]
+    fn as_slice(&self) -> &[T] {
+        match *self {
+            Data::Inline { len, ref buf } => unsafe {
+                slice::from_raw_parts(buf.as_ptr() as *const T, len)
+            },
+            Data::Heap(ref v) => v.as_slice(),
+        }
+    }
+
+    #[inline]
+    fn as_mut_slice(&mut self) -> &mut [T] {
+        match *self {
+            Data::Inline { len, ref mut buf } => unsafe {
+                slice::from_raw_parts_mut(buf.as_mut_ptr() as *mut T, len)
+            },
+            Data::Heap(ref mut v) => v.as_mut_slice(),
+        }
+    }
+
+    /// Moves elements to the heap, if they are stored inline.
+    fn spill(&mut self) {
+        let vec = match *self {
+            Data::Inline { ref mut len, ref buf } => {
+                let mut vec = Vec::with_capacity(std::cmp::max(N * 2, 4));
+                unsafe {
+                    ptr::copy_nonoverlapping(buf.as_ptr() as *const T, vec.as_mut_ptr(), *len);
+                    vec.set_len(*len);
+                }
+                *len = 0;
+                vec
+            }
+            Data::Heap(_) => return,
+        };
+        *self = Data::Heap(vec);
+    }
+
+    #[inline]
+    fn push(&mut self, elem: T) {
+        let len = self.len();
+        self.insert(len, elem);
+    }
+
+    fn insert(&mut self, index: usize, elem: T) {
+        if let Data::Inline { len, .. } = *self {
+            if len == N {
+                self.spill();
+            }
+        }
+        match *self {
+            Data::Inline { ref mut len, ref mut buf } => {
+                assert!(index <= *len, "index out of bounds");
+                unsafe {
+                    let p = buf.as_mut_ptr() as *mut T;
+                    ptr::copy(p.add(index), p.add(index + 1), *len - index);
+                    ptr::write(p.add(index), elem);
+                }
+                *len += 1;
+            }
+            Data::Heap(ref mut v) => v.insert(index, elem),
+        }
+    }
+
+    fn remove(&mut self, index: usize) -> T {
+        match *self {
+            Data::Inline { ref mut len, ref mut buf } => {
+                assert!(index < *len, "index out of bounds");
+                unsafe {
+                    let p = buf.as_mut_ptr() as *mut T;
+                    let elem = ptr::read(p.add(index));
+                    ptr::copy(p.add(index + 1), p.add(index), *len - index - 1);
+                    *len -= 1;
+                    elem
+                }
+            }
+            Data::Heap(ref mut v) => v.remove(index),
+        }
+    }
+
+    fn truncate(&mut self, new_len: usize) {
+        match *self {
+            Data::Inline { ref mut len, ref mut buf } => {
+                if new_len < *len {
+                    let tail = *len - new_len;
+                    *len = new_len;
+                    unsafe {
+                        let p = (buf.as_mut_ptr() as *mut T).add(new_len);
+                        ptr::drop_in_place(ptr::slice_from_raw_parts_mut(p, tail));
+                    }
+                }
+            }
+            Data::Heap(ref mut v) => v.truncate(new_len),
+        }
+    }
+
+    fn split_off(&mut self, at: usize) -> Data<T, N> {
+        match *self {
+            Data::Inline { ref mut len, ref mut buf } => {
+                assert!(at <= *len, "index out of bounds");
+                let mut other = Data::new();
+                if let Data::Inline { len: ref mut other_len, buf: ref mut other_buf } = other {
+                    unsafe {
+                        let p = buf.as_mut_ptr() as *mut T;
+                        ptr::copy_nonoverlapping(p.add(at), other_buf.as_mut_ptr() as *mut T, *len - at);
+                    }
+                    *other_len = *len - at;
+                }
+                *len = at;
+                other
+            }
+            Data::Heap(ref mut v) => Data::from_vec(v.split_off(at)),
+        }
+    }
+
+    /// Retains elements for which `f` returns `true`. Removed elements are dropped in place.
+    /// If `f` panics, remaining elements are leaked.
+    fn retain<F: FnMut(&T) -> bool>(&mut self, mut f: F) {
+        match *self {
+            Data::Inline { ref mut len, ref mut buf } => unsafe {
+                let n = *len;
+                *len = 0;
+                let p = buf.as_mut_ptr() as *mut T;
+                let mut w = 0;
+                for r in 0..n {
+                    if f(&*p.add(r)) {
+                        if r != w {
+                            ptr::copy_nonoverlapping(p.add(r), p.add(w), 1);
+                        }
+                        w += 1;
+                    } else {
+                        ptr::drop_in_place(p.add(r));
+                    }
+                }
+                *len = w;
+            },
+            Data::Heap(ref mut v) => v.retain(f),
+        }
+    }
+
+    fn into_iter(self) -> IntoIter<T, N> {
+        let data = ManuallyDrop::new(self);
+        match *data {
+            Data::Inline { len, ref buf } => IntoIter::Inline {
+                buf: unsafe { ptr::read(buf) },
+                start: 0,
+                end: len,
+            },
+            Data::Heap(ref v) => IntoIter::Heap(unsafe { ptr::read(v) }.into_iter()),
+        }
+    }
+}
+
+impl<T, const N: usize> Drop for Data<T, N> {
+    fn drop(&mut self) {
+        if let Data::Inline { len, ref mut buf } = *self {
+            unsafe {
+                ptr::drop_in_place(ptr::slice_from_raw_parts_mut(buf.as_mut_ptr() as *mut T, len));
+            }
+        }
+    }
+}
+
+impl<T, const N: usize> FromIterator<T> for Data<T, N> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let iter = iter.into_iter();
+        let mut data = Data::with_capacity(iter.size_hint().0);
+        for e in iter {
+            data.push(e);
+        }
+        data
+    }
+}
+
+/// An owning iterator over elements of a `SmallOrdSet`, in ascending order.
+pub enum IntoIter<T, const N: usize> {
+    #[doc(hidden)]
+    Inline {
+        buf: [MaybeUninit<T>; N],
+        start: usize,
+        end: usize,
+    },
+    #[doc(hidden)]
+    Heap(vec::IntoIter<T>),
+}
+
+impl<T, const N: usize> Iterator for IntoIter<T, N> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        match *self {
+            IntoIter::Inline { ref buf, ref mut start, end } => {
+                if *start < end {
+                    let elem = unsafe { ptr::read(buf[*start].as_ptr()) };
+                    *start += 1;
+                    Some(elem)
+                } else {
+                    None
+                }
+            }
+            IntoIter::Heap(ref mut it) => it.next(),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match *self {
+            IntoIter::Inline { start, end, .. } => (end - start, Some(end - start)),
+            IntoIter::Heap(ref it) => it.size_hint(),
+        }
+    }
+}
+
+impl<T, const N: usize> DoubleEndedIterator for IntoIter<T, N> {
+    fn next_back(&mut self) -> Option<T> {
+        match *self {
+            IntoIter::Inline { ref buf, start, ref mut end } => {
+                if start < *end {
+                    *end -= 1;
+                    Some(unsafe { ptr::read(buf[*end].as_ptr()) })
+                } else {
+                    None
+                }
+            }
+            IntoIter::Heap(ref mut it) => it.next_back(),
+        }
+    }
+}
+
+impl<T, const N: usize> ExactSizeIterator for IntoIter<T, N> {}
+
+impl<T, const N: usize> Drop for IntoIter<T, N> {
+    fn drop(&mut self) {
+        if let IntoIter::Inline { ref mut buf, start, end } = *self {
+            unsafe {
+                let p = (buf.as_mut_ptr() as *mut T).add(start);
+                ptr::drop_in_place(ptr::slice_from_raw_parts_mut(p, end - start));
+            }
+        }
+    }
+}
+
+#[cfg(feature = "heapsize_impl")]
+mod heapsize {
+    extern crate heapsize;
+
+    use super::*;
+
+    use self::heapsize::HeapSizeOf;
+
+    impl<T: HeapSizeOf, const N: usize> HeapSizeOf for InlineVec<T, N> {
+        fn heap_size_of_children(&self) -> usize {
+            match self.0 {
+                Data::Inline { .. } => self.0.as_slice().iter().map(|e| e.heap_size_of_children()).sum(),
+                Data::Heap(ref v) => v.heap_size_of_children(),
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::collections::BTreeSet;
+    use std::rc::Rc;
+    use test::Bencher;
+
+    use super::super::OrdSet;
+    use super::*;
+
+    fn random_sets(seed: &mut u32, count: usize, max: u32) -> (SmallOrdSet<u32, 4>, BTreeSet<u32>) {
+        let mut set = SmallOrdSet::new();
+        let mut bset = BTreeSet::new();
+        for _ in 0..count {
+            *seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            let v = (*seed >> 8) % max;
+            assert_eq!(set.insert(v).is_none(), bset.insert(v));
+        }
+        (set, bset)
+    }
+
+    #[test]
+    fn stays_inline_until_full() {
+        let mut set: SmallOrdSet<u32, 4> = SmallOrdSet::new();
+        for v in &[4, 2, 3, 1] {
+            set.insert(*v);
+        }
+        assert!(!set.spilled());
+        assert_eq!(set.capacity(), 4);
+        set.insert(0);
+        assert!(set.spilled());
+        assert_eq!(set.as_slice(), &[0, 1, 2, 3, 4]);
+        set.remove(&0);
+        set.shrink_to_fit();
+        assert!(!set.spilled());
+        assert_eq!(set.as_slice(), &[1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn operations_match_btree_set() {
+        let mut seed = 5;
+        for i in 0..30 {
+            let (mut a, mut ba) = random_sets(&mut seed, i, 20);
+            let (b, bb) = random_sets(&mut seed, 6, 20);
+            assert!(a.iter().eq(ba.iter()));
+            assert!(a.union(&b).eq(ba.union(&bb)));
+            assert!(a.intersection(&b).eq(ba.intersection(&bb)));
+            assert!(a.difference(&b).eq(ba.difference(&bb)));
+            assert!(a.symmetric_difference(&b).eq(ba.symmetric_difference(&bb)));
+            assert!((&a | &b).iter().eq(ba.union(&bb)));
+            assert_eq!(a.is_subset(&b), ba.is_subset(&bb));
+            assert!(a.range(5..15).iter().eq(ba.range(5..15)));
+
+            let mut c = a.clone();
+            let mut bc = ba.clone();
+            c.extend(b.iter().rev().cloned());
+            bc.extend(bb.iter().cloned());
+            assert!(c.iter().eq(bc.iter()));
+
+            let tail = c.split_off(&10);
+            assert!(c.iter().eq(bc.range(..10)));
+            assert!(tail.iter().eq(bc.range(10..)));
+
+            a.retain(|v| v % 3 != 0);
+            ba.retain(|v| v % 3 != 0);
+            assert!(a.iter().eq(ba.iter()));
+            assert_eq!(a.pop_first(), ba.pop_first());
+            assert_eq!(a.pop_last(), ba.pop_last());
+            assert!(a.into_iter().eq(ba.into_iter()));
+        }
+    }
+
+    #[test]
+    fn elements_are_dropped_once() {
+        #[derive(PartialEq, Eq, PartialOrd, Ord)]
+        struct Counted(u32, Rc<Cell<usize>>);
+
+        impl Drop for Counted {
+            fn drop(&mut self) {
+                self.1.set(self.1.get() + 1);
+            }
+        }
+
+        let drops = Rc::new(Cell::new(0));
+        for count in 0..8 {
+            drops.set(0);
+            let mut set: SmallOrdSet<Counted, 4> = (0..count).map(|i| Counted(i, drops.clone())).collect();
+            set.retain(|c| c.0 != 1);
+            let expected = if count > 1 { 1 } else { 0 };
+            assert_eq!(drops.get(), expected);
+            let mut it = set.into_iter();
+            it.next();
+            drop(it);
+            assert_eq!(drops.get(), count as usize);
+        }
+    }
+
+    #[test]
+    fn from_iter_deduplicates() {
+        let set: SmallOrdSet<i32, 8> = vec![5, 1, 3, 1, 5].into_iter().collect();
+        assert!(!set.spilled());
+        assert_eq!(set.as_slice(), &[1, 3, 5]);
+        let set: SmallOrdSet<i32, 2> = SmallOrdSet::from_vec(vec![3, 3, 2, 1]);
+        assert!(set.spilled());
+        assert_eq!(set.as_slice(), &[1, 2, 3]);
+    }
+
+    #[cfg(feature = "serde_impl")]
+    #[test]
+    fn serde_round_trip() {
+        let set: SmallOrdSet<u32, 4> = serde_json::from_str("[3, 1, 3, 2]").unwrap();
+        assert_eq!(set.as_slice(), &[1, 2, 3]);
+        assert_eq!(serde_json::to_string(&set).unwrap(), "[1,2,3]");
+        let copy: SmallOrdSet<u32, 2> = bincode::deserialize(&bincode::serialize(&set).unwrap()).unwrap();
+        assert!(copy.iter().eq(set.iter()));
+    }
+
+    #[cfg(feature = "heapsize_impl")]
+    #[test]
+    fn heap_size() {
+        use ::heapsize::HeapSizeOf;
+
+        let mut set: SmallOrdSet<u64, 4> = SmallOrdSet::new();
+        set.extend(0..4);
+        assert_eq!(set.heap_size_of_children(), 0);
+        set.insert(4);
+        assert!(set.heap_size_of_children() >= 5 * 8);
+    }
+
+    #[bench]
+    fn small_ord_set_insert_bench(b: &mut Bencher) {
+        b.iter(|| {
+            let mut set: SmallOrdSet<u32, 8> = SmallOrdSet::new();
+            for e in &[5, 3, 7, 1, 2, 8, 4, 6] {
+                set.insert(*e);
+            }
+            set
+        });
+    }
+
+    #[bench]
+    fn ord_set_small_insert_bench(b: &mut Bencher) {
+        b.iter(|| {
+            let mut set: OrdSet<u32> = OrdSet::new();
+            for e in &[5, 3, 7, 1, 2, 8, 4, 6] {
+                set.insert(*e);
+            }
+            set
+        });
+    }
+}
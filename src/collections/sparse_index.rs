@@ -0,0 +1,178 @@
+use std::num::{NonZeroU128, NonZeroU16, NonZeroU32, NonZeroU64, NonZeroU8, NonZeroUsize};
+
+/// Element type of sparse collections, like [`SparseSet`](super::SparseSet) or
+/// [`SparseMap`](super::SparseMap), which can be converted to and from an index.
+///
+/// Implementations are provided for all unsigned integer types and their `NonZero*` counterparts
+/// (where value `n` is mapped to index `n - 1`). Simple newtype wrappers can implement this trait
+/// with the [`sparse_index!`](crate::sparse_index) macro.
+pub trait SparseIndex: Copy {
+    /// Unsigned integer type used to store positions of elements in the sparse array.
+    ///
+    /// It can be narrower than `Self` to save memory, as long as it can represent every position
+    /// below the number of elements stored in a collection.
+    type Pos: SparsePos;
+
+    /// Converts this value to an index.
+    fn to_index(self) -> usize;
+
+    /// Converts an index back to a value. Index must have been obtained with
+    /// [to_index()][Self::to_index()].
+    fn from_index(index: usize) -> Self;
+}
+
+/// Unsigned integer type used to store positions of elements in the sparse array of sparse
+/// collections.
+///
+/// # Safety
+///
+/// Sparse arrays of [`SafeSparseSet`](super::SafeSparseSet) are zero-initialized and read
+/// before any position is written to them, so the all-zero bit pattern must be a valid value of
+/// the implementing type. Also [to_pos()][Self::to_pos()] must return any position up to
+/// [MAX_POS][Self::MAX_POS] passed to [from_pos()][Self::from_pos()] unchanged.
+pub unsafe trait SparsePos: Copy {
+    /// Maximum position representable by this type.
+    const MAX_POS: usize;
+
+    /// Converts a position, not greater than [MAX_POS][Self::MAX_POS], to this type.
+    fn from_pos(pos: usize) -> Self;
+
+    /// Converts this value back to a position.
+    fn to_pos(self) -> usize;
+}
+
+macro_rules! impl_unsigned {
+    ($($t: ty),*) => {$(
+        impl SparseIndex for $t {
+            type Pos = $t;
+
+            #[inline]
+            fn to_index(self) -> usize {
+                // values not fitting in `usize` are out of range of any collection
+                if std::mem::size_of::<$t>() > std::mem::size_of::<usize>() && self > usize::MAX as $t {
+                    usize::MAX
+                } else {
+                    self as usize
+                }
+            }
+
+            #[inline]
+            fn from_index(index: usize) -> Self {
+                index as $t
+            }
+        }
+
+        unsafe impl SparsePos for $t {
+            const MAX_POS: usize = if std::mem::size_of::<$t>() >= std::mem::size_of::<usize>() {
+                usize::MAX
+            } else {
+                <$t>::MAX as usize
+            };
+
+            #[inline]
+            fn from_pos(pos: usize) -> Self {
+                pos as $t
+            }
+
+            #[inline]
+            fn to_pos(self) -> usize {
+                self as usize
+            }
+        }
+    )*}
+}
+
+impl_unsigned!(u8, u16, u32, u64, u128, usize);
+
+macro_rules! impl_non_zero {
+    ($($t: ty => $p: ty),*) => {$(
+        impl SparseIndex for $t {
+            type Pos = $p;
+
+            #[inline]
+            fn to_index(self) -> usize {
+                SparseIndex::to_index(self.get() - 1)
+            }
+
+            #[inline]
+            fn from_index(index: usize) -> Self {
+                match <$t>::new((index + 1) as $p) {
+                    Some(v) => v,
+                    None => panic!("index out of range"),
+                }
+            }
+        }
+    )*}
+}
+
+impl_non_zero!(NonZeroU8 => u8, NonZeroU16 => u16, NonZeroU32 => u32, NonZeroU64 => u64,
+    NonZeroU128 => u128, NonZeroUsize => usize);
+
+/// Implements [`SparseIndex`](crate::collections::SparseIndex) for a newtype wrapper around
+/// a type already implementing it.
+///
+/// Positions in the sparse array are stored using the same type as for the wrapped type, unless
+/// another type is given after `=>`.
+///
+/// # Examples
+///
+/// ```
+/// # #[macro_use] extern crate kg_utils;
+/// # fn main() {
+/// use kg_utils::collections::SparseSet;
+///
+/// #[derive(Debug, Clone, Copy, PartialEq)]
+/// struct NodeId(u64);
+///
+/// // positions are stored as `u32`, halving size of the sparse array
+/// sparse_index!(NodeId(u64) => u32);
+///
+/// let mut set = SparseSet::with_capacity(16);
+/// set.insert(NodeId(10));
+/// assert!(set.contains(&NodeId(10)));
+/// # }
+/// ```
+#[macro_export]
+macro_rules! sparse_index {
+    ($name: ident ($inner: ty)) => {
+        $crate::sparse_index!($name($inner) => <$inner as $crate::collections::SparseIndex>::Pos);
+    };
+    ($name: ident ($inner: ty) => $pos: ty) => {
+        impl $crate::collections::SparseIndex for $name {
+            type Pos = $pos;
+
+            #[inline]
+            fn to_index(self) -> usize {
+                $crate::collections::SparseIndex::to_index(self.0)
+            }
+
+            #[inline]
+            fn from_index(index: usize) -> Self {
+                $name($crate::collections::SparseIndex::from_index(index))
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroU128;
+
+    use super::*;
+
+    #[test]
+    fn wide_values_are_out_of_range() {
+        assert_eq!(SparseIndex::to_index(3u128), 3);
+        assert_eq!(SparseIndex::to_index((1u128 << 100) + 3), usize::MAX);
+        assert_eq!(SparseIndex::to_index(NonZeroU128::new(4).unwrap()), 3);
+        assert_eq!(<NonZeroU128 as SparseIndex>::from_index(3).get(), 4);
+    }
+
+    #[test]
+    fn u128_elements_in_sparse_set() {
+        let mut set = crate::collections::SafeSparseSet::with_capacity_zeroed(8);
+        set.insert(3u128);
+        assert!(set.contains(&3));
+        assert!(!set.contains(&((1u128 << 100) + 3)));
+    }
+}
@@ -0,0 +1,486 @@
+use std::ptr::NonNull;
+
+use super::sparse_index::{SparseIndex, SparsePos};
+use super::sparse_set::{mem_alloc, mem_dealloc, mem_realloc};
+
+/// A very fast insertion/lookup map type `SparseMap<K, V>`, with stable insertion-order
+/// iteration, for array index-like key types.
+///
+/// Keys are stored using the same dense/sparse layout as in [`SparseSet`](super::SparseSet),
+/// therefore the same restrictions apply to the key type `K`. Values are kept in a dense array
+/// parallel to the dense array of keys, so unlike keys, values can be of any type, including
+/// types implementing `Drop`.
+///
+/// Removal of an entry moves the last entry in its place, so insertion order is not preserved
+/// after removal.
+pub struct SparseMap<K: SparseIndex, V> {
+    capacity: usize,
+    dense: NonNull<K>,
+    sparse: NonNull<K::Pos>,
+    values: Vec<V>,
+}
+
+impl<K: SparseIndex, V> SparseMap<K, V> {
+    pub fn new() -> SparseMap<K, V> {
+        Self::with_capacity(0)
+    }
+
+    pub fn with_capacity(capacity: usize) -> SparseMap<K, V> {
+        SparseMap {
+            capacity,
+            dense: unsafe { mem_alloc(capacity) },
+            sparse: unsafe { mem_alloc(capacity) },
+            values: Vec::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Grows capacity of the map to at least `capacity`, preserving current contents.
+    /// Does nothing if the map capacity is already sufficient.
+    pub fn reserve_universe(&mut self, capacity: usize) {
+        if capacity > self.capacity {
+            unsafe {
+                self.dense = mem_realloc(self.dense, self.capacity, capacity);
+                self.sparse = mem_realloc(self.sparse, self.capacity, capacity);
+            }
+            self.capacity = capacity;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.values.clear();
+    }
+
+    /// Inserts a key-value pair into the map. If the key already existed, the old value is
+    /// returned, and the entry keeps its position.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `key` is out of range of the map capacity.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        match self.index_of(&key) {
+            Some(index) => Some(std::mem::replace(&mut self.values[index], value)),
+            None => {
+                self.push(key, value);
+                None
+            }
+        }
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.index_of(key).is_some()
+    }
+
+    pub fn get(&self, key: &K) -> Option<&V> {
+        self.index_of(key).map(|index| &self.values[index])
+    }
+
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        self.index_of(key).map(move |index| &mut self.values[index])
+    }
+
+    /// Removes and returns the value corresponding to `key`. The last entry of the map takes
+    /// the place of the removed one, so this operation is O(1).
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        self.index_of(key).map(|index| self.swap_remove_index(index).1)
+    }
+
+    /// Gets the given key's corresponding entry in the map for in-place manipulation.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `key` is out of range of the map capacity.
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V> {
+        if key.to_index() >= self.capacity {
+            panic!("key out of range");
+        }
+        match self.index_of(&key) {
+            Some(index) => Entry::Occupied(OccupiedEntry { map: self, index }),
+            None => Entry::Vacant(VacantEntry { map: self, key }),
+        }
+    }
+
+    /// Returns keys of the map in insertion order.
+    pub fn keys(&self) -> &[K] {
+        unsafe { std::slice::from_raw_parts(self.dense.as_ptr(), self.len()) }
+    }
+
+    /// Returns values of the map in insertion order.
+    pub fn values(&self) -> &[V] {
+        &self.values
+    }
+
+    /// Returns mutable references to values of the map in insertion order.
+    pub fn values_mut(&mut self) -> &mut [V] {
+        &mut self.values
+    }
+
+    /// Returns an iterator visiting all key-value pairs in insertion order.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            keys: self.keys().iter(),
+            values: self.values.iter(),
+        }
+    }
+
+    /// Returns an iterator visiting all key-value pairs in insertion order, with mutable
+    /// references to the values.
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        IterMut {
+            keys: unsafe { std::slice::from_raw_parts(self.dense.as_ptr(), self.values.len()) }.iter(),
+            values: self.values.iter_mut(),
+        }
+    }
+
+    fn push(&mut self, key: K, value: V) {
+        let val = key.to_index();
+        if val >= self.capacity {
+            panic!("key out of range");
+        }
+        let i = self.values.len();
+        if i > K::Pos::MAX_POS {
+            panic!("position out of range of sparse storage");
+        }
+        unsafe {
+            std::ptr::write(self.dense.as_ptr().add(i), key);
+            std::ptr::write(self.sparse.as_ptr().add(val), K::Pos::from_pos(i));
+        }
+        self.values.push(value);
+    }
+
+    fn swap_remove_index(&mut self, index: usize) -> (K, V) {
+        let last = self.values.len() - 1;
+        unsafe {
+            let key = std::ptr::read(self.dense.as_ptr().add(index));
+            if index != last {
+                let moved = std::ptr::read(self.dense.as_ptr().add(last));
+                std::ptr::write(self.dense.as_ptr().add(index), moved);
+                std::ptr::write(self.sparse.as_ptr().add(moved.to_index()), K::Pos::from_pos(index));
+            }
+            (key, self.values.swap_remove(index))
+        }
+    }
+
+    fn index_of(&self, key: &K) -> Option<usize> {
+        let len = self.values.len();
+        if len == 0 {
+            None
+        } else {
+            let val = key.to_index();
+            if val >= self.capacity {
+                return None;
+            }
+            let i = unsafe { std::ptr::read(self.sparse.as_ptr().add(val)) }.to_pos();
+            if i < len && unsafe { std::ptr::read(self.dense.as_ptr().add(i)) }.to_index() == val {
+                Some(i)
+            } else {
+                None
+            }
+        }
+    }
+}
+
+impl<K: SparseIndex, V: Clone> Clone for SparseMap<K, V> {
+    fn clone(&self) -> Self {
+        let m = SparseMap {
+            capacity: self.capacity,
+            dense: unsafe { mem_alloc(self.capacity) },
+            sparse: unsafe { mem_alloc(self.capacity) },
+            values: self.values.clone(),
+        };
+        unsafe {
+            std::ptr::copy_nonoverlapping(self.dense.as_ptr(), m.dense.as_ptr(), self.len());
+            std::ptr::copy_nonoverlapping(self.sparse.as_ptr(), m.sparse.as_ptr(), self.capacity);
+        }
+        m
+    }
+}
+
+impl<K: SparseIndex, V> Drop for SparseMap<K, V> {
+    fn drop(&mut self) {
+        unsafe {
+            mem_dealloc(self.dense, self.capacity);
+            mem_dealloc(self.sparse, self.capacity);
+        }
+    }
+}
+
+impl<K, V> std::fmt::Debug for SparseMap<K, V>
+    where K: SparseIndex + std::fmt::Debug,
+          V: std::fmt::Debug
+{
+    #[inline]
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K: SparseIndex, V> Default for SparseMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, K: SparseIndex, V> IntoIterator for &'a SparseMap<K, V> {
+    type Item = (K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, K: SparseIndex, V> IntoIterator for &'a mut SparseMap<K, V> {
+    type Item = (K, &'a mut V);
+    type IntoIter = IterMut<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+unsafe impl<K: SparseIndex, V: Send> Send for SparseMap<K, V> {}
+
+/// An insertion-order iterator over a `SparseMap`'s entries.
+pub struct Iter<'a, K: 'a, V: 'a> {
+    keys: std::slice::Iter<'a, K>,
+    values: std::slice::Iter<'a, V>,
+}
+
+impl<'a, K: Copy, V> Iterator for Iter<'a, K, V> {
+    type Item = (K, &'a V);
+
+    fn next(&mut self) -> Option<(K, &'a V)> {
+        match (self.keys.next(), self.values.next()) {
+            (Some(&k), Some(v)) => Some((k, v)),
+            _ => None,
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.values.size_hint()
+    }
+}
+
+impl<'a, K: Copy, V> ExactSizeIterator for Iter<'a, K, V> {}
+
+/// An insertion-order iterator over a `SparseMap`'s entries, with mutable references to the
+/// values.
+pub struct IterMut<'a, K: 'a, V: 'a> {
+    keys: std::slice::Iter<'a, K>,
+    values: std::slice::IterMut<'a, V>,
+}
+
+impl<'a, K: Copy, V> Iterator for IterMut<'a, K, V> {
+    type Item = (K, &'a mut V);
+
+    fn next(&mut self) -> Option<(K, &'a mut V)> {
+        match (self.keys.next(), self.values.next()) {
+            (Some(&k), Some(v)) => Some((k, v)),
+            _ => None,
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.values.size_hint()
+    }
+}
+
+impl<'a, K: Copy, V> ExactSizeIterator for IterMut<'a, K, V> {}
+
+/// A view into a single entry in a `SparseMap`, which may be vacant or occupied.
+pub enum Entry<'a, K: SparseIndex + 'a, V: 'a> {
+    /// An occupied entry.
+    Occupied(OccupiedEntry<'a, K, V>),
+    /// A vacant entry.
+    Vacant(VacantEntry<'a, K, V>),
+}
+
+/// A view into an occupied entry in a `SparseMap`.
+pub struct OccupiedEntry<'a, K: SparseIndex + 'a, V: 'a> {
+    map: &'a mut SparseMap<K, V>,
+    index: usize,
+}
+
+/// A view into a vacant entry in a `SparseMap`.
+pub struct VacantEntry<'a, K: SparseIndex + 'a, V: 'a> {
+    map: &'a mut SparseMap<K, V>,
+    key: K,
+}
+
+impl<'a, K: SparseIndex, V> Entry<'a, K, V> {
+    pub fn key(&self) -> K {
+        match *self {
+            Entry::Occupied(ref e) => e.key(),
+            Entry::Vacant(ref e) => e.key(),
+        }
+    }
+
+    /// Ensures a value is in the entry by inserting the default if empty, and returns
+    /// a mutable reference to the value in the entry.
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        match self {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => e.insert(default),
+        }
+    }
+
+    /// Ensures a value is in the entry by inserting the result of the default function if empty,
+    /// and returns a mutable reference to the value in the entry.
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a mut V {
+        match self {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => e.insert(default()),
+        }
+    }
+
+    /// Ensures a value is in the entry by inserting the default value if empty, and returns
+    /// a mutable reference to the value in the entry.
+    pub fn or_default(self) -> &'a mut V where V: Default {
+        self.or_insert_with(V::default)
+    }
+}
+
+impl<'a, K: SparseIndex, V> OccupiedEntry<'a, K, V> {
+    pub fn key(&self) -> K {
+        self.map.keys()[self.index]
+    }
+
+    pub fn get(&self) -> &V {
+        &self.map.values[self.index]
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.map.values[self.index]
+    }
+
+    pub fn into_mut(self) -> &'a mut V {
+        &mut self.map.values[self.index]
+    }
+
+    /// Sets the value of the entry, and returns the entry's old value.
+    pub fn insert(&mut self, value: V) -> V {
+        std::mem::replace(&mut self.map.values[self.index], value)
+    }
+
+    /// Takes the value out of the entry, and returns it.
+    pub fn remove(self) -> V {
+        self.map.swap_remove_index(self.index).1
+    }
+}
+
+impl<'a, K: SparseIndex, V> VacantEntry<'a, K, V> {
+    pub fn key(&self) -> K {
+        self.key
+    }
+
+    /// Sets the value of the entry, and returns a mutable reference to it.
+    pub fn insert(self, value: V) -> &'a mut V {
+        self.map.push(self.key, value);
+        self.map.values.last_mut().unwrap()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::collections::HashMap;
+    use std::rc::Rc;
+
+    use super::*;
+
+    struct DropCounter(Rc<Cell<usize>>);
+
+    impl Drop for DropCounter {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    #[test]
+    fn operations_match_hash_map() {
+        let mut map = SparseMap::with_capacity(128);
+        let mut hmap = HashMap::new();
+        let mut seed = 5u32;
+        for i in 0..5000 {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            let k = ((seed >> 16) % 128) as u16;
+            match (seed >> 8) % 3 {
+                0 => assert_eq!(map.remove(&k), hmap.remove(&k)),
+                _ => assert_eq!(map.insert(k, i), hmap.insert(k, i)),
+            }
+            assert_eq!(map.len(), hmap.len());
+        }
+        for k in 0u16..128 {
+            assert_eq!(map.get(&k), hmap.get(&k));
+        }
+        assert_eq!(map.iter().count(), hmap.len());
+    }
+
+    #[test]
+    fn iterates_in_insertion_order() {
+        let mut map = SparseMap::with_capacity(16);
+        map.insert(7u8, "a");
+        map.insert(3u8, "b");
+        map.insert(11u8, "c");
+        map.insert(3u8, "d");
+        assert_eq!(map.keys(), &[7, 3, 11]);
+        assert_eq!(map.values(), &["a", "d", "c"]);
+        for v in map.values_mut() {
+            *v = "x";
+        }
+        assert_eq!(map.iter().collect::<Vec<_>>(), vec![(7, &"x"), (3, &"x"), (11, &"x")]);
+    }
+
+    #[test]
+    fn entry_api() {
+        let mut map = SparseMap::with_capacity(16);
+        *map.entry(4u32).or_insert(0) += 1;
+        *map.entry(4u32).or_insert(0) += 1;
+        *map.entry(2u32).or_default() += 5;
+        assert_eq!(map.get(&4), Some(&2));
+        assert_eq!(map.get(&2), Some(&5));
+        match map.entry(4) {
+            Entry::Occupied(e) => assert_eq!(e.remove(), 2),
+            Entry::Vacant(_) => unreachable!(),
+        }
+        assert!(!map.contains_key(&4));
+        assert_eq!(map.keys(), &[2]);
+    }
+
+    #[test]
+    #[should_panic(expected = "key out of range")]
+    fn entry_out_of_range_panics() {
+        let mut map: SparseMap<u8, ()> = SparseMap::with_capacity(4);
+        map.entry(4);
+    }
+
+    #[test]
+    fn values_are_dropped() {
+        let drops = Rc::new(Cell::new(0));
+        {
+            let mut map = SparseMap::with_capacity(8);
+            for k in 0u8..8 {
+                map.insert(k, DropCounter(drops.clone()));
+            }
+            map.insert(0, DropCounter(drops.clone()));
+            assert_eq!(drops.get(), 1);
+            drop(map.remove(&1));
+            assert_eq!(drops.get(), 2);
+            assert_eq!(map.len(), 7);
+        }
+        assert_eq!(drops.get(), 9);
+    }
+}
@@ -1,9 +1,11 @@
-use std::alloc::{alloc, dealloc, handle_alloc_error, Layout, realloc};
+use std::alloc::{alloc, alloc_zeroed, dealloc, handle_alloc_error, Layout, realloc};
 use std::collections::VecDeque;
-use std::convert::{TryFrom, TryInto};
+use std::marker::PhantomData;
 use std::ops::Deref;
 use std::ptr::NonNull;
 
+use super::sparse_index::{SparseIndex, SparsePos};
+
 /// A very fast insertion/lookup set type `SparseSet<T>`, with stable insertion-order iteration,
 /// for array index-like element types.
 ///
@@ -12,31 +14,134 @@ use std::ptr::NonNull;
 /// * `T` **must not** implement `Drop`. Implementation sometimes reads elements from uninitialized
 /// memory, and therefore elements cannot be reliably dropped.
 /// * `T` must implement `Copy`.
-/// * `T` must be convertible to and from `usize`, where the converted `usize` value must always
-/// lie in the range from 0 to set capacity (see [capacity()][Self::capacity()] method).
+/// * `T` must implement [SparseIndex], where the index of each element must always lie in the
+///   range from 0 to set capacity (see [capacity()][Self::capacity()] method).
 ///
-/// Pretty much the only sensible choice for `T` is one of primitive integral types, possibly
-/// wrapped in a simple wrapper type, if needed.
+/// Pretty much the only sensible choice for `T` is one of primitive unsigned integral types,
+/// possibly wrapped in a simple wrapper type (see [sparse_index!](crate::sparse_index)), if
+/// needed.
 /// In general this set type is most useful for lookup algorithms, to store indices of an input
 /// collection that fulfill a specific conditions.
-pub struct SparseSet<T: TryFrom<usize> + TryInto<usize> + Copy> {
+///
+/// The second type parameter `M` decides how the sparse array is allocated, see [SparseInit].
+/// [SafeSparseSet] can be used where reading uninitialized memory is not acceptable.
+pub struct SparseSet<T: SparseIndex, M: SparseInit = Uninit> {
     capacity: usize,
     len: usize,
     dense: NonNull<T>,
-    sparse: NonNull<T>,
+    sparse: NonNull<T::Pos>,
+    marker: PhantomData<M>,
 }
 
-impl<T: TryFrom<usize> + TryInto<usize> + Copy> SparseSet<T> {
+/// A [SparseSet] with zero-initialized sparse storage, which never reads uninitialized memory.
+///
+/// It has the same API as [SparseSet], at the cost of zeroing the sparse array on allocation.
+pub type SafeSparseSet<T> = SparseSet<T, Zeroed>;
+
+/// Allocation policy for the sparse array of a [SparseSet].
+///
+/// # Safety
+///
+/// Implementations must return memory valid for `size` (or `new_size`) elements of `T`,
+/// allocated with the layout used by [SparseSet] internally.
+pub unsafe trait SparseInit {
+    #[doc(hidden)]
+    unsafe fn alloc<T>(size: usize) -> NonNull<T>;
+
+    #[doc(hidden)]
+    unsafe fn realloc<T>(ptr: NonNull<T>, size: usize, new_size: usize) -> NonNull<T>;
+}
+
+/// Sparse array is left uninitialized. Lookups may read uninitialized memory.
+#[derive(Debug)]
+pub struct Uninit;
+
+unsafe impl SparseInit for Uninit {
+    #[inline]
+    unsafe fn alloc<T>(size: usize) -> NonNull<T> {
+        mem_alloc(size)
+    }
+
+    #[inline]
+    unsafe fn realloc<T>(ptr: NonNull<T>, size: usize, new_size: usize) -> NonNull<T> {
+        mem_realloc(ptr, size, new_size)
+    }
+}
+
+/// Sparse array is zero-initialized, including memory added when growing.
+#[derive(Debug)]
+pub struct Zeroed;
+
+unsafe impl SparseInit for Zeroed {
+    #[inline]
+    unsafe fn alloc<T>(size: usize) -> NonNull<T> {
+        mem_alloc_zeroed(size)
+    }
+
+    #[inline]
+    unsafe fn realloc<T>(ptr: NonNull<T>, size: usize, new_size: usize) -> NonNull<T> {
+        if size == 0 {
+            mem_alloc_zeroed(new_size)
+        } else {
+            let ptr = mem_realloc(ptr, size, new_size);
+            if new_size > size {
+                std::ptr::write_bytes(ptr.as_ptr().add(size), 0, new_size - size);
+            }
+            ptr
+        }
+    }
+}
+
+impl<T: SparseIndex> SparseSet<T> {
     pub fn new() -> SparseSet<T> {
         Self::with_capacity(0)
     }
 
     pub fn with_capacity(capacity: usize) -> SparseSet<T> {
+        Self::with_universe(capacity)
+    }
+}
+
+// `new()` and `with_capacity()` are only defined for the default policy, so that the set type
+// can still be inferred from them; these are their counterparts for `SafeSparseSet`.
+impl<T: SparseIndex> SparseSet<T, Zeroed> {
+    /// Creates an empty set with zero capacity.
+    ///
+    /// ```
+    /// use kg_utils::collections::SafeSparseSet;
+    ///
+    /// let mut set = SafeSparseSet::new_zeroed();
+    /// assert!(set.insert_grow(10u32));
+    /// assert!(set.contains(&10));
+    /// ```
+    pub fn new_zeroed() -> SafeSparseSet<T> {
+        Self::with_capacity_zeroed(0)
+    }
+
+    /// Creates an empty set with the given capacity, same as
+    /// [with_universe()][Self::with_universe()].
+    pub fn with_capacity_zeroed(capacity: usize) -> SafeSparseSet<T> {
+        Self::with_universe(capacity)
+    }
+}
+
+impl<T: SparseIndex, M: SparseInit> SparseSet<T, M> {
+    /// Creates an empty set with the given capacity, for any sparse storage policy `M`.
+    ///
+    /// ```
+    /// use kg_utils::collections::SafeSparseSet;
+    ///
+    /// let mut set = SafeSparseSet::with_universe(16);
+    /// set.insert(10u32);
+    /// assert!(set.contains(&10));
+    /// ```
+    pub fn with_universe(capacity: usize) -> SparseSet<T, M> {
         SparseSet {
             capacity,
             len: 0,
             dense: unsafe { mem_alloc(capacity) },
-            sparse: unsafe { mem_alloc(capacity) },
+            sparse: unsafe { M::alloc(capacity) },
+            marker: PhantomData,
         }
     }
 
@@ -52,54 +157,551 @@ impl<T: TryFrom<usize> + TryInto<usize> + Copy> SparseSet<T> {
         self.capacity
     }
 
+    /// Changes capacity of the set to `capacity`. The set is cleared in the process.
+    ///
+    /// Use [reserve_universe()][Self::reserve_universe()] to grow the set while preserving its
+    /// contents.
     pub fn resize(&mut self, capacity: usize) {
         if self.capacity != capacity {
             unsafe {
                 self.dense = mem_realloc(self.dense, self.capacity, capacity);
-                self.sparse = mem_realloc(self.sparse, self.capacity, capacity);
+                self.sparse = M::realloc(self.sparse, self.capacity, capacity);
             }
             self.capacity = capacity;
             self.len = 0;
         }
     }
 
+    /// Grows capacity of the set to at least `capacity`, preserving current contents.
+    /// Does nothing if the set capacity is already sufficient.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the set has to grow beyond `T::Pos::MAX_POS + 1`, the number of positions
+    /// representable in the sparse array.
+    pub fn reserve_universe(&mut self, capacity: usize) {
+        if capacity > self.capacity {
+            assert!(capacity <= T::Pos::MAX_POS.saturating_add(1),
+                "capacity {} out of range of sparse storage", capacity);
+            unsafe {
+                self.dense = mem_realloc(self.dense, self.capacity, capacity);
+                self.sparse = M::realloc(self.sparse, self.capacity, capacity);
+            }
+            self.capacity = capacity;
+        }
+    }
+
     pub fn clear(&mut self) {
         self.len = 0;
     }
 
+    /// Inserts `value` into the set.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `value` is out of range of the set capacity.
     pub fn insert(&mut self, value: T) {
-        let val = to_usize(value);
-        if val >= self.capacity {
+        if self.try_insert(value).is_err() {
             panic!("value out of range");
-        } else if !self.contains(&value) {
+        }
+    }
+
+    /// Inserts `value` into the set, returning whether it was newly inserted, or an error if
+    /// `value` is out of range of the set capacity.
+    pub fn try_insert(&mut self, value: T) -> Result<bool, OutOfRange> {
+        let val = value.to_index();
+        if val >= self.capacity {
+            Err(OutOfRange { value: val, capacity: self.capacity })
+        } else if self.contains(&value) {
+            Ok(false)
+        } else {
             let i = self.len;
+            if i > T::Pos::MAX_POS {
+                panic!("position out of range of sparse storage");
+            }
             unsafe {
                 std::ptr::write(self.dense.as_ptr().offset(i as isize), value);
-                std::ptr::write(self.sparse.as_ptr().offset(val as isize), to_value(i));
+                self.set_pos(val, i);
             }
             self.len += 1;
+            Ok(true)
         }
     }
 
-    pub fn contains(&self, value: &T) -> bool {
+    /// Inserts `value` into the set, returning whether it was newly inserted. If `value` is out
+    /// of range, the set capacity is grown (at least doubled, up to `T::Pos::MAX_POS + 1`) to fit
+    /// it, preserving current contents.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `value` is out of range of the maximum capacity `T::Pos::MAX_POS + 1`.
+    pub fn insert_grow(&mut self, value: T) -> bool {
+        let val = value.to_index();
+        if val >= self.capacity {
+            let max_capacity = T::Pos::MAX_POS.saturating_add(1);
+            if val >= max_capacity {
+                panic!("value out of range of sparse storage");
+            }
+            let doubled = std::cmp::min(self.capacity.saturating_mul(2), max_capacity);
+            self.reserve_universe(std::cmp::max(val + 1, doubled));
+        }
+        match self.try_insert(value) {
+            Ok(inserted) => inserted,
+            Err(_) => panic!("value out of range"),
+        }
+    }
+
+    /// Removes `value` from the set, returning whether it was present.
+    ///
+    /// The last element of the set takes the place of the removed one, so this operation is O(1),
+    /// but it does not preserve insertion order. See [shift_remove()][Self::shift_remove()] for
+    /// an order-preserving variant.
+    pub fn remove(&mut self, value: &T) -> bool {
+        match self.index_of(value) {
+            Some(index) => {
+                self.swap_remove_index(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Removes `value` from the set, returning whether it was present.
+    ///
+    /// All elements following the removed one are shifted, so insertion order is preserved,
+    /// but this operation is O(n).
+    pub fn shift_remove(&mut self, value: &T) -> bool {
+        match self.index_of(value) {
+            Some(index) => {
+                self.shift_remove_index(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Removes the most recently inserted element and returns it, or `None` if the set is empty.
+    pub fn pop(&mut self) -> Option<T> {
         if self.len == 0 {
-            false
+            None
+        } else {
+            self.len -= 1;
+            Some(unsafe { self.dense_get(self.len) })
+        }
+    }
+
+    /// Removes and returns the element at position `index` in the dense array, replacing it with
+    /// the last element. This operation is O(1).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn swap_remove_index(&mut self, index: usize) -> T {
+        if index >= self.len {
+            panic!("index out of bounds");
+        }
+        let last = self.len - 1;
+        unsafe {
+            let value = self.dense_get(index);
+            if index != last {
+                let moved = self.dense_get(last);
+                std::ptr::write(self.dense.as_ptr().add(index), moved);
+                self.set_pos(moved.to_index(), index);
+            }
+            self.len = last;
+            value
+        }
+    }
+
+    /// Removes and returns the element at position `index` in the dense array, shifting all
+    /// elements after it to the left. Insertion order is preserved, but this operation is O(n).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn shift_remove_index(&mut self, index: usize) -> T {
+        if index >= self.len {
+            panic!("index out of bounds");
+        }
+        let last = self.len - 1;
+        unsafe {
+            let value = self.dense_get(index);
+            std::ptr::copy(self.dense.as_ptr().add(index + 1),
+                           self.dense.as_ptr().add(index),
+                           last - index);
+            for i in index..last {
+                let moved = self.dense_get(i);
+                self.set_pos(moved.to_index(), i);
+            }
+            self.len = last;
+            value
+        }
+    }
+
+    /// Adds all elements of `other` to this set. New elements are appended in the order they
+    /// appear in `other`. Capacity of this set is grown if needed.
+    pub fn union_with(&mut self, other: &SparseSet<T, M>) {
+        for &e in other.iter() {
+            self.insert_grow(e);
+        }
+    }
+
+    /// Retains only elements also present in `other`, preserving their order.
+    pub fn intersect_with(&mut self, other: &SparseSet<T, M>) {
+        self.retain(|e| other.contains(&e));
+    }
+
+    /// Removes all elements present in `other`, preserving order of the remaining ones.
+    pub fn difference_with(&mut self, other: &SparseSet<T, M>) {
+        self.retain(|e| !other.contains(&e));
+    }
+
+    /// Retains elements present in exactly one of the sets. Elements of this set keep their
+    /// order and elements of `other` not present here are appended in their order.
+    pub fn symmetric_difference_with(&mut self, other: &SparseSet<T, M>) {
+        let len = self.len;
+        for &e in other.iter() {
+            if !self.contains(&e) {
+                self.insert_grow(e);
+            }
+        }
+        let mut index = 0;
+        self.retain(|e| {
+            index += 1;
+            index > len || !other.contains(&e)
+        });
+    }
+
+    /// Returns a lazy iterator over elements of this set, followed by elements of `other` not
+    /// present in this set.
+    pub fn union<'a>(&'a self, other: &'a SparseSet<T, M>) -> Union<'a, T, M> {
+        Union {
+            iter: self.iter(),
+            other: other.iter(),
+            set: self,
+        }
+    }
+
+    /// Returns a lazy iterator over elements of this set also present in `other`.
+    pub fn intersection<'a>(&'a self, other: &'a SparseSet<T, M>) -> Intersection<'a, T, M> {
+        Intersection {
+            iter: self.iter(),
+            other,
+        }
+    }
+
+    /// Returns a lazy iterator over elements of this set not present in `other`.
+    pub fn difference<'a>(&'a self, other: &'a SparseSet<T, M>) -> Difference<'a, T, M> {
+        Difference {
+            iter: self.iter(),
+            other,
+        }
+    }
+
+    /// Returns a lazy iterator over elements of this set not present in `other`, followed by
+    /// elements of `other` not present in this set.
+    pub fn symmetric_difference<'a>(&'a self, other: &'a SparseSet<T, M>) -> SymmetricDifference<'a, T, M> {
+        SymmetricDifference {
+            iter: self.difference(other).chain(other.difference(self)),
+        }
+    }
+
+    /// Retains only elements for which `f` returns `true`, preserving their order.
+    /// This operation is O(n).
+    pub fn retain<F: FnMut(T) -> bool>(&mut self, mut f: F) {
+        let mut j = 0;
+        for i in 0..self.len {
+            let e = unsafe { self.dense_get(i) };
+            if f(e) {
+                if i != j {
+                    unsafe {
+                        std::ptr::write(self.dense.as_ptr().add(j), e);
+                        self.set_pos(e.to_index(), j);
+                    }
+                }
+                j += 1;
+            }
+        }
+        self.len = j;
+    }
+
+    /// Removes all elements from the set, returning them as an iterator in insertion order.
+    pub fn drain(&mut self) -> Drain<'_, T> {
+        let len = self.len;
+        self.len = 0;
+        Drain {
+            iter: unsafe { std::slice::from_raw_parts(self.dense.as_ptr(), len) }.iter(),
+        }
+    }
+
+    /// Sorts elements of the set, updating the sparse array accordingly.
+    pub fn sort(&mut self) where T: Ord {
+        self.dense_mut().sort_unstable();
+        self.rebuild_sparse();
+    }
+
+    /// Sorts elements of the set with a comparator function, updating the sparse array
+    /// accordingly.
+    pub fn sort_by<F>(&mut self, compare: F)
+        where F: FnMut(&T, &T) -> std::cmp::Ordering
+    {
+        self.dense_mut().sort_by(compare);
+        self.rebuild_sparse();
+    }
+
+    /// Sorts elements of the set with a key extraction function, updating the sparse array
+    /// accordingly.
+    pub fn sort_by_key<K, F>(&mut self, f: F)
+        where K: Ord, F: FnMut(&T) -> K
+    {
+        self.dense_mut().sort_by_key(f);
+        self.rebuild_sparse();
+    }
+
+    fn dense_mut(&mut self) -> &mut [T] {
+        unsafe { std::slice::from_raw_parts_mut(self.dense.as_ptr(), self.len) }
+    }
+
+    fn rebuild_sparse(&mut self) {
+        for i in 0..self.len {
+            unsafe {
+                let e = self.dense_get(i);
+                self.set_pos(e.to_index(), i);
+            }
+        }
+    }
+
+    /// Returns position of `value` in the dense array, or `None` if the value is not present.
+    pub fn index_of(&self, value: &T) -> Option<usize> {
+        if self.len == 0 {
+            None
         } else {
-            let val = to_usize(*value);
-            let i = to_usize(unsafe { std::ptr::read(self.sparse.as_ptr().offset(val as isize)) });
-            if i < self.len {
-                let j = to_usize(unsafe { std::ptr::read(self.dense.as_ptr().offset(i as isize)) });
-                val == j
+            let val = value.to_index();
+            if val >= self.capacity {
+                return None;
+            }
+            let i = unsafe { self.get_pos(val) };
+            if i < self.len && unsafe { self.dense_get(i) }.to_index() == val {
+                Some(i)
             } else {
-                false
+                None
             }
         }
     }
+
+    #[inline]
+    unsafe fn dense_get(&self, index: usize) -> T {
+        std::ptr::read(self.dense.as_ptr().add(index))
+    }
+
+    #[inline]
+    unsafe fn get_pos(&self, val: usize) -> usize {
+        std::ptr::read(self.sparse.as_ptr().add(val)).to_pos()
+    }
+
+    #[inline]
+    unsafe fn set_pos(&mut self, val: usize, pos: usize) {
+        std::ptr::write(self.sparse.as_ptr().add(val), T::Pos::from_pos(pos));
+    }
+
+    pub fn contains(&self, value: &T) -> bool {
+        self.index_of(value).is_some()
+    }
+}
+
+/// Lazy iterator over union of two `SparseSet`s, see [SparseSet::union()].
+pub struct Union<'a, T: SparseIndex, M: SparseInit = Uninit> {
+    iter: std::slice::Iter<'a, T>,
+    other: std::slice::Iter<'a, T>,
+    set: &'a SparseSet<T, M>,
+}
+
+impl<'a, T: SparseIndex, M: SparseInit> Iterator for Union<'a, T, M> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        if let Some(&e) = self.iter.next() {
+            return Some(e);
+        }
+        let set = self.set;
+        self.other.find(|e| !set.contains(e)).cloned()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.iter.len();
+        (len, Some(len + self.other.len()))
+    }
+}
+
+/// Lazy iterator over intersection of two `SparseSet`s, see [SparseSet::intersection()].
+pub struct Intersection<'a, T: SparseIndex, M: SparseInit = Uninit> {
+    iter: std::slice::Iter<'a, T>,
+    other: &'a SparseSet<T, M>,
+}
+
+impl<'a, T: SparseIndex, M: SparseInit> Iterator for Intersection<'a, T, M> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        let other = self.other;
+        self.iter.find(|e| other.contains(e)).cloned()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.iter.len()))
+    }
+}
+
+/// Lazy iterator over difference of two `SparseSet`s, see [SparseSet::difference()].
+pub struct Difference<'a, T: SparseIndex, M: SparseInit = Uninit> {
+    iter: std::slice::Iter<'a, T>,
+    other: &'a SparseSet<T, M>,
+}
+
+impl<'a, T: SparseIndex, M: SparseInit> Iterator for Difference<'a, T, M> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        let other = self.other;
+        self.iter.find(|e| !other.contains(e)).cloned()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.iter.len()))
+    }
+}
+
+/// Lazy iterator over symmetric difference of two `SparseSet`s,
+/// see [SparseSet::symmetric_difference()].
+pub struct SymmetricDifference<'a, T: SparseIndex, M: SparseInit = Uninit> {
+    iter: std::iter::Chain<Difference<'a, T, M>, Difference<'a, T, M>>,
+}
+
+impl<'a, T: SparseIndex, M: SparseInit> Iterator for SymmetricDifference<'a, T, M> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.iter.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+/// A draining iterator over elements of a `SparseSet`, see [SparseSet::drain()].
+pub struct Drain<'a, T: 'a> {
+    iter: std::slice::Iter<'a, T>,
+}
+
+impl<'a, T: Copy> Iterator for Drain<'a, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.iter.next().cloned()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'a, T: Copy> DoubleEndedIterator for Drain<'a, T> {
+    fn next_back(&mut self) -> Option<T> {
+        self.iter.next_back().cloned()
+    }
+}
+
+impl<'a, T: Copy> ExactSizeIterator for Drain<'a, T> {}
+
+/// A consuming insertion-order iterator over elements of a `SparseSet`.
+pub struct IntoIter<T: SparseIndex, M: SparseInit = Uninit> {
+    set: SparseSet<T, M>,
+    index: usize,
+}
+
+impl<T: SparseIndex, M: SparseInit> Iterator for IntoIter<T, M> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        if self.index < self.set.len {
+            self.index += 1;
+            Some(unsafe { self.set.dense_get(self.index - 1) })
+        } else {
+            None
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.set.len - self.index;
+        (len, Some(len))
+    }
+}
+
+impl<T: SparseIndex, M: SparseInit> DoubleEndedIterator for IntoIter<T, M> {
+    fn next_back(&mut self) -> Option<T> {
+        if self.index < self.set.len {
+            self.set.len -= 1;
+            Some(unsafe { self.set.dense_get(self.set.len) })
+        } else {
+            None
+        }
+    }
+}
+
+impl<T: SparseIndex, M: SparseInit> ExactSizeIterator for IntoIter<T, M> {}
+
+impl<T: SparseIndex, M: SparseInit> IntoIterator for SparseSet<T, M> {
+    type Item = T;
+    type IntoIter = IntoIter<T, M>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter {
+            set: self,
+            index: 0,
+        }
+    }
+}
+
+impl<'a, T: SparseIndex, M: SparseInit> IntoIterator for &'a SparseSet<T, M> {
+    type Item = &'a T;
+    type IntoIter = std::slice::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Error returned by [SparseSet::try_insert()] when the value does not fit in the set capacity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutOfRange {
+    value: usize,
+    capacity: usize,
+}
+
+impl OutOfRange {
+    /// Value that was attempted to be inserted, converted to `usize`.
+    pub fn value(&self) -> usize {
+        self.value
+    }
+
+    /// Capacity of the set at the time of insertion.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
+
+impl std::fmt::Display for OutOfRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "value {} out of range of set capacity {}", self.value, self.capacity)
+    }
 }
 
-impl<T: TryFrom<usize> + TryInto<usize> + Copy> Clone for SparseSet<T> {
+impl std::error::Error for OutOfRange {}
+
+impl<T: SparseIndex, M: SparseInit> Clone for SparseSet<T, M> {
     fn clone(&self) -> Self {
-        let mut s = SparseSet::with_capacity(self.capacity);
+        let mut s = SparseSet::with_universe(self.capacity);
         unsafe {
             std::ptr::copy_nonoverlapping(self.dense.as_ptr(), s.dense.as_ptr(), self.len);
             std::ptr::copy_nonoverlapping(self.sparse.as_ptr(), s.sparse.as_ptr(), self.capacity);
@@ -109,7 +711,7 @@ impl<T: TryFrom<usize> + TryInto<usize> + Copy> Clone for SparseSet<T> {
     }
 }
 
-impl<T: TryFrom<usize> + TryInto<usize> + Copy> Drop for SparseSet<T> {
+impl<T: SparseIndex, M: SparseInit> Drop for SparseSet<T, M> {
     fn drop(&mut self) {
         unsafe {
             mem_dealloc(self.dense, self.capacity);
@@ -118,7 +720,7 @@ impl<T: TryFrom<usize> + TryInto<usize> + Copy> Drop for SparseSet<T> {
     }
 }
 
-impl<T: TryFrom<usize> + TryInto<usize> + Copy> Deref for SparseSet<T> {
+impl<T: SparseIndex, M: SparseInit> Deref for SparseSet<T, M> {
     type Target = [T];
 
     fn deref(&self) -> &Self::Target {
@@ -126,18 +728,20 @@ impl<T: TryFrom<usize> + TryInto<usize> + Copy> Deref for SparseSet<T> {
     }
 }
 
-impl<T: TryFrom<usize> + TryInto<usize> + Copy + std::fmt::Debug> std::fmt::Debug for SparseSet<T> {
+impl<T: SparseIndex + std::fmt::Debug, M: SparseInit> std::fmt::Debug for SparseSet<T, M> {
     #[inline]
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         f.debug_set().entries(self.deref().iter()).finish()
     }
 }
 
-impl<A, B> PartialEq<SparseSet<B>> for SparseSet<A>
-    where A: TryFrom<usize> + TryInto<usize> + Copy + PartialEq<B>,
-          B: TryFrom<usize> + TryInto<usize> + Copy
+impl<A, B, M, N> PartialEq<SparseSet<B, N>> for SparseSet<A, M>
+    where A: SparseIndex + PartialEq<B>,
+          B: SparseIndex,
+          M: SparseInit,
+          N: SparseInit
 {
-    fn eq(&self, other: &SparseSet<B>) -> bool {
+    fn eq(&self, other: &SparseSet<B, N>) -> bool {
         if self.len() == other.len() {
             for (a, b) in self.iter().zip(other.iter()) {
                 if a != b {
@@ -151,9 +755,9 @@ impl<A, B> PartialEq<SparseSet<B>> for SparseSet<A>
     }
 }
 
-impl<A, B> PartialEq<Vec<B>> for SparseSet<A>
-    where A: TryFrom<usize> + TryInto<usize> + Copy + PartialEq<B>,
-          B: TryFrom<usize> + TryInto<usize> + Copy
+impl<A, B, M> PartialEq<Vec<B>> for SparseSet<A, M>
+    where A: SparseIndex + PartialEq<B>,
+          M: SparseInit
 {
     fn eq(&self, other: &Vec<B>) -> bool {
         if self.len() == other.len() {
@@ -169,9 +773,9 @@ impl<A, B> PartialEq<Vec<B>> for SparseSet<A>
     }
 }
 
-impl<A, B> PartialEq<VecDeque<B>> for SparseSet<A>
-    where A: TryFrom<usize> + TryInto<usize> + Copy + PartialEq<B>,
-          B: TryFrom<usize> + TryInto<usize> + Copy
+impl<A, B, M> PartialEq<VecDeque<B>> for SparseSet<A, M>
+    where A: SparseIndex + PartialEq<B>,
+          M: SparseInit
 {
     fn eq(&self, other: &VecDeque<B>) -> bool {
         if self.len() == other.len() {
@@ -187,58 +791,186 @@ impl<A, B> PartialEq<VecDeque<B>> for SparseSet<A>
     }
 }
 
-impl<T: TryFrom<usize> + TryInto<usize> + Copy> Default for SparseSet<T> {
+impl<T: SparseIndex, M: SparseInit> Default for SparseSet<T, M> {
     fn default() -> Self {
-        Self::new()
+        Self::with_universe(0)
     }
 }
 
-unsafe impl<T: TryFrom<usize> + TryInto<usize> + Copy> Send for SparseSet<T> {}
+unsafe impl<T: SparseIndex, M: SparseInit> Send for SparseSet<T, M> {}
 
 
+/// Serialization of `SparseSet` records set capacity alongside elements, as a struct with
+/// `capacity` and `elements` fields.
+///
+/// For human-readable formats, a plain sequence of elements (format used by previous versions)
+/// is also accepted when deserializing. Capacity of such set is then derived from the maximum
+/// element.
 #[cfg(feature = "serde_impl")]
 mod serde {
     extern crate serde;
 
+    use std::fmt;
+
     use super::*;
 
     use self::serde::{de, ser};
 
-    impl<T> ser::Serialize for SparseSet<T>
-        where T: TryFrom<usize> + TryInto<usize> + Copy + ser::Serialize
+    const FIELDS: &[&str] = &["capacity", "elements"];
+
+    impl<T, M> ser::Serialize for SparseSet<T, M>
+        where T: SparseIndex + ser::Serialize,
+              M: SparseInit
     {
         fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
             where S: ser::Serializer
         {
-            use self::ser::SerializeSeq;
+            use self::ser::SerializeStruct;
 
-            let mut seq = serializer.serialize_seq(Some(self.len()))?;
-            for e in self.iter() {
-                seq.serialize_element(e)?;
-            }
-            seq.end()
+            let mut st = serializer.serialize_struct("SparseSet", 2)?;
+            st.serialize_field("capacity", &self.capacity)?;
+            st.serialize_field("elements", self.deref())?;
+            st.end()
         }
     }
 
-    impl<'de, T> de::Deserialize<'de> for SparseSet<T>
-        where T: TryFrom<usize> + TryInto<usize> + Copy + de::Deserialize<'de>
-    {
+    enum Field {
+        Capacity,
+        Elements,
+    }
+
+    impl<'de> de::Deserialize<'de> for Field {
         fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
             where D: de::Deserializer<'de>
         {
-            let elems: Vec<T> = Vec::deserialize(deserializer)?;
+            struct FieldVisitor;
 
-            let mut max = 0;
-            for e in elems.iter().cloned() {
-                max = std::cmp::max(max, to_usize(e));
+            impl<'de> de::Visitor<'de> for FieldVisitor {
+                type Value = Field;
+
+                fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                    f.write_str("`capacity` or `elements`")
+                }
+
+                fn visit_str<E>(self, value: &str) -> Result<Field, E>
+                    where E: de::Error
+                {
+                    match value {
+                        "capacity" => Ok(Field::Capacity),
+                        "elements" => Ok(Field::Elements),
+                        _ => Err(de::Error::unknown_field(value, FIELDS)),
+                    }
+                }
             }
 
-            let mut set = SparseSet::with_capacity(max);
-            for e in elems {
-                set.insert(e);
+            deserializer.deserialize_identifier(FieldVisitor)
+        }
+    }
+
+    struct SparseSetVisitor<T, M> {
+        human_readable: bool,
+        marker: PhantomData<(T, M)>,
+    }
+
+    impl<'de, T, M> de::Visitor<'de> for SparseSetVisitor<T, M>
+        where T: SparseIndex + de::Deserialize<'de>,
+              M: SparseInit
+    {
+        type Value = SparseSet<T, M>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a sparse set")
+        }
+
+        fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+            where A: de::SeqAccess<'de>
+        {
+            if self.human_readable {
+                let mut elems = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+                while let Some(e) = seq.next_element()? {
+                    elems.push(e);
+                }
+                build_set(None, elems)
+            } else {
+                let capacity = seq.next_element()?
+                    .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+                let elems = seq.next_element()?
+                    .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+                build_set(Some(capacity), elems)
             }
+        }
 
-            Ok(set)
+        fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+            where A: de::MapAccess<'de>
+        {
+            let mut capacity = None;
+            let mut elems = None;
+            while let Some(key) = map.next_key()? {
+                match key {
+                    Field::Capacity => {
+                        if capacity.is_some() {
+                            return Err(de::Error::duplicate_field("capacity"));
+                        }
+                        capacity = Some(map.next_value()?);
+                    }
+                    Field::Elements => {
+                        if elems.is_some() {
+                            return Err(de::Error::duplicate_field("elements"));
+                        }
+                        elems = Some(map.next_value()?);
+                    }
+                }
+            }
+            let elems = elems.ok_or_else(|| de::Error::missing_field("elements"))?;
+            build_set(capacity, elems)
+        }
+    }
+
+    fn build_set<T, M, E>(capacity: Option<usize>, elems: Vec<T>) -> Result<SparseSet<T, M>, E>
+        where T: SparseIndex,
+              M: SparseInit,
+              E: de::Error
+    {
+        let capacity = match capacity {
+            Some(capacity) => capacity,
+            None => elems.iter()
+                .try_fold(0, |max, &e| e.to_index().checked_add(1).map(|c| max.max(c)))
+                .ok_or_else(|| E::custom("element index out of range"))?,
+        };
+        // positions of elements must be representable by `T::Pos`, and both arrays must be
+        // allocatable, larger capacities can only come from malformed input
+        let max_capacity = T::Pos::MAX_POS.saturating_add(1);
+        if capacity > max_capacity
+            || Layout::array::<T>(capacity).is_err()
+            || Layout::array::<T::Pos>(capacity).is_err() {
+            return Err(E::custom(format_args!("set capacity {} out of range", capacity)));
+        }
+        let mut set = SparseSet::with_universe(capacity);
+        for e in elems {
+            if let Err(err) = set.try_insert(e) {
+                return Err(de::Error::custom(err));
+            }
+        }
+        Ok(set)
+    }
+
+    impl<'de, T, M> de::Deserialize<'de> for SparseSet<T, M>
+        where T: SparseIndex + de::Deserialize<'de>,
+              M: SparseInit
+    {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+            where D: de::Deserializer<'de>
+        {
+            let human_readable = deserializer.is_human_readable();
+            let visitor = SparseSetVisitor {
+                human_readable,
+                marker: PhantomData,
+            };
+            if human_readable {
+                deserializer.deserialize_any(visitor)
+            } else {
+                deserializer.deserialize_struct("SparseSet", FIELDS, visitor)
+            }
         }
     }
 }
@@ -252,7 +984,7 @@ fn layout<T>(size: usize) -> Layout {
 }
 
 #[inline]
-unsafe fn mem_alloc<T>(size: usize) -> NonNull<T> {
+pub(super) unsafe fn mem_alloc<T>(size: usize) -> NonNull<T> {
     if size == 0 {
         NonNull::dangling()
     } else {
@@ -267,13 +999,12 @@ unsafe fn mem_alloc<T>(size: usize) -> NonNull<T> {
 }
 
 #[inline]
-unsafe fn mem_realloc<T>(ptr: NonNull<T>, size: usize, new_size: usize) -> NonNull<T> {
+unsafe fn mem_alloc_zeroed<T>(size: usize) -> NonNull<T> {
     if size == 0 {
-        mem_alloc(new_size)
+        NonNull::dangling()
     } else {
         let l = layout::<T>(size);
-
-        let ptr = realloc(ptr.as_ptr() as *mut u8, l, layout::<T>(new_size).size());
+        let ptr = alloc_zeroed(l);
         if ptr.is_null() {
             handle_alloc_error(l);
         } else {
@@ -283,27 +1014,26 @@ unsafe fn mem_realloc<T>(ptr: NonNull<T>, size: usize, new_size: usize) -> NonNu
 }
 
 #[inline]
-unsafe fn mem_dealloc<T>(ptr: NonNull<T>, size: usize) {
-    if size > 0 {
+pub(super) unsafe fn mem_realloc<T>(ptr: NonNull<T>, size: usize, new_size: usize) -> NonNull<T> {
+    if size == 0 {
+        mem_alloc(new_size)
+    } else {
         let l = layout::<T>(size);
-        dealloc(ptr.as_ptr() as *mut u8, l);
-    }
-}
-
 
-#[inline]
-fn to_usize<T: TryFrom<usize> + TryInto<usize> + Copy>(value: T) -> usize {
-    match value.try_into() {
-        Ok(v) => v,
-        Err(_) => panic!("conversion failed"),
+        let ptr = realloc(ptr.as_ptr() as *mut u8, l, layout::<T>(new_size).size());
+        if ptr.is_null() {
+            handle_alloc_error(l);
+        } else {
+            NonNull::new_unchecked(ptr as *mut T)
+        }
     }
 }
 
 #[inline]
-fn to_value<T: TryFrom<usize> + TryInto<usize> + Copy>(value: usize) -> T {
-    match T::try_from(value) {
-        Ok(v) => v,
-        Err(_) => panic!("conversion failed"),
+pub(super) unsafe fn mem_dealloc<T>(ptr: NonNull<T>, size: usize) {
+    if size > 0 {
+        let l = layout::<T>(size);
+        dealloc(ptr.as_ptr() as *mut u8, l);
     }
 }
 
@@ -388,6 +1118,454 @@ mod tests {
         }
     }
 
+    /// Simple deterministic pseudo-random sequence, so that tests are repeatable.
+    fn lcg(seed: &mut u32) -> u32 {
+        *seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+        (*seed >> 16) & 0x7fff
+    }
+
+    #[test]
+    fn remove_matches_hash_set() {
+        let mut set = SparseSet::with_capacity(256);
+        let mut hset = HashSet::new();
+        let mut seed = 7;
+        for _ in 0..10000 {
+            let v = (lcg(&mut seed) % 256) as u16;
+            match lcg(&mut seed) % 3 {
+                0 => assert_eq!(set.remove(&v), hset.remove(&v)),
+                _ => {
+                    set.insert(v);
+                    hset.insert(v);
+                }
+            }
+            assert_eq!(set.len(), hset.len());
+        }
+        for v in 0u16..256 {
+            assert_eq!(set.contains(&v), hset.contains(&v));
+        }
+    }
+
+    #[test]
+    fn shift_remove_matches_hash_set_and_preserves_order() {
+        let mut set = SparseSet::with_capacity(256);
+        let mut hset = HashSet::new();
+        let mut vec = Vec::new();
+        let mut seed = 13;
+        for _ in 0..10000 {
+            let v = (lcg(&mut seed) % 256) as u16;
+            match lcg(&mut seed) % 3 {
+                0 => {
+                    assert_eq!(set.shift_remove(&v), hset.remove(&v));
+                    vec.retain(|&e| e != v);
+                }
+                _ => {
+                    set.insert(v);
+                    if hset.insert(v) {
+                        vec.push(v);
+                    }
+                }
+            }
+            assert_eq!(set, vec);
+        }
+        for v in 0u16..256 {
+            assert_eq!(set.contains(&v), hset.contains(&v));
+        }
+    }
+
+    #[test]
+    fn swap_remove_index_moves_last_element() {
+        let mut set = SparseSet::with_capacity(16);
+        for i in 0u8..5 {
+            set.insert(i);
+        }
+        assert_eq!(set.swap_remove_index(1), 1);
+        assert_eq!(set, vec![0u8, 4, 2, 3]);
+        assert_eq!(set.index_of(&4), Some(1));
+        assert_eq!(set.shift_remove_index(0), 0);
+        assert_eq!(set, vec![4u8, 2, 3]);
+        assert_eq!(set.index_of(&3), Some(2));
+        assert!(!set.contains(&1));
+        assert!(!set.contains(&0));
+    }
+
+    #[test]
+    fn pop_returns_elements_in_reverse_order() {
+        let mut set = SparseSet::with_capacity(16);
+        set.insert(3u32);
+        set.insert(1u32);
+        assert_eq!(set.pop(), Some(1));
+        assert!(!set.contains(&1));
+        assert_eq!(set.pop(), Some(3));
+        assert_eq!(set.pop(), None);
+        set.insert(1u32);
+        assert_eq!(set, vec![1u32]);
+    }
+
+    #[test]
+    fn try_insert_reports_out_of_range() {
+        let mut set = SparseSet::with_capacity(4);
+        assert_eq!(set.try_insert(3u8), Ok(true));
+        assert_eq!(set.try_insert(3u8), Ok(false));
+        let err = set.try_insert(4u8).unwrap_err();
+        assert_eq!(err.value(), 4);
+        assert_eq!(err.capacity(), 4);
+        assert_eq!(set, vec![3u8]);
+    }
+
+    #[test]
+    fn insert_grow_preserves_contents() {
+        let mut set = SparseSet::new();
+        let mut vec = Vec::new();
+        let mut seed = 3;
+        for _ in 0..2000 {
+            let v = lcg(&mut seed) % 5000;
+            if set.insert_grow(v) {
+                vec.push(v);
+            }
+        }
+        assert!(set.capacity() <= 2 * 5000);
+        assert_eq!(set, vec);
+        for v in 0..5000 {
+            assert_eq!(set.contains(&v), vec.contains(&v));
+        }
+    }
+
+    #[test]
+    fn reserve_universe_never_shrinks() {
+        let mut set = SparseSet::with_capacity(10);
+        set.insert(9usize);
+        set.reserve_universe(5);
+        assert_eq!(set.capacity(), 10);
+        set.reserve_universe(100);
+        assert_eq!(set.capacity(), 100);
+        assert!(set.contains(&9));
+        set.insert(99);
+        assert_eq!(set, vec![9usize, 99]);
+    }
+
+    #[test]
+    fn insert_grow_is_bounded_by_positions() {
+        let mut set = SparseSet::<u8>::new();
+        assert!(set.insert_grow(200));
+        assert_eq!(set.capacity(), 201);
+        assert!(set.insert_grow(255));
+        assert_eq!(set.capacity(), 256);
+        assert!(!set.insert_grow(200));
+        assert_eq!(set, vec![200u8, 255]);
+    }
+
+    #[test]
+    #[should_panic(expected = "value out of range of sparse storage")]
+    fn insert_grow_beyond_positions_panics() {
+        #[derive(Clone, Copy)]
+        struct Narrow(u32);
+
+        crate::sparse_index!(Narrow(u32) => u8);
+
+        let mut set = SparseSet::new();
+        set.insert_grow(Narrow(256));
+    }
+
+    #[test]
+    #[should_panic(expected = "value out of range of sparse storage")]
+    fn insert_grow_max_value_panics() {
+        SparseSet::<usize>::new().insert_grow(usize::MAX);
+    }
+
+    #[test]
+    #[should_panic(expected = "out of range of sparse storage")]
+    fn reserve_universe_beyond_positions_panics() {
+        SparseSet::<u8>::new().reserve_universe(257);
+    }
+
+    fn random_set(seed: &mut u32, capacity: usize, count: usize) -> (SparseSet<u32>, HashSet<u32>) {
+        let mut set = SparseSet::with_capacity(capacity);
+        let mut hset = HashSet::new();
+        for _ in 0..count {
+            let v = lcg(seed) % capacity as u32;
+            set.insert(v);
+            hset.insert(v);
+        }
+        (set, hset)
+    }
+
+    #[test]
+    fn set_algebra_matches_hash_set() {
+        let mut seed = 11;
+        for _ in 0..20 {
+            let (a, ha) = random_set(&mut seed, 300, 150);
+            let (b, hb) = random_set(&mut seed, 500, 200);
+
+            let expected: HashSet<u32> = ha.union(&hb).cloned().collect();
+            let mut s = a.clone();
+            s.union_with(&b);
+            assert_eq!(s.iter().cloned().collect::<HashSet<_>>(), expected);
+            assert_eq!(s.len(), expected.len());
+            assert_eq!(a.union(&b).collect::<Vec<_>>(), s.iter().cloned().collect::<Vec<_>>());
+
+            let expected: HashSet<u32> = ha.intersection(&hb).cloned().collect();
+            let mut s = a.clone();
+            s.intersect_with(&b);
+            assert_eq!(s.iter().cloned().collect::<HashSet<_>>(), expected);
+            assert_eq!(s.len(), expected.len());
+            assert_eq!(a.intersection(&b).collect::<Vec<_>>(), s.iter().cloned().collect::<Vec<_>>());
+
+            let expected: HashSet<u32> = ha.difference(&hb).cloned().collect();
+            let mut s = a.clone();
+            s.difference_with(&b);
+            assert_eq!(s.iter().cloned().collect::<HashSet<_>>(), expected);
+            assert_eq!(s.len(), expected.len());
+            assert_eq!(a.difference(&b).collect::<Vec<_>>(), s.iter().cloned().collect::<Vec<_>>());
+
+            let expected: HashSet<u32> = ha.symmetric_difference(&hb).cloned().collect();
+            let mut s = a.clone();
+            s.symmetric_difference_with(&b);
+            assert_eq!(s.iter().cloned().collect::<HashSet<_>>(), expected);
+            assert_eq!(s.len(), expected.len());
+            assert_eq!(a.symmetric_difference(&b).collect::<Vec<_>>(), s.iter().cloned().collect::<Vec<_>>());
+            for v in 0..500 {
+                assert_eq!(s.contains(&v), expected.contains(&v));
+            }
+        }
+    }
+
+    #[test]
+    fn set_algebra_preserves_receiver_order() {
+        let mut a = SparseSet::with_capacity(10);
+        let mut b = SparseSet::with_capacity(10);
+        for &v in [5u8, 1, 7, 3, 9].iter() {
+            a.insert(v);
+        }
+        for &v in [3u8, 2, 5, 8].iter() {
+            b.insert(v);
+        }
+        let mut s = a.clone();
+        s.intersect_with(&b);
+        assert_eq!(s, vec![5u8, 3]);
+        let mut s = a.clone();
+        s.difference_with(&b);
+        assert_eq!(s, vec![1u8, 7, 9]);
+        let mut s = a.clone();
+        s.union_with(&b);
+        assert_eq!(s, vec![5u8, 1, 7, 3, 9, 2, 8]);
+        let mut s = a.clone();
+        s.symmetric_difference_with(&b);
+        assert_eq!(s, vec![1u8, 7, 9, 2, 8]);
+    }
+
+    #[test]
+    fn safe_sparse_set_matches_hash_set() {
+        let mut set = SafeSparseSet::default();
+        let mut hset = HashSet::new();
+        let mut seed = 17;
+        for _ in 0..10000 {
+            let v = lcg(&mut seed) % 1000;
+            match lcg(&mut seed) % 3 {
+                0 => assert_eq!(set.remove(&v), hset.remove(&v)),
+                _ => assert_eq!(set.insert_grow(v), hset.insert(v)),
+            }
+        }
+        for v in 0..2000 {
+            assert_eq!(set.contains(&v), hset.contains(&v));
+        }
+        let copy = set.clone();
+        assert_eq!(copy, set);
+    }
+
+    #[test]
+    fn drain_empties_set() {
+        let mut set = SparseSet::with_capacity(16);
+        for &v in [4u8, 9, 1].iter() {
+            set.insert(v);
+        }
+        let drained: Vec<u8> = set.drain().collect();
+        assert_eq!(drained, vec![4, 9, 1]);
+        assert!(set.is_empty());
+        assert!(!set.contains(&9));
+        set.insert(9);
+        assert_eq!(set, vec![9u8]);
+    }
+
+    #[test]
+    fn into_iter_yields_insertion_order() {
+        let mut set = SafeSparseSet::with_universe(16);
+        for &v in [4u8, 9, 1, 7].iter() {
+            set.insert(v);
+        }
+        let mut iter = set.clone().into_iter();
+        assert_eq!(iter.len(), 4);
+        assert_eq!(iter.next(), Some(4));
+        assert_eq!(iter.next_back(), Some(7));
+        assert_eq!(iter.collect::<Vec<_>>(), vec![9, 1]);
+        let mut sum = 0;
+        for v in &set {
+            sum += v;
+        }
+        assert_eq!(sum, 21);
+    }
+
+    #[test]
+    fn sort_keeps_sparse_index_in_sync() {
+        let mut set = SparseSet::with_capacity(1000);
+        let mut seed = 31;
+        for _ in 0..500 {
+            set.insert(lcg(&mut seed) % 1000);
+        }
+        set.sort();
+        assert!(set.windows(2).all(|w| w[0] < w[1]));
+        for (i, v) in set.iter().enumerate() {
+            assert_eq!(set.index_of(v), Some(i));
+        }
+        set.sort_by_key(|&v| std::cmp::Reverse(v));
+        assert!(set.windows(2).all(|w| w[0] > w[1]));
+        for (i, v) in set.iter().enumerate() {
+            assert_eq!(set.index_of(v), Some(i));
+        }
+        let first = set[0];
+        assert!(set.remove(&first));
+        assert!(!set.contains(&first));
+    }
+
+    #[test]
+    fn retain_preserves_order() {
+        let mut set = SparseSet::with_capacity(100);
+        for v in (0u32..100).rev() {
+            set.insert(v);
+        }
+        set.retain(|v| v % 10 == 0);
+        assert_eq!(set, vec![90u32, 80, 70, 60, 50, 40, 30, 20, 10, 0]);
+        for v in 0..100 {
+            assert_eq!(set.contains(&v), v % 10 == 0);
+        }
+        assert_eq!(set.index_of(&0), Some(9));
+    }
+
+    #[test]
+    fn supports_non_zero_elements() {
+        use std::num::NonZeroU32;
+
+        let mut set = SparseSet::with_capacity(4);
+        for v in 1..5 {
+            set.insert(NonZeroU32::new(v).unwrap());
+        }
+        assert!(set.contains(&NonZeroU32::new(4).unwrap()));
+        assert!(set.remove(&NonZeroU32::new(1).unwrap()));
+        assert_eq!(set.len(), 3);
+        assert_eq!(<NonZeroU32 as SparseIndex>::from_index(3), NonZeroU32::new(4).unwrap());
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct Wide(u64);
+
+    crate::sparse_index!(Wide(u64) => u16);
+
+    #[test]
+    fn supports_newtype_with_narrow_positions() {
+        let mut set = SparseSet::with_capacity(1000);
+        let mut seed = 23;
+        for _ in 0..500 {
+            set.insert(Wide((lcg(&mut seed) % 1000) as u64));
+        }
+        for v in 0..1000 {
+            let w = Wide(v);
+            assert_eq!(set.contains(&w), set.iter().filter(|&&e| e == w).count() == 1);
+        }
+        assert_eq!(Wide::from_index(Wide(7).to_index()), Wide(7));
+    }
+
+    #[test]
+    #[should_panic(expected = "position out of range")]
+    fn narrow_positions_overflow_panics() {
+        #[derive(Clone, Copy)]
+        struct Narrow(u32);
+
+        crate::sparse_index!(Narrow(u32) => u8);
+
+        let mut set = SparseSet::with_capacity(300);
+        for v in 0..300 {
+            set.insert(Narrow(v));
+        }
+    }
+
+    #[cfg(feature = "serde_impl")]
+    #[test]
+    fn serde_json_round_trip_keeps_capacity() {
+        let mut set = SparseSet::with_capacity(10);
+        set.insert(5u32);
+        set.insert(0u32);
+        let json = serde_json::to_string(&set).unwrap();
+        assert_eq!(json, r#"{"capacity":10,"elements":[5,0]}"#);
+        let copy: SparseSet<u32> = serde_json::from_str(&json).unwrap();
+        assert_eq!(copy.capacity(), 10);
+        assert_eq!(copy, set);
+    }
+
+    #[cfg(feature = "serde_impl")]
+    #[test]
+    fn serde_json_accepts_plain_sequence() {
+        let set: SparseSet<u32> = serde_json::from_str("[0, 5]").unwrap();
+        assert_eq!(set.capacity(), 6);
+        assert_eq!(set, vec![0u32, 5]);
+        let set: SafeSparseSet<u32> = serde_json::from_str("[]").unwrap();
+        assert!(set.is_empty());
+    }
+
+    #[cfg(feature = "serde_impl")]
+    #[test]
+    fn serde_json_rejects_out_of_range_elements() {
+        let res: Result<SparseSet<u32>, _> = serde_json::from_str(r#"{"capacity":4,"elements":[1,4]}"#);
+        assert!(res.is_err());
+    }
+
+    #[cfg(feature = "serde_impl")]
+    #[test]
+    fn serde_rejects_huge_capacity() {
+        let json = format!(r#"{{"capacity":{},"elements":[]}}"#, u64::MAX);
+        assert!(serde_json::from_str::<SparseSet<u32>>(&json).is_err());
+        assert!(serde_json::from_str::<SparseSet<u64>>(&json).is_err());
+        let json = format!("[{}]", u64::MAX);
+        assert!(serde_json::from_str::<SparseSet<u64>>(&json).is_err());
+        let bytes = bincode::serialize(&(u64::MAX, Vec::<u16>::new())).unwrap();
+        assert!(bincode::deserialize::<SafeSparseSet<u16>>(&bytes).is_err());
+    }
+
+    #[cfg(feature = "serde_impl")]
+    #[test]
+    fn bincode_round_trip_keeps_capacity() {
+        let mut set = SafeSparseSet::with_universe(100);
+        for &e in [42u16, 7, 99, 0].iter() {
+            set.insert(e);
+        }
+        let bytes = bincode::serialize(&set).unwrap();
+        let copy: SafeSparseSet<u16> = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(copy.capacity(), 100);
+        assert_eq!(copy, set);
+    }
+
+    #[bench]
+    fn sparse_set_with_capacity_bench(b: &mut Bencher) {
+        b.iter(|| SparseSet::<u32>::with_capacity(65536));
+    }
+
+    #[bench]
+    fn safe_sparse_set_with_capacity_bench(b: &mut Bencher) {
+        b.iter(|| SafeSparseSet::<u32>::with_universe(65536));
+    }
+
+    #[bench]
+    fn safe_sparse_set_insert_bench(b: &mut Bencher) {
+        let mut set = SafeSparseSet::with_universe(1024);
+
+        b.iter(|| {
+            set.clear();
+            for i in 0u16..1024u16 {
+                if (i % 3) == 0 {
+                    set.insert(i);
+                }
+            }
+        });
+    }
+
     #[bench]
     fn sparse_set_insert_bench(b: &mut Bencher) {
         let mut set = SparseSet::with_capacity(1024);
@@ -449,6 +1627,25 @@ mod tests {
         });
     }
 
+    #[bench]
+    fn safe_sparse_set_contains_bench(b: &mut Bencher) {
+        let mut set = SafeSparseSet::with_universe(1024);
+        for i in 0u16..1024u16 {
+            if (i % 3) == 0 {
+                set.insert(i);
+            }
+        }
+
+        let mut count = 0;
+        b.iter(|| {
+            for i in 0u16..1024u16 {
+                if set.contains(&i) {
+                    count += 1;
+                }
+            }
+        });
+    }
+
     #[bench]
     fn hash_set_contains_bench(b: &mut Bencher) {
         let mut set = HashSet::with_capacity(1024);